@@ -0,0 +1,373 @@
+use crate::oauth::{self, OAuth2Config};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use base64::Engine;
+use eframe::egui;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+/// Authorization for the requests we send (not restty's own login).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    #[default]
+    None,
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer {
+        token: String,
+    },
+    ApiKey {
+        key: String,
+        value: String,
+        location: ApiKeyLocation,
+    },
+    Digest {
+        username: String,
+        password: String,
+    },
+    #[serde(rename = "oauth2")]
+    OAuth2(Box<OAuth2Config>),
+}
+
+impl Auth {
+    pub fn is_none(&self) -> bool {
+        matches!(self, Auth::None)
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Auth::None => "No auth",
+            Auth::Basic { .. } => "Basic",
+            Auth::Bearer { .. } => "Bearer token",
+            Auth::ApiKey { .. } => "API key",
+            Auth::Digest { .. } => "Digest",
+            Auth::OAuth2(_) => "OAuth 2.0",
+        }
+    }
+
+    /// Every text field, for `{{var}}` substitution and highlighting.
+    pub fn fields_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Auth::None => Vec::new(),
+            Auth::Basic { username, password } | Auth::Digest { username, password } => vec![username, password],
+            Auth::Bearer { token } => vec![token],
+            Auth::ApiKey { key, value, .. } => vec![key, value],
+            Auth::OAuth2(config) => config.fields_mut(),
+        }
+    }
+
+    /// The fields that hold credentials, which are kept out of the files
+    /// restty writes.
+    pub fn secrets_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Auth::None => Vec::new(),
+            Auth::Basic { password, .. } | Auth::Digest { password, .. } => vec![password],
+            Auth::Bearer { token } => vec![token],
+            Auth::ApiKey { value, .. } => vec![value],
+            Auth::OAuth2(config) => vec![&mut config.client_secret, &mut config.password, &mut config.refresh_token],
+        }
+    }
+
+    /// A copy with the credentials blanked, for history.
+    pub fn without_secrets(&self) -> Auth {
+        let mut auth = self.clone();
+        for secret in auth.secrets_mut() {
+            secret.clear();
+        }
+        auth
+    }
+}
+
+pub fn basic_authorization(username: &str, password: &str) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+    format!("Basic {}", encoded)
+}
+
+/// What the worker needs to answer a Digest challenge.
+pub struct DigestCredentials {
+    pub username: String,
+    pub password: String,
+    pub method: String,
+    pub url: String,
+}
+
+/// Splits `realm="a, b", nonce=xyz, qop="auth"` into pairs, honouring quotes.
+fn parse_challenge(params: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut chars = params.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        let key: String = std::iter::from_fn(|| chars.next_if(|c| *c != '=' && *c != ',')).collect();
+        if key.trim().is_empty() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        c => value.push(c),
+                    }
+                }
+            } else {
+                value = std::iter::from_fn(|| chars.next_if(|c| *c != ',')).collect::<String>().trim().to_string();
+            }
+        }
+        out.push((key.trim().to_ascii_lowercase(), value));
+    }
+    out
+}
+
+/// An RFC 7616 quoted-string: `"` and `\` are escaped with a backslash.
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn hex_hash(algorithm: &str, data: &str) -> String {
+    let bytes = if algorithm.starts_with("SHA-256") {
+        Sha256::digest(data.as_bytes()).to_vec()
+    } else {
+        Md5::digest(data.as_bytes()).to_vec()
+    };
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Builds the `Authorization` header answering a `WWW-Authenticate: Digest`
+/// challenge (RFC 7616), for MD5 and SHA-256 with or without `-sess`.
+pub fn digest_authorization(creds: &DigestCredentials, challenge: &str) -> Result<String, String> {
+    let mut cnonce_bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut cnonce_bytes);
+    let cnonce: String = cnonce_bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    answer_digest(creds, challenge, &cnonce)
+}
+
+fn answer_digest(creds: &DigestCredentials, challenge: &str, cnonce: &str) -> Result<String, String> {
+    let challenge = challenge.trim();
+    let params = challenge
+        .get(..6)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("digest"))
+        .map(|_| &challenge[6..])
+        .ok_or_else(|| "Not a Digest challenge".to_string())?;
+    let params = parse_challenge(params);
+    let get = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+
+    let realm = get("realm").unwrap_or("");
+    let nonce = get("nonce").ok_or_else(|| "Digest challenge has no nonce".to_string())?;
+    let algorithm = get("algorithm").unwrap_or("MD5").to_ascii_uppercase();
+    if !matches!(algorithm.as_str(), "MD5" | "MD5-SESS" | "SHA-256" | "SHA-256-SESS") {
+        return Err(format!("Unsupported Digest algorithm: {}", algorithm));
+    }
+    let qop = get("qop").map(|qop| qop.split(',').map(str::trim).any(|q| q == "auth"));
+
+    let uri = match reqwest::Url::parse(&creds.url) {
+        Ok(url) => match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        },
+        Err(_) => creds.url.clone(),
+    };
+
+    let nc = "00000001";
+
+    let mut ha1 = hex_hash(&algorithm, &format!("{}:{}:{}", creds.username, realm, creds.password));
+    if algorithm.ends_with("-SESS") {
+        ha1 = hex_hash(&algorithm, &format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = hex_hash(&algorithm, &format!("{}:{}", creds.method, uri));
+
+    let response = match qop {
+        Some(true) => hex_hash(&algorithm, &format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2)),
+        Some(false) => return Err("Server only offers Digest qop=auth-int, which isn't supported".to_string()),
+        None => hex_hash(&algorithm, &format!("{}:{}:{}", ha1, nonce, ha2)),
+    };
+
+    let mut header = format!(
+        "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response=\"{}\"",
+        quoted(&creds.username),
+        quoted(realm),
+        quoted(nonce),
+        quoted(&uri),
+        get("algorithm").unwrap_or("MD5"),
+        response
+    );
+    if qop.is_some() {
+        header.push_str(&format!(", qop=auth, nc={}, cnonce={}", nc, quoted(cnonce)));
+    }
+    if let Some(opaque) = get("opaque") {
+        header.push_str(&format!(", opaque={}", quoted(opaque)));
+    }
+    Ok(header)
+}
+
+/// The Auth section of the request editor. Returns true if anything changed.
+pub fn show_editor(ui: &mut egui::Ui, auth: &mut Auth) -> bool {
+    let mut changed = false;
+
+    egui::ComboBox::from_id_source("auth_type")
+        .selected_text(auth.label().to_string())
+        .width(160.0)
+        .show_ui(ui, |ui| {
+            let options = [
+                Auth::None,
+                Auth::Basic { username: String::new(), password: String::new() },
+                Auth::Bearer { token: String::new() },
+                Auth::ApiKey { key: String::new(), value: String::new(), location: ApiKeyLocation::Header },
+                Auth::Digest { username: String::new(), password: String::new() },
+                Auth::OAuth2(Box::default()),
+            ];
+            for option in options {
+                let selected = std::mem::discriminant(auth) == std::mem::discriminant(&option);
+                if ui.selectable_label(selected, option.label()).clicked() && !selected {
+                    *auth = option;
+                    changed = true;
+                }
+            }
+        });
+
+    let field = |ui: &mut egui::Ui, label: &str, value: &mut String, password: bool| {
+        ui.horizontal(|ui| {
+            ui.add_sized([90.0, 20.0], egui::Label::new(label));
+            ui.add(egui::TextEdit::singleline(value).password(password).desired_width(300.0)).changed()
+        })
+        .inner
+    };
+
+    match auth {
+        Auth::None => {}
+        Auth::Basic { username, password } | Auth::Digest { username, password } => {
+            changed |= field(ui, "Username", username, false);
+            changed |= field(ui, "Password", password, true);
+        }
+        Auth::Bearer { token } => {
+            changed |= field(ui, "Token", token, false);
+        }
+        Auth::ApiKey { key, value, location } => {
+            changed |= field(ui, "Key", key, false);
+            changed |= field(ui, "Value", value, false);
+            ui.horizontal(|ui| {
+                ui.add_sized([90.0, 20.0], egui::Label::new("Add to"));
+                changed |= ui.selectable_value(location, ApiKeyLocation::Header, "Header").changed();
+                changed |= ui.selectable_value(location, ApiKeyLocation::Query, "Query params").changed();
+            });
+        }
+        Auth::OAuth2(config) => {
+            changed |= oauth::show_editor(ui, config);
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mufasa(password: &str, url: &str) -> DigestCredentials {
+        DigestCredentials {
+            username: "Mufasa".to_string(),
+            password: password.to_string(),
+            method: "GET".to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    /// The `response=` value of a header.
+    fn response(header: &str) -> &str {
+        let start = header.find("response=\"").unwrap() + 10;
+        &header[start..start + header[start..].find('"').unwrap()]
+    }
+
+    #[test]
+    fn challenge_params() {
+        assert_eq!(
+            parse_challenge(r#"realm="a, b", nonce=xyz , qop="auth,auth-int",Opaque="q\"t",stale"#),
+            [
+                ("realm".to_string(), "a, b".to_string()),
+                ("nonce".to_string(), "xyz".to_string()),
+                ("qop".to_string(), "auth,auth-int".to_string()),
+                ("opaque".to_string(), "q\"t".to_string()),
+                ("stale".to_string(), String::new()),
+            ]
+        );
+    }
+
+    // RFC 7616 section 3.9.1
+    const RFC_7616_CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    fn rfc_7616_challenge(algorithm: &str) -> String {
+        format!(
+            r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm={}, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+            algorithm
+        )
+    }
+
+    #[test]
+    fn rfc_7616_md5() {
+        let creds = mufasa("Circle of Life", "http://www.example.org/dir/index.html");
+        let header = answer_digest(&creds, &rfc_7616_challenge("MD5"), RFC_7616_CNONCE).unwrap();
+        assert_eq!(
+            header,
+            r#"Digest username="Mufasa", realm="http-auth@example.org", nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", uri="/dir/index.html", algorithm=MD5, response="8ca523f5e9506fed4657c9700eebdbec", qop=auth, nc=00000001, cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#
+        );
+    }
+
+    #[test]
+    fn rfc_7616_sha256() {
+        let creds = mufasa("Circle of Life", "http://www.example.org/dir/index.html");
+        let header = answer_digest(&creds, &rfc_7616_challenge("SHA-256"), RFC_7616_CNONCE).unwrap();
+        assert_eq!(response(&header), "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1");
+        assert!(header.contains(", algorithm=SHA-256, "));
+
+        let header = answer_digest(&creds, &rfc_7616_challenge("SHA-256-sess"), RFC_7616_CNONCE).unwrap();
+        assert_eq!(response(&header), "2fd51b3a77ad75bad6afad6003e818d767133c46d9e2749e7f5232ae1ea3efd7");
+        assert!(header.contains(", algorithm=SHA-256-sess, "));
+    }
+
+    #[test]
+    fn rfc_2617_and_no_qop() {
+        let creds = mufasa("Circle Of Life", "http://www.nowhere.org/dir/index.html");
+        let challenge = r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#;
+        let header = answer_digest(&creds, challenge, "0a4f113b").unwrap();
+        assert_eq!(response(&header), "6629fae49393a05397450978507c4ef1");
+
+        let challenge = r#"Digest realm="testrealm@host.com", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093""#;
+        let header = answer_digest(&creds, challenge, "0a4f113b").unwrap();
+        assert_eq!(response(&header), "670fd8c2df070c60b045671b8b24ff02");
+        assert!(!header.contains("qop") && !header.contains("cnonce") && !header.contains("opaque"));
+    }
+
+    #[test]
+    fn quoted_strings_are_escaped() {
+        let creds = mufasa("x", "http://example.org/");
+        let header = answer_digest(&DigestCredentials { username: r#"a"b\c"#.to_string(), ..creds }, r#"Digest realm="r\"1", nonce="n""#, "c").unwrap();
+        assert!(header.starts_with(r#"Digest username="a\"b\\c", realm="r\"1", nonce="n", uri="/", "#));
+        // The header round-trips through the challenge parser
+        let params = parse_challenge(&header[6..]);
+        assert_eq!(params[0], ("username".to_string(), r#"a"b\c"#.to_string()));
+        assert_eq!(params[1], ("realm".to_string(), r#"r"1"#.to_string()));
+    }
+
+    #[test]
+    fn digest_errors() {
+        let creds = mufasa("x", "http://example.org/");
+        assert!(answer_digest(&creds, r#"Basic realm="a""#, "c").is_err());
+        assert!(answer_digest(&creds, r#"Digest realm="a""#, "c").is_err());
+        assert!(answer_digest(&creds, r#"Digest nonce="n", algorithm=SHA-512"#, "c").is_err());
+        assert!(answer_digest(&creds, r#"Digest nonce="n", qop="auth-int""#, "c").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use crate::find::{self, Find};
+use crate::{environments, key_value_editor, KeyValue};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+
+/// A `multipart/form-data` field. File fields hold a path in `value`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FormField {
+    pub enabled: bool,
+    pub key: String,
+    pub value: String,
+    #[serde(default)]
+    pub file: bool,
+}
+
+impl FormField {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// How the request body is edited and encoded. JSON, XML and raw text use
+/// the request's body text; the other modes carry their own data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BodyType {
+    #[default]
+    Json,
+    Xml,
+    Text {
+        content_type: String,
+    },
+    Form {
+        fields: Vec<KeyValue>,
+    },
+    Multipart {
+        fields: Vec<FormField>,
+    },
+    Binary {
+        path: String,
+    },
+}
+
+impl BodyType {
+    pub fn is_json(&self) -> bool {
+        matches!(self, BodyType::Json)
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            BodyType::Json => "JSON",
+            BodyType::Xml => "XML",
+            BodyType::Text { .. } => "Text",
+            BodyType::Form { .. } => "Form",
+            BodyType::Multipart { .. } => "Multipart",
+            BodyType::Binary { .. } => "Binary",
+        }
+    }
+
+    /// True for the modes edited as free text.
+    pub fn uses_text(&self) -> bool {
+        matches!(self, BodyType::Json | BodyType::Xml | BodyType::Text { .. })
+    }
+
+    /// Every text field except the body text, for `{{var}}` substitution.
+    pub fn fields_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            BodyType::Json | BodyType::Xml => Vec::new(),
+            BodyType::Text { content_type } => vec![content_type],
+            BodyType::Form { fields } => fields.iter_mut().flat_map(|f| [&mut f.key, &mut f.value]).collect(),
+            BodyType::Multipart { fields } => fields.iter_mut().flat_map(|f| [&mut f.key, &mut f.value]).collect(),
+            BodyType::Binary { path } => vec![path],
+        }
+    }
+
+    /// The body as it should be sent, after variable substitution. None if
+    /// there is nothing to send. Also returns the default Content-Type.
+    pub fn payload(&self, text: &str) -> Option<(Payload, String)> {
+        match self {
+            BodyType::Json | BodyType::Xml | BodyType::Text { .. } if text.is_empty() => None,
+            BodyType::Json => Some((Payload::Text(text.to_string()), "application/json".to_string())),
+            BodyType::Xml => Some((Payload::Text(text.to_string()), "application/xml".to_string())),
+            BodyType::Text { content_type } => {
+                let content_type = if content_type.trim().is_empty() { "text/plain" } else { content_type.trim() };
+                Some((Payload::Text(text.to_string()), content_type.to_string()))
+            }
+            BodyType::Form { fields } => {
+                let encoded = fields
+                    .iter()
+                    .filter(|f| f.enabled && !f.key.is_empty())
+                    .map(|f| format!("{}={}", form_encode(&f.key), form_encode(&f.value)))
+                    .collect::<Vec<_>>()
+                    .join("&");
+                (!encoded.is_empty()).then(|| (Payload::Text(encoded), "application/x-www-form-urlencoded".to_string()))
+            }
+            BodyType::Multipart { fields } => {
+                let fields: Vec<FormField> = fields.iter().filter(|f| f.enabled && !f.key.is_empty()).cloned().collect();
+                if fields.is_empty() {
+                    return None;
+                }
+                let boundary = format!("----restty{:x}", std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_nanos())
+                    .unwrap_or_default());
+                let content_type = format!("multipart/form-data; boundary={}", boundary);
+                Some((Payload::Multipart { boundary, fields }, content_type))
+            }
+            BodyType::Binary { path } if path.trim().is_empty() => None,
+            BodyType::Binary { path } => Some((Payload::File(path.trim().to_string()), "application/octet-stream".to_string())),
+        }
+    }
+}
+
+/// A resolved request body.
+pub enum Payload {
+    Text(String),
+    Multipart { boundary: String, fields: Vec<FormField> },
+    /// Streamed from disk when sent.
+    File(String),
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters.
+fn form_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+pub fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Escapes a `Content-Disposition` parameter the way browsers do, so a
+/// quote or line break can't end the value or the header early.
+fn disposition_param(s: &str) -> String {
+    s.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Encodes multipart fields, reading file fields from disk.
+pub fn encode_multipart(boundary: &str, fields: &[FormField]) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    for field in fields {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        let name = disposition_param(&field.key);
+        if field.file {
+            let content = std::fs::read(&field.value).map_err(|e| format!("Failed to read {}: {}", field.value, e))?;
+            body.extend_from_slice(
+                format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+                    name,
+                    disposition_param(&file_name(&field.value))
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(&content);
+        } else {
+            body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes());
+            body.extend_from_slice(field.value.as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    Ok(body)
+}
+
+/// The body text editor's id, so Cmd+F can tell whether it has focus.
+pub fn editor_id() -> egui::Id {
+    egui::Id::new("body_editor")
+}
+
+/// The body section: mode selector plus the editor for that mode. Returns
+/// true if anything changed.
+pub fn show_editor(ui: &mut egui::Ui, body_type: &mut BodyType, text: &mut String, vars: &[(String, String)], find: &mut Find) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        ui.label("Body:");
+        ui.add_space(10.0);
+        let options = [
+            BodyType::Json,
+            BodyType::Xml,
+            BodyType::Text { content_type: "text/plain".to_string() },
+            BodyType::Form { fields: vec![KeyValue::new()] },
+            BodyType::Multipart { fields: vec![FormField::new()] },
+            BodyType::Binary { path: String::new() },
+        ];
+        for option in options {
+            let selected = std::mem::discriminant(body_type) == std::mem::discriminant(&option);
+            if ui.selectable_label(selected, option.label()).clicked() && !selected {
+                *body_type = option;
+                changed = true;
+            }
+        }
+        if let BodyType::Text { content_type } = body_type {
+            ui.add_space(10.0);
+            changed |= ui
+                .add(egui::TextEdit::singleline(content_type).hint_text("Content-Type").desired_width(200.0))
+                .changed();
+        }
+    });
+
+    match body_type {
+        BodyType::Json | BodyType::Xml | BodyType::Text { .. } => {
+            if find.open {
+                find.show_bar(ui);
+                let mut hasher = DefaultHasher::new();
+                text.hash(&mut hasher);
+                find.update(hasher.finish(), |re| {
+                    re.find_iter(text).filter(|m| !m.is_empty()).take(find::MAX_MATCHES).enumerate().map(|(i, m)| (0, m.range(), i)).collect()
+                });
+            }
+            let marks = find.in_row(0);
+            let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                let mut job = environments::highlight(ui, text, vars, wrap_width, false);
+                find::mark(&mut job, &marks);
+                ui.fonts(|f| f.layout_job(job))
+            };
+            let hint = match body_type {
+                BodyType::Json => "JSON body...",
+                BodyType::Xml => "XML body...",
+                _ => "Body...",
+            };
+            let output = egui::TextEdit::multiline(text)
+                .id(editor_id())
+                .hint_text(hint)
+                .desired_width(ui.available_width() - 20.0)
+                .desired_rows(4)
+                .layouter(&mut layouter)
+                .show(ui);
+            changed |= output.response.changed();
+            if let Some((_, range)) = find.take_moved() {
+                let cursor = output.galley.from_ccursor(egui::text::CCursor::new(text[..range.start].chars().count()));
+                let rect = output.galley.pos_from_cursor(&cursor).translate(output.galley_pos.to_vec2());
+                ui.scroll_to_rect(rect, Some(egui::Align::Center));
+            }
+
+            if body_type.is_json() && !text.trim().is_empty() {
+                let error = cached(ui, "body_json_error", (&*text, vars), || {
+                    let substituted = environments::substitute(text, vars);
+                    serde_json::from_str::<serde_json::Value>(&substituted).err().map(|e| e.to_string())
+                });
+                if let Some(e) = error {
+                    ui.label(egui::RichText::new(format!("Invalid JSON: {}", e)).size(12.0).color(egui::Color32::from_rgb(255, 80, 80)));
+                }
+            }
+        }
+        BodyType::Form { fields } => {
+            changed |= key_value_editor(ui, "form_body_grid", fields, "Field", "+ Add field", true);
+        }
+        BodyType::Multipart { fields } => {
+            changed |= show_multipart_editor(ui, fields);
+        }
+        BodyType::Binary { path } => {
+            changed |= ui
+                .add(egui::TextEdit::singleline(path).hint_text("/path/to/file").desired_width(ui.available_width() - 20.0))
+                .changed();
+            if !path.trim().is_empty() {
+                let (message, color) = cached(ui, "body_binary_file", (&*path, vars), || {
+                    let path = environments::substitute(path.trim(), vars);
+                    match std::fs::metadata(&path) {
+                        Ok(meta) if meta.is_file() => (format!("{} bytes, sent as application/octet-stream", meta.len()), egui::Color32::GRAY),
+                        Ok(_) => ("Not a file".to_string(), egui::Color32::from_rgb(255, 80, 80)),
+                        Err(e) => (e.to_string(), egui::Color32::from_rgb(255, 80, 80)),
+                    }
+                });
+                ui.label(egui::RichText::new(message).size(12.0).color(color));
+            }
+        }
+    }
+
+    changed
+}
+
+/// Returns `compute()`'s result from the last frame unless `input` changed,
+/// so checks that parse the body or hit the disk don't run every frame.
+fn cached<T: Clone + Send + Sync + 'static>(ui: &egui::Ui, name: &str, input: impl Hash, compute: impl FnOnce() -> T) -> T {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    let key = hasher.finish();
+    let id = egui::Id::new(name);
+    if let Some((last, value)) = ui.data(|d| d.get_temp::<(u64, T)>(id)) {
+        if last == key {
+            return value;
+        }
+    }
+    let value = compute();
+    ui.data_mut(|d| d.insert_temp(id, (key, value.clone())));
+    value
+}
+
+fn show_multipart_editor(ui: &mut egui::Ui, fields: &mut Vec<FormField>) -> bool {
+    let mut changed = false;
+    let mut to_remove = None;
+    let value_width = (ui.available_width() - 360.0).max(150.0);
+
+    egui::Grid::new("multipart_body_grid").num_columns(5).spacing([8.0, 4.0]).show(ui, |ui| {
+        for (i, field) in fields.iter_mut().enumerate() {
+            changed |= ui.checkbox(&mut field.enabled, "").changed();
+            changed |= ui.add(egui::TextEdit::singleline(&mut field.key).hint_text("Field").desired_width(200.0)).changed();
+            egui::ComboBox::from_id_source(("multipart_kind", i))
+                .selected_text(if field.file { "File" } else { "Text" })
+                .width(60.0)
+                .show_ui(ui, |ui| {
+                    changed |= ui.selectable_value(&mut field.file, false, "Text").changed();
+                    changed |= ui.selectable_value(&mut field.file, true, "File").changed();
+                });
+            let hint = if field.file { "/path/to/file" } else { "Value" };
+            changed |= ui.add(egui::TextEdit::singleline(&mut field.value).hint_text(hint).desired_width(value_width)).changed();
+            if ui.small_button("×").clicked() {
+                to_remove = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+
+    if let Some(i) = to_remove {
+        fields.remove(i);
+        changed = true;
+    }
+
+    if ui.small_button("+ Add field").clicked() {
+        fields.push(FormField::new());
+        changed = true;
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multipart_names_cannot_break_headers() {
+        let field = FormField {
+            enabled: true,
+            key: "a\"b\r\nX-Injected: 1".to_string(),
+            value: "v".to_string(),
+            file: false,
+        };
+        let body = String::from_utf8(encode_multipart("xyz", &[field]).unwrap()).unwrap();
+        assert_eq!(body, "--xyz\r\nContent-Disposition: form-data; name=\"a%22b%0D%0AX-Injected: 1\"\r\n\r\nv\r\n--xyz--\r\n");
+        assert_eq!(disposition_param("new\nline\".txt"), "new%0Aline%22.txt");
+    }
+}
@@ -0,0 +1,455 @@
+use crate::body::{self, Payload};
+use crate::ResolvedRequest;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Language {
+    Curl,
+    Httpie,
+    Python,
+    JavaScript,
+    Go,
+    Rust,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::Curl,
+        Language::Httpie,
+        Language::Python,
+        Language::JavaScript,
+        Language::Go,
+        Language::Rust,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            Language::Curl => "cURL",
+            Language::Httpie => "HTTPie",
+            Language::Python => "Python requests",
+            Language::JavaScript => "JavaScript fetch",
+            Language::Go => "Go net/http",
+            Language::Rust => "Rust reqwest",
+        }
+    }
+}
+
+pub fn render(language: Language, req: &ResolvedRequest) -> String {
+    match language {
+        Language::Curl => curl(req),
+        Language::Httpie => httpie(req),
+        Language::Python => python(req),
+        Language::JavaScript => javascript(req),
+        Language::Go => go(req),
+        Language::Rust => rust(req),
+    }
+}
+
+/// Single-quotes for POSIX shells, where nothing inside is special except
+/// the quote itself.
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:@%+=,".contains(c)) {
+        return s.to_string();
+    }
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Headers to write out. Multipart bodies get their Content-Type (with
+/// boundary) from the client library instead.
+fn headers(req: &ResolvedRequest) -> Vec<&(String, String)> {
+    let multipart = matches!(req.body, Some(Payload::Multipart { .. }));
+    req.headers
+        .iter()
+        .filter(|(key, _)| !(multipart && key.eq_ignore_ascii_case("content-type")))
+        .collect()
+}
+
+/// A double-quoted literal with JSON escapes, which Python, JavaScript and
+/// Go all accept as a string literal.
+fn json_quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+fn curl(req: &ResolvedRequest) -> String {
+    // With -X HEAD curl would wait for a body that never comes
+    let method = if req.method == "HEAD" { "-I".to_string() } else { format!("-X {}", shell_quote(&req.method)) };
+    let mut parts = vec![format!("curl {} {}", method, shell_quote(&req.url))];
+    for (key, value) in headers(req) {
+        parts.push(format!("-H {}", shell_quote(&format!("{}: {}", key, value))));
+    }
+    match &req.body {
+        Some(Payload::Text(text)) => parts.push(format!("--data-raw {}", shell_quote(text))),
+        Some(Payload::Multipart { fields, .. }) => {
+            for field in fields {
+                if field.file {
+                    parts.push(format!("-F {}", shell_quote(&format!("{}=@{}", field.key, field.value))));
+                } else {
+                    parts.push(format!("--form-string {}", shell_quote(&format!("{}={}", field.key, field.value))));
+                }
+            }
+        }
+        Some(Payload::File(path)) => parts.push(format!("--data-binary {}", shell_quote(&format!("@{}", path)))),
+        None => {}
+    }
+    parts.join(" \\\n  ")
+}
+
+fn httpie(req: &ResolvedRequest) -> String {
+    let mut parts = vec![format!("http {} {}", shell_quote(&req.method), shell_quote(&req.url))];
+    if let Some(Payload::Multipart { .. }) = &req.body {
+        parts[0] = format!("http --multipart {} {}", shell_quote(&req.method), shell_quote(&req.url));
+    }
+    if let Some(Payload::Text(text)) = &req.body {
+        parts.push(format!("--raw {}", shell_quote(text)));
+    }
+    for (key, value) in headers(req) {
+        parts.push(shell_quote(&format!("{}:{}", key, value)));
+    }
+    match &req.body {
+        Some(Payload::Multipart { fields, .. }) => {
+            for field in fields {
+                let separator = if field.file { "@" } else { "=" };
+                parts.push(shell_quote(&format!("{}{}{}", field.key, separator, field.value)));
+            }
+        }
+        Some(Payload::File(path)) => parts.push(format!("< {}", shell_quote(path))),
+        Some(Payload::Text(_)) | None => {}
+    }
+    parts.join(" \\\n  ")
+}
+
+fn python(req: &ResolvedRequest) -> String {
+    let mut out = String::from("import requests\n\n");
+    out.push_str(&format!("url = {}\n", json_quote(&req.url)));
+
+    let headers = headers(req);
+    if !headers.is_empty() {
+        out.push_str("headers = {\n");
+        for (key, value) in &headers {
+            out.push_str(&format!("    {}: {},\n", json_quote(key), json_quote(value)));
+        }
+        out.push_str("}\n");
+    }
+
+    let mut args = String::new();
+    match &req.body {
+        Some(Payload::Text(text)) => {
+            // As a str, requests would encode it as Latin-1
+            out.push_str(&format!("data = {}.encode(\"utf-8\")\n", json_quote(text)));
+            args.push_str(", data=data");
+        }
+        Some(Payload::Multipart { fields, .. }) => {
+            // A list of tuples keeps repeated field names
+            out.push_str("files = [\n");
+            for field in fields {
+                if field.file {
+                    out.push_str(&format!(
+                        "    ({}, ({}, open({}, \"rb\"))),\n",
+                        json_quote(&field.key),
+                        json_quote(&body::file_name(&field.value)),
+                        json_quote(&field.value)
+                    ));
+                } else {
+                    out.push_str(&format!("    ({}, (None, {})),\n", json_quote(&field.key), json_quote(&field.value)));
+                }
+            }
+            out.push_str("]\n");
+            args.push_str(", files=files");
+        }
+        Some(Payload::File(path)) => {
+            out.push_str(&format!("data = open({}, \"rb\")\n", json_quote(path)));
+            args.push_str(", data=data");
+        }
+        None => {}
+    }
+
+    out.push_str(&format!("\nresponse = requests.request({}, url", json_quote(&req.method)));
+    if !headers.is_empty() {
+        out.push_str(", headers=headers");
+    }
+    out.push_str(&args);
+    out.push_str(")\n\nprint(response.status_code)\nprint(response.text)\n");
+    out
+}
+
+fn javascript(req: &ResolvedRequest) -> String {
+    let mut out = String::new();
+    let body = match &req.body {
+        Some(Payload::Text(text)) => Some(json_quote(text)),
+        Some(Payload::Multipart { fields, .. }) => {
+            if fields.iter().any(|field| field.file) {
+                out.push_str("import { openAsBlob } from \"node:fs\";\n\n");
+            }
+            out.push_str("const form = new FormData();\n");
+            for field in fields {
+                if field.file {
+                    out.push_str(&format!(
+                        "form.append({}, await openAsBlob({}), {});\n",
+                        json_quote(&field.key),
+                        json_quote(&field.value),
+                        json_quote(&body::file_name(&field.value))
+                    ));
+                } else {
+                    out.push_str(&format!("form.append({}, {});\n", json_quote(&field.key), json_quote(&field.value)));
+                }
+            }
+            out.push('\n');
+            Some("form".to_string())
+        }
+        Some(Payload::File(path)) => {
+            out.push_str("import { readFileSync } from \"node:fs\";\n\n");
+            Some(format!("readFileSync({})", json_quote(path)))
+        }
+        None => None,
+    };
+
+    out.push_str(&format!("const response = await fetch({}, {{\n", json_quote(&req.url)));
+    out.push_str(&format!("  method: {},\n", json_quote(&req.method)));
+
+    let headers = headers(req);
+    if !headers.is_empty() {
+        out.push_str("  headers: {\n");
+        for (key, value) in headers {
+            out.push_str(&format!("    {}: {},\n", json_quote(key), json_quote(value)));
+        }
+        out.push_str("  },\n");
+    }
+    if let Some(body) = body {
+        out.push_str(&format!("  body: {},\n", body));
+    }
+
+    out.push_str("});\n\nconsole.log(response.status);\nconsole.log(await response.text());\n");
+    out
+}
+
+fn go(req: &ResolvedRequest) -> String {
+    let mut imports = vec!["fmt", "io", "net/http"];
+    match &req.body {
+        Some(Payload::Text(_)) => imports.push("strings"),
+        Some(Payload::Multipart { fields, .. }) => {
+            imports.extend(["bytes", "mime/multipart"]);
+            if fields.iter().any(|field| field.file) {
+                imports.push("os");
+            }
+        }
+        Some(Payload::File(_)) => imports.push("os"),
+        None => {}
+    }
+    imports.sort();
+
+    let mut out = String::from("package main\n\nimport (\n");
+    for import in imports {
+        out.push_str(&format!("\t\"{}\"\n", import));
+    }
+    out.push_str(")\n\nfunc main() {\n");
+
+    let body = match &req.body {
+        Some(Payload::Text(text)) => {
+            out.push_str(&format!("\tbody := strings.NewReader({})\n", json_quote(text)));
+            "body"
+        }
+        Some(Payload::Multipart { fields, .. }) => {
+            out.push_str("\tbody := &bytes.Buffer{}\n\tform := multipart.NewWriter(body)\n");
+            for field in fields {
+                if field.file {
+                    out.push_str(&format!(
+                        "\tif part, err := form.CreateFormFile({}, {}); err == nil {{\n\t\tfile, err := os.Open({})\n\t\tif err != nil {{\n\t\t\tpanic(err)\n\t\t}}\n\t\tio.Copy(part, file)\n\t\tfile.Close()\n\t}}\n",
+                        json_quote(&field.key),
+                        json_quote(&body::file_name(&field.value)),
+                        json_quote(&field.value)
+                    ));
+                } else {
+                    out.push_str(&format!("\tform.WriteField({}, {})\n", json_quote(&field.key), json_quote(&field.value)));
+                }
+            }
+            out.push_str("\tform.Close()\n\n");
+            "body"
+        }
+        Some(Payload::File(path)) => {
+            out.push_str(&format!("\tbody, err := os.Open({})\n\tif err != nil {{\n\t\tpanic(err)\n\t}}\n\tdefer body.Close()\n\n", json_quote(path)));
+            "body"
+        }
+        None => "nil",
+    };
+    out.push_str(&format!("\treq, err := http.NewRequest({}, {}, {})\n", json_quote(&req.method), json_quote(&req.url), body));
+    out.push_str("\tif err != nil {\n\t\tpanic(err)\n\t}\n");
+
+    for (key, value) in headers(req) {
+        out.push_str(&format!("\treq.Header.Set({}, {})\n", json_quote(key), json_quote(value)));
+    }
+    if let Some(Payload::Multipart { .. }) = &req.body {
+        out.push_str("\treq.Header.Set(\"Content-Type\", form.FormDataContentType())\n");
+    }
+
+    out.push_str("\n\tresp, err := http.DefaultClient.Do(req)\n\tif err != nil {\n\t\tpanic(err)\n\t}\n\tdefer resp.Body.Close()\n\n");
+    out.push_str("\trespBody, _ := io.ReadAll(resp.Body)\n\tfmt.Println(resp.Status)\n\tfmt.Println(string(respBody))\n}\n");
+    out
+}
+
+fn rust(req: &ResolvedRequest) -> String {
+    // Debug formatting of a str is a valid Rust string literal
+    let mut out = String::from("fn main() -> Result<(), Box<dyn std::error::Error>> {\n");
+    if let Some(Payload::Multipart { fields, .. }) = &req.body {
+        out.push_str("    // Needs reqwest's \"multipart\" feature\n");
+        out.push_str("    let form = reqwest::blocking::multipart::Form::new()");
+        for field in fields {
+            if field.file {
+                out.push_str(&format!("\n        .file({:?}, {:?})?", field.key, field.value));
+            } else {
+                out.push_str(&format!("\n        .text({:?}, {:?})", field.key, field.value));
+            }
+        }
+        out.push_str(";\n\n");
+    }
+    out.push_str("    let client = reqwest::blocking::Client::new();\n");
+    out.push_str(&format!(
+        "    let response = client\n        .request(reqwest::Method::from_bytes({:?}.as_bytes())?, {:?})\n",
+        req.method, req.url
+    ));
+    for (key, value) in headers(req) {
+        out.push_str(&format!("        .header({:?}, {:?})\n", key, value));
+    }
+    match &req.body {
+        Some(Payload::Text(text)) => out.push_str(&format!("        .body({:?})\n", text)),
+        Some(Payload::Multipart { .. }) => out.push_str("        .multipart(form)\n"),
+        Some(Payload::File(path)) => out.push_str(&format!("        .body(std::fs::File::open({:?})?)\n", path)),
+        None => {}
+    }
+    out.push_str("        .send()?;\n\n");
+    out.push_str("    println!(\"{}\", response.status());\n    println!(\"{}\", response.text()?);\n    Ok(())\n}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::FormField;
+
+    fn request(method: &str, body: Option<Payload>) -> ResolvedRequest {
+        ResolvedRequest {
+            method: method.to_string(),
+            url: "https://example.com/a?b=c&d=it's".to_string(),
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body,
+            digest: None,
+            oauth: None,
+        }
+    }
+
+    fn multipart(file: bool) -> Option<Payload> {
+        let mut fields = vec![FormField {
+            enabled: true,
+            key: "name".to_string(),
+            value: "a \"b\"".to_string(),
+            file: false,
+        }];
+        if file {
+            fields.push(FormField {
+                enabled: true,
+                key: "upload".to_string(),
+                value: "/tmp/my file.png".to_string(),
+                file: true,
+            });
+        }
+        Some(Payload::Multipart {
+            boundary: "x".to_string(),
+            fields,
+        })
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(shell_quote("GET"), "GET");
+        assert_eq!(shell_quote("https://example.com/a"), "https://example.com/a");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(json_quote("plain"), "\"plain\"");
+        assert_eq!(json_quote("a \"b\"\\\n\t"), "\"a \\\"b\\\"\\\\\\n\\t\"");
+        assert_eq!(json_quote("\u{1}é"), "\"\\u0001é\"");
+    }
+
+    #[test]
+    fn shell_snippets_quote_the_method() {
+        let req = request("PURGE; rm -rf ~", None);
+        assert!(curl(&req).starts_with("curl -X 'PURGE; rm -rf ~' 'https://example.com/a?b=c&d=it'\\''s'"));
+        assert!(httpie(&req).starts_with("http 'PURGE; rm -rf ~' "));
+        let req = request("PURGE; rm -rf ~", multipart(false));
+        assert!(httpie(&req).starts_with("http --multipart 'PURGE; rm -rf ~' "));
+    }
+
+    #[test]
+    fn curl_head() {
+        assert!(curl(&request("HEAD", None)).starts_with("curl -I 'https://"));
+        assert!(curl(&request("GET", None)).starts_with("curl -X GET 'https://"));
+    }
+
+    #[test]
+    fn curl_bodies() {
+        let out = curl(&request("POST", multipart(true)));
+        assert!(out.contains("--form-string 'name=a \"b\"'"));
+        assert!(out.contains("-F 'upload=@/tmp/my file.png'"));
+        assert!(!out.contains("Content-Type"));
+        let out = curl(&request("PUT", Some(Payload::File("/tmp/a b".to_string()))));
+        assert!(out.contains("--data-binary '@/tmp/a b'"));
+    }
+
+    #[test]
+    fn httpie_bodies() {
+        let out = httpie(&request("POST", multipart(true)));
+        assert!(out.contains("'name=a \"b\"'"));
+        assert!(out.contains("'upload@/tmp/my file.png'"));
+        let out = httpie(&request("PUT", Some(Payload::File("/tmp/a b".to_string()))));
+        assert!(out.contains("< '/tmp/a b'"));
+    }
+
+    #[test]
+    fn python_bodies() {
+        let out = python(&request("POST", Some(Payload::Text("日本 \"é\"".to_string()))));
+        assert!(out.contains("data = \"日本 \\\"é\\\"\".encode(\"utf-8\")\n"));
+        assert!(out.contains(", data=data)"));
+
+        let out = python(&request("POST", multipart(true)));
+        assert!(out.contains("    (\"name\", (None, \"a \\\"b\\\"\")),\n"));
+        assert!(out.contains("    (\"upload\", (\"my file.png\", open(\"/tmp/my file.png\", \"rb\"))),\n"));
+        assert!(out.contains(", files=files)"));
+        let out = python(&request("PUT", Some(Payload::File("/tmp/a".to_string()))));
+        assert!(out.contains("data = open(\"/tmp/a\", \"rb\")\n"));
+    }
+
+    #[test]
+    fn javascript_bodies() {
+        let out = javascript(&request("POST", multipart(false)));
+        assert!(!out.contains("openAsBlob"));
+        assert!(out.contains("form.append(\"name\", \"a \\\"b\\\"\");\n"));
+        let out = javascript(&request("POST", multipart(true)));
+        assert!(out.starts_with("import { openAsBlob } from \"node:fs\";"));
+        assert!(out.contains("form.append(\"upload\", await openAsBlob(\"/tmp/my file.png\"), \"my file.png\");\n"));
+        let out = javascript(&request("PUT", Some(Payload::File("/tmp/a".to_string()))));
+        assert!(out.contains("  body: readFileSync(\"/tmp/a\"),\n"));
+    }
+
+    #[test]
+    fn go_bodies() {
+        let out = go(&request("POST", multipart(false)));
+        assert!(!out.contains("\"os\""));
+        assert!(out.contains("\tform.WriteField(\"name\", \"a \\\"b\\\"\")\n"));
+        let out = go(&request("POST", multipart(true)));
+        assert!(out.contains("\t\"os\"\n"));
+        assert!(out.contains("form.CreateFormFile(\"upload\", \"my file.png\")"));
+        assert!(out.contains("os.Open(\"/tmp/my file.png\")"));
+        let out = go(&request("PUT", Some(Payload::File("/tmp/a".to_string()))));
+        assert!(out.contains("\t\"os\"\n"));
+        assert!(out.contains("\tbody, err := os.Open(\"/tmp/a\")\n"));
+    }
+
+    #[test]
+    fn rust_bodies() {
+        let out = rust(&request("POST", multipart(true)));
+        assert!(out.contains("\n        .text(\"name\", \"a \\\"b\\\"\")"));
+        assert!(out.contains("\n        .file(\"upload\", \"/tmp/my file.png\")?"));
+        assert!(out.contains("        .multipart(form)\n"));
+        let out = rust(&request("PUT", Some(Payload::File("/tmp/a".to_string()))));
+        assert!(out.contains("        .body(std::fs::File::open(\"/tmp/a\")?)\n"));
+    }
+}
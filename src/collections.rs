@@ -0,0 +1,409 @@
+use crate::auth::Auth;
+use crate::body::BodyType;
+use crate::secrets::Vault;
+use crate::{config_file, method_color, KeyValue};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedRequest {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<KeyValue>,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default, skip_serializing_if = "BodyType::is_json")]
+    pub body_type: BodyType,
+    #[serde(default, skip_serializing_if = "Auth::is_none")]
+    pub auth: Auth,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Folder {
+    pub name: String,
+    #[serde(default)]
+    pub items: Vec<Node>,
+}
+
+impl SavedRequest {
+    pub fn stash_secrets(&mut self, vault: &mut Vault) {
+        for secret in self.auth.secrets_mut() {
+            vault.stash(secret);
+        }
+    }
+
+    pub fn restore_secrets(&mut self, vault: &Vault) {
+        for secret in self.auth.secrets_mut() {
+            vault.restore(secret);
+        }
+    }
+}
+
+impl Folder {
+    fn for_each_request(&mut self, f: &mut impl FnMut(&mut SavedRequest)) {
+        for item in &mut self.items {
+            match item {
+                Node::Folder(folder) => folder.for_each_request(f),
+                Node::Request(request) => f(request),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Node {
+    Folder(Folder),
+    Request(SavedRequest),
+}
+
+impl Node {
+    fn name(&self) -> &str {
+        match self {
+            Node::Folder(folder) => &folder.name,
+            Node::Request(request) => &request.name,
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        match self {
+            Node::Folder(folder) => &mut folder.name,
+            Node::Request(request) => &mut request.name,
+        }
+    }
+}
+
+/// Location of a node: the first index picks the collection, the rest walk
+/// down through folder items.
+pub type NodePath = Vec<usize>;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Collections {
+    pub collections: Vec<Folder>,
+}
+
+impl Collections {
+    pub fn load() -> Self {
+        let mut collections: Self = fs::read_to_string(config_file("collections.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        match Vault::load("collections") {
+            Ok(vault) => {
+                for folder in &mut collections.collections {
+                    folder.for_each_request(&mut |request| request.restore_secrets(&vault));
+                }
+            }
+            Err(e) => eprintln!("Failed to load collection secrets: {}", e),
+        }
+        collections
+    }
+
+    /// Credentials go to the secret store; the file only references them.
+    /// Nothing is written while the stored secrets can't be read, since that
+    /// would replace them.
+    pub fn save(&self) {
+        let mut vault = match Vault::load("collections") {
+            Ok(vault) => vault,
+            Err(e) => {
+                eprintln!("Not saving collections, their secrets can't be read: {}", e);
+                return;
+            }
+        };
+        let mut stashed = Collections {
+            collections: self.collections.clone(),
+        };
+        for folder in &mut stashed.collections {
+            folder.for_each_request(&mut |request| request.stash_secrets(&mut vault));
+        }
+        if let Ok(json) = serde_json::to_string_pretty(&stashed) {
+            vault.save("collections");
+            fs::write(config_file("collections.json"), json).ok();
+        }
+    }
+
+    /// Folder at `path`; a single-element path is a collection root.
+    pub fn folder_mut(&mut self, path: &[usize]) -> Option<&mut Folder> {
+        let (first, rest) = path.split_first()?;
+        let mut folder = self.collections.get_mut(*first)?;
+        for &i in rest {
+            folder = match folder.items.get_mut(i)? {
+                Node::Folder(child) => child,
+                Node::Request(_) => return None,
+            };
+        }
+        Some(folder)
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        let (last, parent) = path.split_last()?;
+        self.folder_mut(parent)?.items.get_mut(*last)
+    }
+
+    fn remove(&mut self, path: &[usize]) -> Option<Node> {
+        if path.len() == 1 {
+            return (path[0] < self.collections.len()).then(|| Node::Folder(self.collections.remove(path[0])));
+        }
+        let (last, parent) = path.split_last()?;
+        let folder = self.folder_mut(parent)?;
+        (*last < folder.items.len()).then(|| folder.items.remove(*last))
+    }
+
+    /// All folders as display paths like "API / Users", for the save dialog.
+    pub fn folder_paths(&self) -> Vec<(NodePath, String)> {
+        fn walk(folder: &Folder, path: NodePath, label: String, out: &mut Vec<(NodePath, String)>) {
+            out.push((path.clone(), label.clone()));
+            for (i, item) in folder.items.iter().enumerate() {
+                if let Node::Folder(child) = item {
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    walk(child, child_path, format!("{} / {}", label, child.name), out);
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        for (i, collection) in self.collections.iter().enumerate() {
+            walk(collection, vec![i], collection.name.clone(), &mut out);
+        }
+        out
+    }
+
+    /// Moves the node at `from` so it sits at `to`. Dropping onto a folder
+    /// puts the node inside it; dropping onto a request puts it just before.
+    fn move_node(&mut self, from: &[usize], to: &[usize]) {
+        if from.len() < 2 || to.is_empty() || to.starts_with(from) {
+            return;
+        }
+
+        let into_folder = to.len() == 1 || matches!(self.node_mut(to), Some(Node::Folder(_)));
+        let Some(node) = self.remove(from) else { return };
+
+        // Removing `from` shifts later siblings on the way to `to` up by one
+        let mut to = to.to_vec();
+        let depth = from.len() - 1;
+        if to.len() > depth && to[..depth] == from[..depth] && to[depth] > from[depth] {
+            to[depth] -= 1;
+        }
+
+        if into_folder {
+            if let Some(folder) = self.folder_mut(&to) {
+                folder.items.push(node);
+                return;
+            }
+        } else if let Some((last, parent)) = to.split_last() {
+            if let Some(folder) = self.folder_mut(parent) {
+                folder.items.insert((*last).min(folder.items.len()), node);
+                return;
+            }
+        }
+
+        // Target vanished, put it back at the end of its own collection
+        if let Some(collection) = self.collections.get_mut(from[0]) {
+            collection.items.push(node);
+        }
+    }
+
+    fn rename(&mut self, path: &[usize], name: String) {
+        if path.len() == 1 {
+            if let Some(collection) = self.collections.get_mut(path[0]) {
+                collection.name = name;
+            }
+        } else if let Some(node) = self.node_mut(path) {
+            *node.name_mut() = name;
+        }
+    }
+
+    fn add_folder(&mut self, path: &[usize]) {
+        if let Some(folder) = self.folder_mut(path) {
+            folder.items.push(Node::Folder(Folder {
+                name: "New folder".to_string(),
+                items: Vec::new(),
+            }));
+        }
+    }
+}
+
+/// What the tree wants the app to do after a frame.
+pub enum TreeAction {
+    Load(SavedRequest),
+}
+
+#[derive(Default)]
+pub struct TreeState {
+    renaming: Option<(NodePath, String)>,
+    new_collection_name: String,
+}
+
+/// Draws the collections tree and applies edits (rename, delete, reorder)
+/// in place, saving when something changed.
+pub fn show_tree(ui: &mut egui::Ui, collections: &mut Collections, state: &mut TreeState, green: egui::Color32) -> Option<TreeAction> {
+    let mut action = None;
+    let mut edits = Vec::new();
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut state.new_collection_name).hint_text("New collection").desired_width(200.0));
+        let name = state.new_collection_name.trim().to_string();
+        if ui.add_enabled(!name.is_empty(), egui::Button::new("Add")).clicked() {
+            collections.collections.push(Folder { name, items: Vec::new() });
+            state.new_collection_name.clear();
+            collections.save();
+        }
+    });
+    ui.add_space(5.0);
+
+    for (i, collection) in collections.collections.iter().enumerate() {
+        show_folder(ui, collection, vec![i], state, green, &mut action, &mut edits);
+    }
+
+    if collections.collections.is_empty() {
+        ui.label(egui::RichText::new("No collections yet. Use \"Save\" next to Send to add requests.").size(12.0).color(egui::Color32::GRAY));
+    }
+
+    if !edits.is_empty() {
+        for edit in edits {
+            match edit {
+                Edit::Move(from, to) => collections.move_node(&from, &to),
+                Edit::Rename(path, name) => collections.rename(&path, name),
+                Edit::Delete(path) => {
+                    collections.remove(&path);
+                }
+                Edit::AddFolder(path) => collections.add_folder(&path),
+            }
+        }
+        collections.save();
+    }
+
+    action
+}
+
+enum Edit {
+    Move(NodePath, NodePath),
+    Rename(NodePath, String),
+    Delete(NodePath),
+    AddFolder(NodePath),
+}
+
+/// Shows the rename field if `path` is being renamed. Returns true if it did.
+fn show_rename(ui: &mut egui::Ui, path: &NodePath, state: &mut TreeState, edits: &mut Vec<Edit>) -> bool {
+    let Some((renaming, name)) = &mut state.renaming else { return false };
+    if renaming != path {
+        return false;
+    }
+
+    let response = ui.add(egui::TextEdit::singleline(name).desired_width(180.0));
+    response.request_focus();
+    if response.lost_focus() {
+        if ui.input(|i| i.key_pressed(egui::Key::Enter)) && !name.trim().is_empty() {
+            edits.push(Edit::Rename(path.clone(), name.trim().to_string()));
+        }
+        state.renaming = None;
+    }
+    true
+}
+
+fn show_folder(
+    ui: &mut egui::Ui,
+    folder: &Folder,
+    path: NodePath,
+    state: &mut TreeState,
+    green: egui::Color32,
+    action: &mut Option<TreeAction>,
+    edits: &mut Vec<Edit>,
+) {
+    if show_rename(ui, &path, state, edits) {
+        return;
+    }
+
+    let drag_id = egui::Id::new(("collection_node", &path));
+    let header = egui::CollapsingHeader::new(&folder.name).id_source(drag_id);
+    let is_collection = path.len() == 1;
+
+    let response = if is_collection {
+        Some(header.show(ui, |ui| show_items(ui, folder, &path, state, green, action, edits)).header_response)
+    } else {
+        ui.dnd_drag_source(drag_id, path.clone(), |ui| {
+            header.show(ui, |ui| show_items(ui, folder, &path, state, green, action, edits))
+        })
+        .inner
+        .header_response
+        .into()
+    };
+
+    if let Some(response) = response {
+        if let Some(from) = response.dnd_release_payload::<NodePath>() {
+            edits.push(Edit::Move((*from).clone(), path.clone()));
+        }
+        response.context_menu(|ui| {
+            if ui.button("New folder").clicked() {
+                edits.push(Edit::AddFolder(path.clone()));
+                ui.close_menu();
+            }
+            if ui.button("Rename").clicked() {
+                state.renaming = Some((path.clone(), folder.name.clone()));
+                ui.close_menu();
+            }
+            if ui.button("Delete").clicked() {
+                edits.push(Edit::Delete(path.clone()));
+                ui.close_menu();
+            }
+        });
+    }
+}
+
+fn show_items(
+    ui: &mut egui::Ui,
+    folder: &Folder,
+    path: &NodePath,
+    state: &mut TreeState,
+    green: egui::Color32,
+    action: &mut Option<TreeAction>,
+    edits: &mut Vec<Edit>,
+) {
+    if folder.items.is_empty() {
+        ui.label(egui::RichText::new("Empty").size(11.0).color(egui::Color32::DARK_GRAY));
+    }
+
+    for (i, item) in folder.items.iter().enumerate() {
+        let mut item_path = path.clone();
+        item_path.push(i);
+
+        match item {
+            Node::Folder(child) => show_folder(ui, child, item_path, state, green, action, edits),
+            Node::Request(request) => {
+                if show_rename(ui, &item_path, state, edits) {
+                    continue;
+                }
+
+                let drag_id = egui::Id::new(("collection_node", &item_path));
+                let response = ui.dnd_drag_source(drag_id, item_path.clone(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(&request.method).size(11.0).color(method_color(&request.method, green)).strong());
+                        ui.selectable_label(false, item.name())
+                    })
+                    .inner
+                });
+
+                if response.inner.clicked() {
+                    *action = Some(TreeAction::Load(request.clone()));
+                }
+                if let Some(from) = response.response.dnd_release_payload::<NodePath>() {
+                    edits.push(Edit::Move((*from).clone(), item_path.clone()));
+                }
+                response.inner.context_menu(|ui| {
+                    if ui.button("Rename").clicked() {
+                        state.renaming = Some((item_path.clone(), request.name.clone()));
+                        ui.close_menu();
+                    }
+                    if ui.button("Delete").clicked() {
+                        edits.push(Edit::Delete(item_path.clone()));
+                        ui.close_menu();
+                    }
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::config_file;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const DEFAULT_SUPABASE_URL: &str = "https://drtejwkmjuwyqugpdspe.supabase.co";
+const DEFAULT_SUPABASE_ANON_KEY: &str = "sb_publishable_0zSJqibEWNDVan_BOpvJDg_yYMdp9lO";
+
+pub const USAGE: &str = "Usage: restty [--supabase-url <url>] [--supabase-anon-key <key>]
+
+Options:
+  --supabase-url <url>        Supabase (or PostgREST) base URL
+  --supabase-anon-key <key>   Supabase anon/publishable key
+  -h, --help                  Print this help
+
+Environment:
+  RESTTY_SUPABASE_URL, RESTTY_SUPABASE_ANON_KEY";
+
+/// Backend settings, resolved from (lowest to highest priority) the built-in
+/// defaults, `config.json` in the restty config folder, environment
+/// variables and command line flags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub supabase_url: String,
+    pub supabase_anon_key: String,
+    /// Environment variables and flags that override the file, for display.
+    #[serde(skip)]
+    pub overrides: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            supabase_url: DEFAULT_SUPABASE_URL.to_string(),
+            supabase_anon_key: DEFAULT_SUPABASE_ANON_KEY.to_string(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(args: &[String]) -> Result<Self, String> {
+        let mut config = Self::load_file();
+
+        if let Ok(url) = std::env::var("RESTTY_SUPABASE_URL") {
+            config.supabase_url = url;
+            config.overrides.push("RESTTY_SUPABASE_URL".to_string());
+        }
+        if let Ok(key) = std::env::var("RESTTY_SUPABASE_ANON_KEY") {
+            config.supabase_anon_key = key;
+            config.overrides.push("RESTTY_SUPABASE_ANON_KEY".to_string());
+        }
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || inline.clone().or_else(|| args.next().cloned()).ok_or_else(|| format!("{} needs a value", flag));
+            match flag {
+                "--supabase-url" => config.supabase_url = value()?,
+                "--supabase-anon-key" => config.supabase_anon_key = value()?,
+                // Launchers may pass arguments of their own, which shouldn't keep the app from starting
+                _ => {
+                    eprintln!("Ignoring unknown argument: {}", arg);
+                    continue;
+                }
+            }
+            config.overrides.push(flag.to_string());
+        }
+
+        config.supabase_url = config.supabase_url.trim().trim_end_matches('/').to_string();
+        Ok(config)
+    }
+
+    fn load_file() -> Self {
+        fs::read_to_string(config_file("config.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(config_file("config.json"), json).map_err(|e| e.to_string())
+    }
+}
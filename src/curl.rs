@@ -0,0 +1,503 @@
+use crate::auth::Auth;
+use crate::body::{BodyType, FormField};
+use crate::{query, KeyValue};
+
+/// A request parsed out of a `curl ...` command line.
+#[derive(Debug, Default)]
+pub struct CurlRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<KeyValue>,
+    pub body: String,
+    pub body_type: BodyType,
+    pub auth: Auth,
+    pub insecure: bool,
+}
+
+pub fn looks_like_curl(text: &str) -> bool {
+    let text = text.trim_start();
+    text.starts_with("curl ") || text.starts_with("curl\t") || text.starts_with("curl\\")
+}
+
+/// Splits a command line the way a POSIX shell would: single quotes,
+/// double quotes with backslash escapes, bash `$'...'` strings and
+/// backslash-newline continuations.
+pub fn tokenize(cmd: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = cmd.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some('\r') => {
+                    chars.next_if_eq(&'\n');
+                }
+                Some(next) => {
+                    current.push(next);
+                    in_token = true;
+                }
+                None => {}
+            },
+            '\'' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => current.push(ch),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch @ ('"' | '\\' | '$' | '`')) => current.push(ch),
+                            Some('\n') => {}
+                            Some(ch) => {
+                                current.push('\\');
+                                current.push(ch);
+                            }
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(ch) => current.push(ch),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => current.push('\n'),
+                            Some('t') => current.push('\t'),
+                            Some('r') => current.push('\r'),
+                            Some('0') => current.push('\0'),
+                            Some('x') => {
+                                let hex: String = (0..2).filter_map(|_| chars.next_if(|ch| ch.is_ascii_hexdigit())).collect();
+                                match u8::from_str_radix(&hex, 16) {
+                                    Ok(b) => current.push(b as char),
+                                    Err(_) => current.push_str("\\x"),
+                                }
+                            }
+                            Some('u') => {
+                                let hex: String = (0..4).filter_map(|_| chars.next_if(|ch| ch.is_ascii_hexdigit())).collect();
+                                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                                    Some(ch) => current.push(ch),
+                                    None => current.push_str("\\u"),
+                                }
+                            }
+                            Some(ch) => current.push(ch),
+                            None => return Err("Unterminated $'...' string".to_string()),
+                        },
+                        Some(ch) => current.push(ch),
+                        None => return Err("Unterminated $'...' string".to_string()),
+                    }
+                }
+            }
+            _ => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// Long options that take a value and that we don't use. Their value
+/// mustn't be mistaken for the URL, so this covers all of curl's.
+const IGNORED_WITH_VALUE: &[&str] = &[
+    "--abstract-unix-socket", "--alt-svc", "--aws-sigv4", "--cacert", "--capath", "--cert", "--cert-type",
+    "--ciphers", "--config", "--connect-timeout", "--connect-to", "--continue-at", "--cookie-jar",
+    "--create-file-mode", "--crlfile", "--curves", "--delegation", "--dns-interface", "--dns-ipv4-addr",
+    "--dns-ipv6-addr", "--dns-servers", "--doh-url", "--dump-header", "--ech", "--egd-file", "--engine",
+    "--etag-compare", "--etag-save", "--expect100-timeout", "--ftp-account", "--ftp-alternative-to-user",
+    "--ftp-method", "--ftp-port", "--ftp-ssl-ccc-mode", "--happy-eyeballs-timeout-ms", "--haproxy-clientip",
+    "--hostpubmd5", "--hostpubsha256", "--hsts", "--interface", "--ip-tos", "--ipfs-gateway", "--keepalive-cnt",
+    "--keepalive-time", "--key", "--key-type", "--krb", "--libcurl", "--limit-rate", "--local-port",
+    "--login-options", "--mail-auth", "--mail-from", "--mail-rcpt", "--max-filesize", "--max-redirs",
+    "--max-time", "--netrc-file", "--noproxy", "--output", "--output-dir", "--parallel-max", "--pass",
+    "--pinnedpubkey", "--preproxy", "--proto", "--proto-default", "--proto-redir", "--proxy", "--proxy-cacert",
+    "--proxy-capath", "--proxy-cert", "--proxy-cert-type", "--proxy-ciphers", "--proxy-crlfile",
+    "--proxy-header", "--proxy-key", "--proxy-key-type", "--proxy-pass", "--proxy-pinnedpubkey",
+    "--proxy-service-name", "--proxy-tls13-ciphers", "--proxy-tlsauthtype", "--proxy-tlspassword",
+    "--proxy-tlsuser", "--proxy-user", "--proxy1.0", "--pubkey", "--quote", "--random-file", "--range", "--rate",
+    "--request-target", "--resolve", "--retry", "--retry-delay", "--retry-max-time", "--sasl-authzid",
+    "--service-name", "--socks4", "--socks4a", "--socks5", "--socks5-gssapi-service", "--socks5-hostname",
+    "--speed-limit", "--speed-time", "--stderr", "--telnet-option", "--tftp-blksize", "--time-cond", "--tls-max",
+    "--tls13-ciphers", "--tlsauthtype", "--tlspassword", "--tlsuser", "--trace", "--trace-ascii",
+    "--trace-config", "--unix-socket", "--upload-file", "--url-query", "--variable", "--write-out",
+];
+
+/// Short options (after the dash) that take a value and that we don't use.
+const IGNORED_SHORT_WITH_VALUE: &[char] = &['o', 'm', 'w', 'x', 'E', 'c', 'T', 'K', 'U', 'r', 'y', 'Y', 'z', 'C', 'D', 'P', 'Q', 't'];
+
+const SHORT_WITH_VALUE: &[char] = &['X', 'H', 'd', 'u', 'F', 'A', 'e', 'b'];
+
+pub fn parse(cmd: &str) -> Result<CurlRequest, String> {
+    let tokens = tokenize(cmd)?;
+    let mut args = tokens.into_iter();
+    match args.next() {
+        Some(first) if first == "curl" => {}
+        _ => return Err("Command must start with curl".to_string()),
+    }
+
+    let mut req = CurlRequest::default();
+    let mut method = None;
+    let mut data: Vec<String> = Vec::new();
+    // A file sent as is, from `--data-binary @file` or a `-d @file` that isn't text
+    let mut binary: Option<String> = None;
+    let mut form = Vec::new();
+    let mut get = false;
+    let mut head = false;
+    let mut digest = false;
+
+    // Normalize "-XPOST", "-sSL" and "--data=..." into separate option/value pairs
+    let mut options: Vec<(String, Option<String>)> = Vec::new();
+    while let Some(arg) = args.next() {
+        if let Some(long) = arg.strip_prefix("--") {
+            if long.is_empty() {
+                // Everything after "--" is a URL
+                options.extend(args.by_ref().map(|rest| (String::new(), Some(rest))));
+                break;
+            }
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (format!("--{}", name), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            let takes_value = long_takes_value(&name);
+            let value = if takes_value { inline.or_else(|| args.next()) } else { None };
+            if takes_value && value.is_none() {
+                return Err(format!("Option {} needs a value", name));
+            }
+            options.push((name, value));
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            let flags: Vec<char> = arg[1..].chars().collect();
+            for (i, flag) in flags.iter().enumerate() {
+                if SHORT_WITH_VALUE.contains(flag) || IGNORED_SHORT_WITH_VALUE.contains(flag) {
+                    let rest: String = flags[i + 1..].iter().collect();
+                    let value = if rest.is_empty() { args.next() } else { Some(rest) };
+                    if value.is_none() {
+                        return Err(format!("Option -{} needs a value", flag));
+                    }
+                    options.push((format!("-{}", flag), value));
+                    break;
+                }
+                options.push((format!("-{}", flag), None));
+            }
+        } else {
+            options.push((String::new(), Some(arg)));
+        }
+    }
+
+    for (name, value) in options {
+        let value = value.unwrap_or_default();
+        match name.as_str() {
+            "" | "--url" if req.url.is_empty() => req.url = value,
+            "-X" | "--request" => method = Some(value),
+            "-H" | "--header" => {
+                if let Some((key, val)) = value.split_once(':') {
+                    req.headers.push(KeyValue {
+                        enabled: true,
+                        key: key.trim().to_string(),
+                        value: val.trim().to_string(),
+                    });
+                }
+            }
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii" => match value.strip_prefix('@') {
+                Some(path) if name == "--data-binary" => binary = Some(path.to_string()),
+                Some(path) if name != "--data-raw" => match read_text(path)? {
+                    Some(text) => data.push(text),
+                    None => binary = Some(path.to_string()),
+                },
+                _ => data.push(value),
+            },
+            "--data-urlencode" => data.push(urlencode_data(&value)?),
+            "--json" => {
+                data.push(value);
+                push_header_if_missing(&mut req.headers, "Content-Type", "application/json");
+                push_header_if_missing(&mut req.headers, "Accept", "application/json");
+            }
+            "-F" | "--form" | "--form-string" => {
+                let (key, val) = value.split_once('=').unwrap_or((value.as_str(), ""));
+                let mut field = FormField {
+                    enabled: true,
+                    key: key.to_string(),
+                    value: val.to_string(),
+                    file: false,
+                };
+                if name != "--form-string" {
+                    if let Some(spec) = val.strip_prefix('@') {
+                        // "@path;type=text/plain" - drop curl's extra attributes
+                        field.value = spec.split(';').next().unwrap_or(spec).to_string();
+                        field.file = true;
+                    } else if let Some(path) = val.strip_prefix('<') {
+                        // A field can only hold text, so other files are uploaded instead
+                        match read_text(path)? {
+                            Some(text) => field.value = text,
+                            None => {
+                                field.value = path.to_string();
+                                field.file = true;
+                            }
+                        }
+                    }
+                }
+                form.push(field);
+            }
+            "-u" | "--user" => {
+                let (username, password) = value.split_once(':').unwrap_or((value.as_str(), ""));
+                req.auth = Auth::Basic {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                };
+            }
+            "--oauth2-bearer" => req.auth = Auth::Bearer { token: value },
+            "--digest" => digest = true,
+            "-A" | "--user-agent" => push_header_if_missing(&mut req.headers, "User-Agent", &value),
+            "-e" | "--referer" => push_header_if_missing(&mut req.headers, "Referer", &value),
+            "-b" | "--cookie" => push_header_if_missing(&mut req.headers, "Cookie", &value),
+            "-k" | "--insecure" => req.insecure = true,
+            "-G" | "--get" => get = true,
+            "-I" | "--head" => head = true,
+            // The response is always decoded for us, nothing to do
+            "--compressed" => {}
+            _ => {}
+        }
+    }
+
+    if req.url.is_empty() {
+        return Err("No URL found in curl command".to_string());
+    }
+
+    if let (true, Auth::Basic { username, password }) = (digest, &req.auth) {
+        req.auth = Auth::Digest {
+            username: username.clone(),
+            password: password.clone(),
+        };
+    }
+
+    if binary.is_some() && (!data.is_empty() || !form.is_empty() || get) {
+        return Err("A binary @file body can't be combined with other data".to_string());
+    }
+
+    if get && !data.is_empty() {
+        let separator = if req.url.contains('?') { '&' } else { '?' };
+        req.url = format!("{}{}{}", req.url, separator, data.join("&"));
+    } else {
+        req.body = data.join("&");
+    }
+
+    req.method = match method {
+        Some(method) => method.to_uppercase(),
+        None if head => "HEAD".to_string(),
+        None if get => "GET".to_string(),
+        None if !req.body.is_empty() || !form.is_empty() || binary.is_some() => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+
+    if let Some(path) = binary {
+        req.body_type = BodyType::Binary { path };
+    } else if !form.is_empty() {
+        req.headers.retain(|h| !h.key.eq_ignore_ascii_case("content-type"));
+        req.body_type = BodyType::Multipart { fields: form };
+    } else if !req.body.is_empty() {
+        req.body_type = data_body_type(&mut req.headers, &req.body);
+        if matches!(req.body_type, BodyType::Form { .. }) {
+            req.body.clear();
+        }
+    }
+
+    Ok(req)
+}
+
+fn long_takes_value(name: &str) -> bool {
+    matches!(
+        name,
+        "--url" | "--request" | "--header" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii"
+            | "--data-urlencode" | "--json" | "--form" | "--form-string" | "--user" | "--user-agent"
+            | "--referer" | "--cookie" | "--oauth2-bearer"
+    ) || IGNORED_WITH_VALUE.contains(&name)
+}
+
+fn push_header_if_missing(headers: &mut Vec<KeyValue>, key: &str, value: &str) {
+    if !headers.iter().any(|h| h.key.eq_ignore_ascii_case(key)) {
+        headers.push(KeyValue {
+            enabled: true,
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+}
+
+/// Reads an `@file` argument. Returns None when the file isn't text.
+fn read_text(path: &str) -> Result<Option<String>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok(String::from_utf8(bytes).ok())
+}
+
+/// `--data-urlencode` accepts "content", "=content", "name=content",
+/// "@file" and "name@file"; only the content part gets encoded.
+fn urlencode_data(value: &str) -> Result<String, String> {
+    if let Some((name, content)) = value.split_once('=') {
+        if name.is_empty() {
+            return Ok(crate::query::encode(content));
+        }
+        return Ok(format!("{}={}", name, crate::query::encode(content)));
+    }
+    if let Some((name, path)) = value.split_once('@') {
+        let content = read_text(path)?.ok_or_else(|| format!("{} is not a text file", path))?;
+        if name.is_empty() {
+            return Ok(crate::query::encode(&content));
+        }
+        return Ok(format!("{}={}", name, crate::query::encode(&content)));
+    }
+    Ok(crate::query::encode(value))
+}
+
+/// Picks the body mode for `-d` data from the Content-Type header, which
+/// is dropped when the mode supplies it. curl sends `-d` as a form by default.
+fn data_body_type(headers: &mut Vec<KeyValue>, body: &str) -> BodyType {
+    let content_type = headers
+        .iter()
+        .find(|h| h.key.eq_ignore_ascii_case("content-type"))
+        .map(|h| h.value.clone());
+
+    match content_type {
+        Some(content_type) if content_type.contains("json") => BodyType::Json,
+        Some(content_type) if content_type.contains("xml") => BodyType::Xml,
+        Some(content_type) if !content_type.starts_with("application/x-www-form-urlencoded") => {
+            headers.retain(|h| !h.key.eq_ignore_ascii_case("content-type"));
+            BodyType::Text { content_type }
+        }
+        _ => {
+            headers.retain(|h| !h.key.eq_ignore_ascii_case("content-type"));
+            if body.split('&').all(|pair| pair.contains('=')) {
+                BodyType::Form {
+                    fields: query::parse_params(&format!("?{}", body)),
+                }
+            } else {
+                BodyType::Text {
+                    content_type: "application/x-www-form-urlencoded".to_string(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(req: &'a CurlRequest, key: &str) -> Option<&'a str> {
+        req.headers.iter().find(|h| h.key == key).map(|h| h.value.as_str())
+    }
+
+    /// A file in the temp directory, removed when dropped.
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("restty-curl-{}-{}", std::process::id(), name));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn quoting_and_escapes() {
+        assert_eq!(tokenize(r#"curl 'a b' "c \"d\" \$e \x" f\ g"#).unwrap(), ["curl", "a b", r#"c "d" $e \x"#, "f g"]);
+        assert_eq!(tokenize("curl 'it'\\''s' a''b \"\"").unwrap(), ["curl", "it's", "ab", ""]);
+        assert_eq!(tokenize(r"curl $'a\nb\t\x41\u00e9\'c'").unwrap(), ["curl", "a\nb\tAé'c"]);
+        assert_eq!(tokenize("curl \\\n  -k \\\r\n  url").unwrap(), ["curl", "-k", "url"]);
+        assert!(tokenize("curl 'open").is_err());
+        assert!(tokenize("curl \"open").is_err());
+        assert!(tokenize("curl $'open").is_err());
+    }
+
+    #[test]
+    fn bundled_short_flags() {
+        let req = parse("curl -sSLkXPUT -HAccept:\\ text/plain -uuser:pass https://example.com").unwrap();
+        assert_eq!(req.method, "PUT");
+        assert!(req.insecure);
+        assert_eq!(header(&req, "Accept"), Some("text/plain"));
+        assert_eq!(req.auth, Auth::Basic { username: "user".to_string(), password: "pass".to_string() });
+        assert_eq!(req.url, "https://example.com");
+
+        let req = parse("curl -sI --url=https://example.com").unwrap();
+        assert_eq!(req.method, "HEAD");
+        assert!(parse("curl https://example.com -X").is_err());
+    }
+
+    #[test]
+    fn data_from_files() {
+        let text = TempFile::new("body.json", b"{\"a\": 1}");
+        let req = parse(&format!("curl -H 'Content-Type: application/json' -d @{} https://example.com", text.path())).unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.body, "{\"a\": 1}");
+        assert_eq!(req.body_type, BodyType::Json);
+
+        // Not text, so it's sent as the file itself
+        let binary = TempFile::new("body.bin", &[0xff, 0x00, 0xfe]);
+        let req = parse(&format!("curl -d @{} https://example.com", binary.path())).unwrap();
+        assert_eq!(req.body_type, BodyType::Binary { path: binary.path().to_string() });
+
+        let req = parse(&format!("curl --data-binary @{} https://example.com", text.path())).unwrap();
+        assert_eq!(req.body_type, BodyType::Binary { path: text.path().to_string() });
+
+        let req = parse("curl --data-raw @literal https://example.com").unwrap();
+        assert_eq!(req.body, "@literal");
+        assert!(parse("curl -d @/no/such/file https://example.com").is_err());
+    }
+
+    #[test]
+    fn data_urlencode() {
+        let req = parse("curl -G --data-urlencode 'q=a b&c' --data-urlencode '=x=y' --data-urlencode z https://example.com/s?p=1").unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.url, "https://example.com/s?p=1&q=a%20b%26c&x%3Dy&z");
+
+        let file = TempFile::new("query.txt", b"1 + 1");
+        let req = parse(&format!("curl -G --data-urlencode sum@{} https://example.com", file.path())).unwrap();
+        assert_eq!(req.url, "https://example.com?sum=1%20%2B%201");
+    }
+
+    #[test]
+    fn unknown_options_keep_their_values() {
+        let req = parse(
+            "curl -D - --dump-header h.txt --retry-delay 2 -w '%{http_code}' --connect-to a:1:b:2 --url-query x=1 \\
+             --oauth2-bearer tok --compressed -v https://example.com/ok",
+        )
+        .unwrap();
+        assert_eq!(req.url, "https://example.com/ok");
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.auth, Auth::Bearer { token: "tok".to_string() });
+        assert!(parse("curl --dump-header h.txt").is_err());
+    }
+}
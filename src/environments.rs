@@ -0,0 +1,133 @@
+use crate::{config_file, KeyValue};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environment {
+    pub name: String,
+    pub variables: Vec<KeyValue>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Environments {
+    pub active: Option<String>,
+    pub environments: Vec<Environment>,
+}
+
+impl Environments {
+    pub fn load() -> Self {
+        fs::read_to_string(config_file("environments.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            fs::write(config_file("environments.json"), json).ok();
+        }
+    }
+
+    pub fn active(&self) -> Option<&Environment> {
+        let name = self.active.as_ref()?;
+        self.environments.iter().find(|env| &env.name == name)
+    }
+
+    /// Enabled variables of the active environment.
+    pub fn variables(&self) -> Vec<(String, String)> {
+        self.active()
+            .map(|env| {
+                env.variables
+                    .iter()
+                    .filter(|v| v.enabled && !v.key.trim().is_empty())
+                    .map(|v| (v.key.trim().to_string(), v.value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// A `{{name}}` placeholder found in a string, as a byte range plus the
+/// trimmed variable name.
+struct Placeholder<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+}
+
+fn placeholders(text: &str) -> Vec<Placeholder<'_>> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    while let Some(open) = text[offset..].find("{{") {
+        let start = offset + open;
+        match text[start + 2..].find("}}") {
+            Some(close) => {
+                let end = start + 2 + close + 2;
+                let name = text[start + 2..end - 2].trim();
+                if !name.is_empty() {
+                    found.push(Placeholder { start, end, name });
+                }
+                offset = end;
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+fn lookup<'a>(vars: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    vars.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
+
+/// Replaces every resolvable `{{name}}` in `text`; unknown ones are left as is.
+pub fn substitute(text: &str, vars: &[(String, String)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for p in placeholders(text) {
+        if let Some(value) = lookup(vars, p.name) {
+            out.push_str(&text[last..p.start]);
+            out.push_str(value);
+            last = p.end;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Names of placeholders in `text` that the variables can't resolve.
+pub fn unresolved(text: &str, vars: &[(String, String)]) -> Vec<String> {
+    placeholders(text)
+        .into_iter()
+        .filter(|p| lookup(vars, p.name).is_none())
+        .map(|p| p.name.to_string())
+        .collect()
+}
+
+/// Layout for text edits that colors resolved placeholders green and
+/// unresolved ones red.
+pub fn highlight(ui: &egui::Ui, text: &str, vars: &[(String, String)], wrap_width: f32, monospace: bool) -> egui::text::LayoutJob {
+    let font_id = if monospace {
+        egui::TextStyle::Monospace.resolve(ui.style())
+    } else {
+        egui::TextStyle::Body.resolve(ui.style())
+    };
+    let plain = egui::TextFormat::simple(font_id.clone(), ui.visuals().text_color());
+
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = wrap_width;
+
+    let mut last = 0;
+    for p in placeholders(text) {
+        job.append(&text[last..p.start], 0.0, plain.clone());
+        let color = if lookup(vars, p.name).is_some() {
+            egui::Color32::from_rgb(0, 200, 120)
+        } else {
+            egui::Color32::from_rgb(255, 80, 80)
+        };
+        job.append(&text[p.start..p.end], 0.0, egui::TextFormat::simple(font_id.clone(), color));
+        last = p.end;
+    }
+    job.append(&text[last..], 0.0, plain);
+    job
+}
@@ -0,0 +1,1163 @@
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// Applies a response filter. Expressions starting with `$` are JSONPath
+/// and give back an array of matches; anything else is read as a jq
+/// subset, whose outputs are collected into an array when there isn't
+/// exactly one.
+pub fn apply(expr: &str, value: &Value) -> Result<Value, String> {
+    let mut p = Parser::new(expr);
+    p.skip_ws();
+    if p.eat("$") {
+        let steps = p.path_steps()?;
+        p.end()?;
+        Ok(Value::Array(select(vec![value], &steps, value).into_iter().cloned().collect()))
+    } else {
+        let filter = p.pipe()?;
+        p.end()?;
+        let mut outputs = filter.eval(value)?;
+        if outputs.len() == 1 {
+            Ok(outputs.remove(0))
+        } else {
+            Ok(Value::Array(outputs))
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Self {
+        Self { chars: s.chars().collect(), pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn looking_at(&mut self, s: &str) -> bool {
+        self.skip_ws();
+        s.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.looking_at(s);
+        if found {
+            self.pos += s.chars().count();
+        }
+        found
+    }
+
+    /// Like `eat`, but only for a whole word, so `or` doesn't match `order`.
+    fn eat_word(&mut self, word: &str) -> bool {
+        self.skip_ws();
+        let end = self.pos + word.chars().count();
+        if self.looking_at(word) && !self.chars.get(end).is_some_and(|&c| is_ident_char(c)) {
+            self.pos = end;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), String> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", s)))
+        }
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_ws();
+        match self.peek() {
+            None => Ok(()),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.pos + 1)
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        self.skip_ws();
+        if !self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') {
+            return None;
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(is_ident_char) {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// A single- or double-quoted string with the usual escapes.
+    fn string(&mut self) -> Result<Option<String>, String> {
+        self.skip_ws();
+        let quote = match self.peek() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(Some(out));
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    out.push(match c {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    });
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        self.skip_ws();
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos = start;
+            return None;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E') {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().ok().or_else(|| {
+            self.pos = start;
+            None
+        })
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        match self.number() {
+            Some(n) if n.fract() == 0.0 => Ok(Some(n as i64)),
+            Some(_) => Err(self.error("expected an integer")),
+            None => Ok(None),
+        }
+    }
+
+    /// A literal shared by both syntaxes: number, string, true, false or null.
+    fn literal(&mut self) -> Result<Option<Value>, String> {
+        if let Some(n) = self.number() {
+            return Ok(Some(number(n)));
+        }
+        if let Some(s) = self.string()? {
+            return Ok(Some(Value::String(s)));
+        }
+        for (word, value) in [("true", Value::Bool(true)), ("false", Value::Bool(false)), ("null", Value::Null)] {
+            if self.eat_word(word) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whole floats come back as integers, so `length` and friends print `3`.
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        Value::from(n as i64)
+    } else {
+        serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn children(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(map) => map.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The value and everything below it, parents first.
+fn descendants(value: &Value) -> Vec<&Value> {
+    fn walk<'a>(value: &'a Value, out: &mut Vec<&'a Value>) {
+        out.push(value);
+        for child in children(value) {
+            walk(child, out);
+        }
+    }
+    let mut out = Vec::new();
+    walk(value, &mut out);
+    out
+}
+
+// JSONPath
+
+enum Selector {
+    Key(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, i64),
+    Wildcard,
+}
+
+enum Step {
+    Select(Vec<Selector>),
+    /// `..`: applies the step to every descendant.
+    Descend(Box<Step>),
+    Filter(Condition),
+}
+
+enum Condition {
+    Or(Box<Condition>, Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Compare(Operand, String, Operand),
+    Exists(Operand),
+}
+
+enum Operand {
+    /// `@...` when relative, `$...` when not.
+    Path(bool, Vec<Step>),
+    Literal(Value),
+}
+
+impl Parser {
+    fn path_steps(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = Vec::new();
+        loop {
+            if self.eat("..") {
+                let step = if self.looking_at("[") { self.bracket()? } else { Step::Select(vec![self.dot_selector()?]) };
+                steps.push(Step::Descend(Box::new(step)));
+            } else if self.eat(".") {
+                steps.push(Step::Select(vec![self.dot_selector()?]));
+            } else if self.looking_at("[") {
+                steps.push(self.bracket()?);
+            } else {
+                return Ok(steps);
+            }
+        }
+    }
+
+    fn dot_selector(&mut self) -> Result<Selector, String> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && !".[]()=!<>&|,'\"".contains(c)) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected a name"));
+        }
+        Ok(Selector::Key(self.chars[start..self.pos].iter().collect()))
+    }
+
+    fn bracket(&mut self) -> Result<Step, String> {
+        self.expect("[")?;
+        if self.eat("?") {
+            let parens = self.eat("(");
+            let condition = self.condition()?;
+            if parens {
+                self.expect(")")?;
+            }
+            self.expect("]")?;
+            return Ok(Step::Filter(condition));
+        }
+
+        let mut selectors = Vec::new();
+        loop {
+            if self.eat("*") {
+                selectors.push(Selector::Wildcard);
+            } else if let Some(key) = self.string()? {
+                selectors.push(Selector::Key(key));
+            } else {
+                let start = self.integer()?;
+                if self.eat(":") {
+                    let end = self.integer()?;
+                    let step = if self.eat(":") { self.integer()?.unwrap_or(1) } else { 1 };
+                    selectors.push(Selector::Slice(start, end, step));
+                } else {
+                    selectors.push(Selector::Index(start.ok_or_else(|| self.error("expected an index, name or '*'"))?));
+                }
+            }
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect("]")?;
+        Ok(Step::Select(selectors))
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let mut lhs = self.condition_and()?;
+        while self.eat("||") {
+            lhs = Condition::Or(Box::new(lhs), Box::new(self.condition_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn condition_and(&mut self) -> Result<Condition, String> {
+        let mut lhs = self.condition_unary()?;
+        while self.eat("&&") {
+            lhs = Condition::And(Box::new(lhs), Box::new(self.condition_unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn condition_unary(&mut self) -> Result<Condition, String> {
+        if self.eat("!") {
+            return Ok(Condition::Not(Box::new(self.condition_unary()?)));
+        }
+        if self.eat("(") {
+            let condition = self.condition()?;
+            self.expect(")")?;
+            return Ok(condition);
+        }
+        let lhs = self.operand()?;
+        for op in ["==", "!=", "<=", ">=", "<", ">"] {
+            if self.eat(op) {
+                return Ok(Condition::Compare(lhs, op.to_string(), self.operand()?));
+            }
+        }
+        Ok(Condition::Exists(lhs))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if self.eat("@") {
+            return Ok(Operand::Path(true, self.path_steps()?));
+        }
+        if self.eat("$") {
+            return Ok(Operand::Path(false, self.path_steps()?));
+        }
+        match self.literal()? {
+            Some(value) => Ok(Operand::Literal(value)),
+            None => Err(self.error("expected '@', '$' or a literal")),
+        }
+    }
+}
+
+fn select<'a>(mut nodes: Vec<&'a Value>, steps: &[Step], root: &'a Value) -> Vec<&'a Value> {
+    for step in steps {
+        nodes = nodes.into_iter().flat_map(|node| apply_step(node, step, root)).collect();
+    }
+    nodes
+}
+
+fn apply_step<'a>(node: &'a Value, step: &Step, root: &'a Value) -> Vec<&'a Value> {
+    match step {
+        Step::Select(selectors) => selectors.iter().flat_map(|selector| apply_selector(node, selector)).collect(),
+        Step::Descend(step) => descendants(node).into_iter().flat_map(|d| apply_step(d, step, root)).collect(),
+        Step::Filter(condition) => children(node).into_iter().filter(|child| condition.test(child, root)).collect(),
+    }
+}
+
+fn apply_selector<'a>(node: &'a Value, selector: &Selector) -> Vec<&'a Value> {
+    match (selector, node) {
+        (Selector::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+        (Selector::Index(i), Value::Array(items)) => {
+            let i = if *i < 0 { items.len() as i64 + i } else { *i };
+            usize::try_from(i).ok().and_then(|i| items.get(i)).into_iter().collect()
+        }
+        (Selector::Slice(start, end, step), Value::Array(items)) => {
+            slice_indices(items.len(), *start, *end, *step).into_iter().map(|i| &items[i]).collect()
+        }
+        (Selector::Wildcard, _) => children(node),
+        _ => Vec::new(),
+    }
+}
+
+/// Python-style slice bounds.
+fn slice_indices(len: usize, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let len = len as i64;
+    let clamp = |i: i64, low: i64, high: i64| if i < 0 { (len + i).max(low) } else { i.min(high) };
+    let mut out = Vec::new();
+    if step > 0 {
+        let (mut i, end) = (clamp(start.unwrap_or(0), 0, len), clamp(end.unwrap_or(len), 0, len));
+        while i < end {
+            out.push(i as usize);
+            let Some(next) = i.checked_add(step) else { break };
+            i = next;
+        }
+    } else if step < 0 {
+        let mut i = clamp(start.unwrap_or(len - 1), -1, len - 1);
+        let end = end.map(|e| clamp(e, -1, len - 1)).unwrap_or(-1);
+        while i > end {
+            out.push(i as usize);
+            let Some(next) = i.checked_add(step) else { break };
+            i = next;
+        }
+    }
+    out
+}
+
+impl Condition {
+    fn test(&self, current: &Value, root: &Value) -> bool {
+        match self {
+            Condition::Or(a, b) => a.test(current, root) || b.test(current, root),
+            Condition::And(a, b) => a.test(current, root) && b.test(current, root),
+            Condition::Not(c) => !c.test(current, root),
+            Condition::Exists(operand) => match operand {
+                Operand::Literal(value) => truthy(value),
+                _ => operand.resolve(current, root).is_some(),
+            },
+            Condition::Compare(a, op, b) => {
+                let (a, b) = (a.resolve(current, root), b.resolve(current, root));
+                let equal = match (a, b) {
+                    (Some(a), Some(b)) => compare(a, b).is_eq(),
+                    (a, b) => a.is_none() && b.is_none(),
+                };
+                match op.as_str() {
+                    "==" => equal,
+                    "!=" => !equal,
+                    _ => {
+                        let ordering = match (a, b) {
+                            (Some(Value::Number(x)), Some(Value::Number(y))) => x.as_f64().partial_cmp(&y.as_f64()),
+                            (Some(Value::String(x)), Some(Value::String(y))) => Some(x.cmp(y)),
+                            _ => None,
+                        };
+                        match ordering {
+                            Some(o) => match op.as_str() {
+                                "<" => o.is_lt(),
+                                "<=" => o.is_le(),
+                                ">" => o.is_gt(),
+                                _ => o.is_ge(),
+                            },
+                            None => false,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'a>(&'a self, current: &'a Value, root: &'a Value) -> Option<&'a Value> {
+        match self {
+            Operand::Literal(value) => Some(value),
+            Operand::Path(relative, steps) => {
+                let start = if *relative { current } else { root };
+                select(vec![start], steps, root).into_iter().next()
+            }
+        }
+    }
+}
+
+// jq
+
+enum Filter {
+    Identity,
+    Recurse,
+    Field(String),
+    Iterate,
+    Index(Box<Filter>, Box<Filter>),
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    Literal(Value),
+    Array(Option<Box<Filter>>),
+    Object(Vec<(ObjectKey, Option<Filter>)>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Alternative(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Compare(Box<Filter>, String, Box<Filter>),
+    Optional(Box<Filter>),
+    Call(String, Vec<Filter>),
+}
+
+enum ObjectKey {
+    Name(String),
+    Expr(Filter),
+}
+
+/// Builtins and how many arguments each takes.
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("select", 1),
+    ("map", 1),
+    ("has", 1),
+    ("sort_by", 1),
+    ("first", 1),
+    ("keys", 0),
+    ("length", 0),
+    ("not", 0),
+    ("type", 0),
+    ("first", 0),
+    ("last", 0),
+    ("add", 0),
+    ("sort", 0),
+    ("unique", 0),
+    ("reverse", 0),
+    ("min", 0),
+    ("max", 0),
+    ("to_entries", 0),
+    ("flatten", 0),
+    ("tostring", 0),
+    ("empty", 0),
+];
+
+impl Parser {
+    fn pipe(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.comma()?;
+        while self.eat("|") {
+            lhs = Filter::Pipe(Box::new(lhs), Box::new(self.comma()?));
+        }
+        Ok(lhs)
+    }
+
+    fn comma(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.alternative()?;
+        while self.eat(",") {
+            lhs = Filter::Comma(Box::new(lhs), Box::new(self.alternative()?));
+        }
+        Ok(lhs)
+    }
+
+    fn alternative(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.or()?;
+        while self.eat("//") {
+            lhs = Filter::Alternative(Box::new(lhs), Box::new(self.or()?));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.and()?;
+        while self.eat_word("or") {
+            lhs = Filter::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.comparison()?;
+        while self.eat_word("and") {
+            lhs = Filter::And(Box::new(lhs), Box::new(self.comparison()?));
+        }
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> Result<Filter, String> {
+        let lhs = self.postfix()?;
+        for op in ["==", "!=", "<=", ">=", "<", ">"] {
+            if self.eat(op) {
+                return Ok(Filter::Compare(Box::new(lhs), op.to_string(), Box::new(self.postfix()?)));
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn postfix(&mut self) -> Result<Filter, String> {
+        let mut term = self.term()?;
+        loop {
+            if self.looking_at("..") {
+                return Ok(term);
+            } else if self.eat(".") {
+                if self.looking_at("[") {
+                    continue;
+                }
+                let field = self.field_name()?;
+                term = Filter::Pipe(Box::new(term), Box::new(Filter::Field(field)));
+            } else if self.looking_at("[") {
+                term = self.index(term)?;
+            } else if self.eat("?") {
+                term = Filter::Optional(Box::new(term));
+            } else {
+                return Ok(term);
+            }
+        }
+    }
+
+    fn field_name(&mut self) -> Result<String, String> {
+        if let Some(name) = self.string()? {
+            return Ok(name);
+        }
+        self.ident().ok_or_else(|| self.error("expected a field name"))
+    }
+
+    /// `[]`, `[i]` or `[from:to]` after a term.
+    fn index(&mut self, term: Filter) -> Result<Filter, String> {
+        self.expect("[")?;
+        if self.eat("]") {
+            return Ok(Filter::Pipe(Box::new(term), Box::new(Filter::Iterate)));
+        }
+        let from = if self.looking_at(":") { None } else { Some(Box::new(self.pipe()?)) };
+        if self.eat(":") {
+            let to = if self.looking_at("]") { None } else { Some(Box::new(self.pipe()?)) };
+            self.expect("]")?;
+            return Ok(Filter::Slice(Box::new(term), from, to));
+        }
+        self.expect("]")?;
+        let index = from.ok_or_else(|| self.error("expected an index"))?;
+        Ok(Filter::Index(Box::new(term), index))
+    }
+
+    fn term(&mut self) -> Result<Filter, String> {
+        if self.eat("..") {
+            return Ok(Filter::Recurse);
+        }
+        if self.eat(".") {
+            self.skip_ws();
+            return match self.peek() {
+                Some('[') => self.index(Filter::Identity),
+                Some(c) if c == '"' || c.is_alphabetic() || c == '_' => Ok(Filter::Field(self.field_name()?)),
+                _ => Ok(Filter::Identity),
+            };
+        }
+        if let Some(value) = self.literal()? {
+            return Ok(Filter::Literal(value));
+        }
+        if self.eat("(") {
+            let inner = self.pipe()?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+        if self.eat("[") {
+            if self.eat("]") {
+                return Ok(Filter::Array(None));
+            }
+            let inner = self.pipe()?;
+            self.expect("]")?;
+            return Ok(Filter::Array(Some(Box::new(inner))));
+        }
+        if self.eat("{") {
+            return self.object();
+        }
+        if let Some(name) = self.ident() {
+            let mut args = Vec::new();
+            if self.eat("(") {
+                loop {
+                    args.push(self.pipe()?);
+                    if !self.eat(";") {
+                        break;
+                    }
+                }
+                self.expect(")")?;
+            }
+            if !FUNCTIONS.contains(&(name.as_str(), args.len())) {
+                return Err(self.error(&format!("unknown function {}/{}", name, args.len())));
+            }
+            return Ok(Filter::Call(name, args));
+        }
+        match self.peek() {
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of filter")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Filter, String> {
+        let mut entries = Vec::new();
+        if self.eat("}") {
+            return Ok(Filter::Object(entries));
+        }
+        loop {
+            let key = if self.eat("(") {
+                let key = self.pipe()?;
+                self.expect(")")?;
+                ObjectKey::Expr(key)
+            } else {
+                ObjectKey::Name(self.field_name()?)
+            };
+            let value = if self.eat(":") { Some(self.alternative()?) } else { None };
+            if value.is_none() && matches!(key, ObjectKey::Expr(_)) {
+                return Err(self.error("expected ':'"));
+            }
+            entries.push((key, value));
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect("}")?;
+        Ok(Filter::Object(entries))
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// jq's ordering: null < false < true < numbers < strings < arrays < objects.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64().partial_cmp(&y.as_f64()).unwrap_or(Ordering::Equal),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => x.iter().zip(y).map(|(x, y)| compare(x, y)).find(|o| o.is_ne()).unwrap_or(x.len().cmp(&y.len())),
+        (Value::Object(x), Value::Object(y)) => {
+            let keys = |m: &Map<String, Value>| {
+                let mut keys: Vec<String> = m.keys().cloned().collect();
+                keys.sort();
+                keys
+            };
+            keys(x).cmp(&keys(y)).then_with(|| {
+                keys(x).iter().map(|k| compare(&x[k], &y[k])).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn as_index(value: &Value) -> Result<i64, String> {
+    value.as_f64().map(|n| n as i64).ok_or_else(|| format!("Cannot use {} as an index", type_name(value)))
+}
+
+impl Filter {
+    fn eval(&self, input: &Value) -> Result<Vec<Value>, String> {
+        match self {
+            Filter::Identity => Ok(vec![input.clone()]),
+            Filter::Recurse => Ok(descendants(input).into_iter().cloned().collect()),
+            Filter::Field(name) => match input {
+                Value::Object(map) => Ok(vec![map.get(name).cloned().unwrap_or(Value::Null)]),
+                Value::Null => Ok(vec![Value::Null]),
+                other => Err(format!("Cannot index {} with \"{}\"", type_name(other), name)),
+            },
+            Filter::Iterate => match input {
+                Value::Array(items) => Ok(items.clone()),
+                Value::Object(map) => Ok(map.values().cloned().collect()),
+                other => Err(format!("Cannot iterate over {}", type_name(other))),
+            },
+            Filter::Index(term, index) => {
+                let mut out = Vec::new();
+                for target in term.eval(input)? {
+                    for index in index.eval(input)? {
+                        out.push(match (&target, &index) {
+                            (Value::Null, _) => Value::Null,
+                            (Value::Object(map), Value::String(key)) => map.get(key).cloned().unwrap_or(Value::Null),
+                            (Value::Array(items), Value::Number(_)) => {
+                                let i = as_index(&index)?;
+                                let i = if i < 0 { items.len() as i64 + i } else { i };
+                                usize::try_from(i).ok().and_then(|i| items.get(i)).cloned().unwrap_or(Value::Null)
+                            }
+                            _ => return Err(format!("Cannot index {} with {}", type_name(&target), type_name(&index))),
+                        });
+                    }
+                }
+                Ok(out)
+            }
+            Filter::Slice(term, from, to) => {
+                let bound = |f: &Option<Box<Filter>>| -> Result<Option<i64>, String> {
+                    match f {
+                        Some(f) => f.eval(input)?.first().map(as_index).transpose(),
+                        None => Ok(None),
+                    }
+                };
+                let (from, to) = (bound(from)?, bound(to)?);
+                term.eval(input)?
+                    .into_iter()
+                    .map(|target| match target {
+                        Value::Null => Ok(Value::Null),
+                        Value::Array(items) => Ok(Value::Array(slice_indices(items.len(), from, to, 1).into_iter().map(|i| items[i].clone()).collect())),
+                        Value::String(s) => {
+                            let chars: Vec<char> = s.chars().collect();
+                            Ok(Value::String(slice_indices(chars.len(), from, to, 1).into_iter().map(|i| chars[i]).collect()))
+                        }
+                        other => Err(format!("Cannot slice {}", type_name(&other))),
+                    })
+                    .collect()
+            }
+            Filter::Literal(value) => Ok(vec![value.clone()]),
+            Filter::Array(None) => Ok(vec![Value::Array(Vec::new())]),
+            Filter::Array(Some(inner)) => Ok(vec![Value::Array(inner.eval(input)?)]),
+            Filter::Object(entries) => {
+                let mut objects = vec![Map::new()];
+                for (key, value) in entries {
+                    let keys = match key {
+                        ObjectKey::Name(name) => vec![name.clone()],
+                        ObjectKey::Expr(f) => f
+                            .eval(input)?
+                            .into_iter()
+                            .map(|k| match k {
+                                Value::String(s) => Ok(s),
+                                other => Err(format!("Object keys must be strings, not {}", type_name(&other))),
+                            })
+                            .collect::<Result<_, _>>()?,
+                    };
+                    let values = match (value, key) {
+                        (Some(f), _) => f.eval(input)?,
+                        (None, ObjectKey::Name(name)) => Filter::Field(name.clone()).eval(input)?,
+                        (None, ObjectKey::Expr(_)) => Vec::new(),
+                    };
+                    let mut next = Vec::new();
+                    for object in &objects {
+                        for key in &keys {
+                            for value in &values {
+                                let mut object = object.clone();
+                                object.insert(key.clone(), value.clone());
+                                next.push(object);
+                            }
+                        }
+                    }
+                    objects = next;
+                }
+                Ok(objects.into_iter().map(Value::Object).collect())
+            }
+            Filter::Pipe(a, b) => {
+                let mut out = Vec::new();
+                for value in a.eval(input)? {
+                    out.extend(b.eval(&value)?);
+                }
+                Ok(out)
+            }
+            Filter::Comma(a, b) => {
+                let mut out = a.eval(input)?;
+                out.extend(b.eval(input)?);
+                Ok(out)
+            }
+            Filter::Alternative(a, b) => {
+                let found: Vec<Value> = a.eval(input).unwrap_or_default().into_iter().filter(truthy).collect();
+                if found.is_empty() {
+                    b.eval(input)
+                } else {
+                    Ok(found)
+                }
+            }
+            Filter::Or(a, b) | Filter::And(a, b) => {
+                let is_or = matches!(self, Filter::Or(..));
+                let mut out = Vec::new();
+                for lhs in a.eval(input)? {
+                    if truthy(&lhs) == is_or {
+                        out.push(Value::Bool(is_or));
+                        continue;
+                    }
+                    for rhs in b.eval(input)? {
+                        out.push(Value::Bool(truthy(&rhs)));
+                    }
+                }
+                Ok(out)
+            }
+            Filter::Compare(a, op, b) => {
+                let mut out = Vec::new();
+                for lhs in a.eval(input)? {
+                    for rhs in b.eval(input)? {
+                        let o = compare(&lhs, &rhs);
+                        out.push(Value::Bool(match op.as_str() {
+                            "==" => o.is_eq(),
+                            "!=" => o.is_ne(),
+                            "<" => o.is_lt(),
+                            "<=" => o.is_le(),
+                            ">" => o.is_gt(),
+                            _ => o.is_ge(),
+                        }));
+                    }
+                }
+                Ok(out)
+            }
+            Filter::Optional(inner) => Ok(inner.eval(input).unwrap_or_default()),
+            Filter::Call(name, args) => call(name, args, input),
+        }
+    }
+}
+
+fn call(name: &str, args: &[Filter], input: &Value) -> Result<Vec<Value>, String> {
+    let items = || match input {
+        Value::Array(items) => Ok(items.clone()),
+        other => Err(format!("{} needs an array, not {}", name, type_name(other))),
+    };
+    let one = |value: Value| Ok(vec![value]);
+
+    match (name, args) {
+        ("empty", _) => Ok(Vec::new()),
+        ("select", [f]) => Ok(if f.eval(input)?.iter().any(truthy) { vec![input.clone()] } else { Vec::new() }),
+        ("map", [f]) => {
+            let mut out = Vec::new();
+            for item in Filter::Iterate.eval(input)? {
+                out.extend(f.eval(&item)?);
+            }
+            one(Value::Array(out))
+        }
+        ("first", [f]) => Ok(f.eval(input)?.into_iter().take(1).collect()),
+        ("has", [f]) => f
+            .eval(input)?
+            .into_iter()
+            .map(|key| match (input, &key) {
+                (Value::Object(map), Value::String(key)) => Ok(Value::Bool(map.contains_key(key))),
+                (Value::Array(items), Value::Number(_)) => Ok(Value::Bool((0..items.len() as i64).contains(&as_index(&key)?))),
+                _ => Err(format!("Cannot check whether {} has a {} key", type_name(input), type_name(&key))),
+            })
+            .collect(),
+        ("sort_by", [f]) => {
+            let mut keyed = items()?.into_iter().map(|x| Ok((Value::Array(f.eval(&x)?), x))).collect::<Result<Vec<_>, String>>()?;
+            keyed.sort_by(|a, b| compare(&a.0, &b.0));
+            one(Value::Array(keyed.into_iter().map(|(_, x)| x).collect()))
+        }
+        ("keys", _) => match input {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                one(keys.into_iter().map(|k| Value::String(k.clone())).collect())
+            }
+            Value::Array(items) => one((0..items.len()).map(Value::from).collect()),
+            other => Err(format!("{} has no keys", type_name(other))),
+        },
+        ("length", _) => one(match input {
+            Value::Null => Value::from(0),
+            Value::Number(n) => number(n.as_f64().unwrap_or_default().abs()),
+            Value::String(s) => Value::from(s.chars().count()),
+            Value::Array(items) => Value::from(items.len()),
+            Value::Object(map) => Value::from(map.len()),
+            Value::Bool(_) => return Err("boolean has no length".to_string()),
+        }),
+        ("not", _) => one(Value::Bool(!truthy(input))),
+        ("type", _) => one(Value::String(type_name(input).to_string())),
+        ("first", _) => one(items()?.into_iter().next().unwrap_or(Value::Null)),
+        ("last", _) => one(items()?.pop().unwrap_or(Value::Null)),
+        ("reverse", _) => match input {
+            Value::String(s) => one(Value::String(s.chars().rev().collect())),
+            Value::Null => one(Value::Array(Vec::new())),
+            _ => one(Value::Array(items()?.into_iter().rev().collect())),
+        },
+        ("sort", _) => {
+            let mut items = items()?;
+            items.sort_by(compare);
+            one(Value::Array(items))
+        }
+        ("unique", _) => {
+            let mut items = items()?;
+            items.sort_by(compare);
+            items.dedup_by(|a, b| compare(a, b).is_eq());
+            one(Value::Array(items))
+        }
+        ("min", _) => one(items()?.into_iter().min_by(compare).unwrap_or(Value::Null)),
+        ("max", _) => one(items()?.into_iter().max_by(compare).unwrap_or(Value::Null)),
+        ("add", _) => {
+            let mut total = Value::Null;
+            for item in items()? {
+                total = match (total, item) {
+                    (Value::Null, item) => item,
+                    (total, Value::Null) => total,
+                    (Value::Number(a), Value::Number(b)) => number(a.as_f64().unwrap_or_default() + b.as_f64().unwrap_or_default()),
+                    (Value::String(a), Value::String(b)) => Value::String(a + &b),
+                    (Value::Array(mut a), Value::Array(b)) => {
+                        a.extend(b);
+                        Value::Array(a)
+                    }
+                    (Value::Object(mut a), Value::Object(b)) => {
+                        a.extend(b);
+                        Value::Object(a)
+                    }
+                    (a, b) => return Err(format!("Cannot add {} and {}", type_name(&a), type_name(&b))),
+                };
+            }
+            one(total)
+        }
+        ("to_entries", _) => match input {
+            Value::Object(map) => one(map
+                .iter()
+                .map(|(k, v)| serde_json::json!({ "key": k, "value": v }))
+                .collect()),
+            other => Err(format!("Cannot turn {} into entries", type_name(other))),
+        },
+        ("flatten", _) => {
+            fn flatten(items: Vec<Value>, out: &mut Vec<Value>) {
+                for item in items {
+                    match item {
+                        Value::Array(inner) => flatten(inner, out),
+                        other => out.push(other),
+                    }
+                }
+            }
+            let mut out = Vec::new();
+            flatten(items()?, &mut out);
+            one(Value::Array(out))
+        }
+        ("tostring", _) => one(match input {
+            Value::String(_) => input.clone(),
+            other => Value::String(other.to_string()),
+        }),
+        _ => Err(format!("unknown function {}/{}", name, args.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc() -> Value {
+        json!({
+            "store": {
+                "books": [
+                    { "title": "Dune", "price": 9.5, "tags": ["sf"], "author": "Herbert" },
+                    { "title": "Emma", "price": 4, "tags": [] },
+                    { "title": "Ubik", "price": 12, "tags": ["sf", "pkd"], "author": "Dick" }
+                ],
+                "open": true
+            },
+            "order": 3,
+            "empty": null
+        })
+    }
+
+    fn check(cases: &[(&str, Value)]) {
+        let doc = doc();
+        for (expr, expected) in cases {
+            match apply(expr, &doc) {
+                Ok(actual) => assert_eq!(&actual, expected, "{}", expr),
+                Err(e) => panic!("{}: {}", expr, e),
+            }
+        }
+    }
+
+    #[test]
+    fn jsonpath_paths() {
+        check(&[
+            ("$.order", json!([3])),
+            ("$.store.books[0].title", json!(["Dune"])),
+            ("$['store']['open']", json!([true])),
+            ("$.store.books[-1].title", json!(["Ubik"])),
+            ("$.store.books[*].price", json!([9.5, 4, 12])),
+            ("$.store.books[0,2].title", json!(["Dune", "Ubik"])),
+            ("$.store.books[1:].title", json!(["Emma", "Ubik"])),
+            ("$.store.books[::-1].title", json!(["Ubik", "Emma", "Dune"])),
+            ("$..author", json!(["Herbert", "Dick"])),
+            ("$..tags[0]", json!(["sf", "sf"])),
+            ("$.missing", json!([])),
+        ]);
+    }
+
+    #[test]
+    fn jsonpath_filters() {
+        check(&[
+            ("$.store.books[?(@.price > 5)].title", json!(["Dune", "Ubik"])),
+            ("$.store.books[?(@.author)].title", json!(["Dune", "Ubik"])),
+            ("$.store.books[?(!@.author)].title", json!(["Emma"])),
+            ("$.store.books[?(@.price < 5 || @.title == 'Ubik')].title", json!(["Emma", "Ubik"])),
+            ("$.store.books[?(@.author && @.price > 10)].title", json!(["Ubik"])),
+            ("$.store.books[?(@.price >= $.order)].title", json!(["Dune", "Emma", "Ubik"])),
+            ("$.store.books[?(@.title != \"Dune\")].price", json!([4, 12])),
+        ]);
+    }
+
+    #[test]
+    fn jq_paths() {
+        check(&[
+            (".", doc()),
+            (".order", json!(3)),
+            (".store.books[1].title", json!("Emma")),
+            (".store.books[-1].title", json!("Ubik")),
+            (".store.books[].title", json!(["Dune", "Emma", "Ubik"])),
+            (".store.books[:2] | length", json!(2)),
+            (".store[\"open\"]", json!(true)),
+            (".missing.deeper", json!(null)),
+            (".order?", json!(3)),
+            ("[.store.books[].price] | add", json!(25.5)),
+            (".store.books | map(.title) | sort | reverse", json!(["Ubik", "Emma", "Dune"])),
+            (".store.books | sort_by(.price) | first.title", json!("Emma")),
+            (".store.books[0] | keys", json!(["author", "price", "tags", "title"])),
+            ("{title: .store.books[0].title, order}", json!({ "title": "Dune", "order": 3 })),
+            ("[.store.books[].tags[]] | unique", json!(["pkd", "sf"])),
+            (".empty // \"default\"", json!("default")),
+            (".store.books[0].title, .order", json!(["Dune", 3])),
+        ]);
+    }
+
+    #[test]
+    fn jq_select() {
+        check(&[
+            (".store.books[] | select(.price > 5) | .title", json!(["Dune", "Ubik"])),
+            (".store.books[] | select(has(\"author\") | not) | .title", json!("Emma")),
+            ("[.store.books[] | select(.tags | length == 0)] | length", json!(1)),
+            (".store.books | map(select(.title == \"Ubik\")) | .[0].author", json!("Dick")),
+        ]);
+    }
+
+    #[test]
+    fn jq_or_and() {
+        check(&[
+            (".order or .empty", json!(true)),
+            (".empty or .empty", json!(false)),
+            (".order and .store.open", json!(true)),
+            (".order and .empty", json!(false)),
+            (".store.books[] | select(.price < 5 or .title == \"Ubik\") | .title", json!(["Emma", "Ubik"])),
+            (".store.books[] | select(.author and .price > 10) | .title", json!("Ubik")),
+            // Words that only start with a keyword are still field names
+            (".order", json!(3)),
+            ("{order} | .order", json!(3)),
+        ]);
+    }
+
+    #[test]
+    fn huge_slice_steps() {
+        check(&[
+            ("$.store.books[1::9223372036854775807].title", json!(["Emma"])),
+            ("$.store.books[-1::-9223372036854775807].title", json!(["Ubik"])),
+            ("$.store.books[9223372036854775807::-2].title", json!(["Ubik", "Dune"])),
+            (".store.books[-9223372036854775807:] | length", json!(3)),
+        ]);
+        assert_eq!(slice_indices(3, Some(1), None, i64::MAX), [1]);
+        assert_eq!(slice_indices(3, None, None, i64::MIN), [2]);
+    }
+
+    #[test]
+    fn words_after_whitespace() {
+        for (input, word, found) in [(" or .b", "or", true), ("  and x", "and", true), (" order", "or", false), ("or", "or", true)] {
+            let mut p = Parser::new(input);
+            assert_eq!(p.eat_word(word), found, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let doc = doc();
+        for (expr, message) in [
+            ("$.store.books[", "expected an index"),
+            ("$.store.books[?(@.price >)]", "expected '@', '$' or a literal"),
+            (".store |", "unexpected end of filter"),
+            (".order | nope", "unknown function nope/0"),
+            (".order.x", "Cannot index number with \"x\""),
+            (".order[]", "Cannot iterate over number"),
+            ("[.order, \"x\"] | add", "Cannot add number and string"),
+            (".store.books[0] | sort", "sort needs an array"),
+            ("\"unterminated", "unterminated string"),
+            (".order )", "unexpected ')'"),
+        ] {
+            match apply(expr, &doc) {
+                Ok(value) => panic!("{} should fail, got {}", expr, value),
+                Err(e) => assert!(e.contains(message), "{}: {}", expr, e),
+            }
+        }
+    }
+}
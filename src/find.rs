@@ -0,0 +1,231 @@
+use eframe::egui;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// Counting stops here, so a one-letter query on a huge body stays quick.
+pub const MAX_MATCHES: usize = 100_000;
+
+/// A match, or the part of one on a single row: the row, the byte range
+/// within it and which match it belongs to. Matches can span rows.
+pub type Match = (usize, Range<usize>, usize);
+
+/// A find bar's query, options and matches. The owner decides what a row
+/// is and does the searching through `update`.
+pub struct Find {
+    pub open: bool,
+    id: egui::Id,
+    query: String,
+    case_sensitive: bool,
+    whole_word: bool,
+    regex: bool,
+    error: String,
+    /// Sorted by row, then position.
+    matches: Vec<Match>,
+    count: usize,
+    current: usize,
+    /// Query, options and content version the matches were found for.
+    searched: Option<(String, bool, bool, bool, u64)>,
+    focus: bool,
+    /// Set when the current match changed and should be scrolled to.
+    moved: bool,
+}
+
+impl Find {
+    pub fn new(name: &str) -> Self {
+        Self {
+            open: false,
+            id: egui::Id::new(name),
+            query: String::new(),
+            case_sensitive: false,
+            whole_word: false,
+            regex: false,
+            error: String::new(),
+            matches: Vec::new(),
+            count: 0,
+            current: 0,
+            searched: None,
+            focus: false,
+            moved: false,
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.focus = true;
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+        self.matches.clear();
+        self.count = 0;
+        self.searched = None;
+        self.error.clear();
+    }
+
+    pub fn has_focus(&self, ctx: &egui::Context) -> bool {
+        ctx.memory(|m| m.has_focus(self.id))
+    }
+
+    /// Searches again if the query, options or `version` of the content
+    /// changed. `search` gets the compiled pattern and returns matches in
+    /// row order.
+    pub fn update(&mut self, version: u64, search: impl FnOnce(&Regex) -> Vec<Match>) {
+        let key = (self.query.clone(), self.case_sensitive, self.whole_word, self.regex, version);
+        if !self.open || self.searched.as_ref() == Some(&key) {
+            return;
+        }
+        // Jump to the first match while typing, but not when the content
+        // changes underneath
+        let query_changed = self.searched.as_ref().is_none_or(|s| (&s.0, s.1, s.2, s.3) != (&key.0, key.1, key.2, key.3));
+        self.searched = Some(key);
+        self.error.clear();
+        self.matches.clear();
+        self.count = 0;
+        if self.query.is_empty() {
+            return;
+        }
+
+        match self.pattern() {
+            Ok(re) => {
+                self.matches = search(&re);
+                self.matches.retain(|m| m.2 < MAX_MATCHES);
+                self.count = self.matches.last().map_or(0, |m| m.2 + 1);
+                if query_changed {
+                    self.current = 0;
+                    self.moved = true;
+                }
+                self.current = self.current.min(self.count.saturating_sub(1));
+            }
+            Err(e) => self.error = e,
+        }
+    }
+
+    fn pattern(&self) -> Result<Regex, String> {
+        let mut pattern = if self.regex { self.query.clone() } else { regex::escape(&self.query) };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|e| e.to_string().lines().last().unwrap_or_default().trim().to_string())
+    }
+
+    fn step(&mut self, forward: bool) {
+        if self.count == 0 {
+            return;
+        }
+        let len = self.count;
+        self.current = if forward { (self.current + 1) % len } else { (self.current + len - 1) % len };
+        self.moved = true;
+    }
+
+    /// Where the current match starts, once after it changes.
+    pub fn take_moved(&mut self) -> Option<(usize, Range<usize>)> {
+        if !std::mem::take(&mut self.moved) {
+            return None;
+        }
+        let first = self.matches.partition_point(|m| m.2 < self.current);
+        self.matches.get(first).map(|(row, range, _)| (*row, range.clone()))
+    }
+
+    /// Matches on `row`, each flagged if it's the current one.
+    pub fn in_row(&self, row: usize) -> Vec<(Range<usize>, bool)> {
+        if !self.open {
+            return Vec::new();
+        }
+        let start = self.matches.partition_point(|m| m.0 < row);
+        self.matches[start..]
+            .iter()
+            .take_while(|m| m.0 == row)
+            .map(|(_, range, index)| (range.clone(), *index == self.current))
+            .collect()
+    }
+
+    /// The bar itself: query, options, match count and navigation. Enter
+    /// and Shift+Enter step through matches, Esc closes.
+    pub fn show_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut self.query).id(self.id).hint_text("Find").desired_width(220.0));
+            if std::mem::take(&mut self.focus) {
+                response.request_focus();
+            }
+            if response.lost_focus() {
+                if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    let forward = !ui.input(|i| i.modifiers.shift);
+                    self.step(forward);
+                    response.request_focus();
+                } else if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    self.close();
+                    return;
+                }
+            }
+
+            ui.toggle_value(&mut self.case_sensitive, "Aa").on_hover_text("Match case");
+            ui.toggle_value(&mut self.whole_word, "W").on_hover_text("Whole word");
+            ui.toggle_value(&mut self.regex, ".*").on_hover_text("Regular expression");
+
+            if !self.error.is_empty() {
+                ui.label(egui::RichText::new(&self.error).size(12.0).color(egui::Color32::from_rgb(255, 80, 80)));
+            } else if self.count == 0 {
+                if !self.query.is_empty() {
+                    ui.label(egui::RichText::new("No matches").size(12.0).color(egui::Color32::GRAY));
+                }
+            } else {
+                let more = if self.count == MAX_MATCHES { "+" } else { "" };
+                ui.label(egui::RichText::new(format!("{} of {}{}", self.current + 1, self.count, more)).size(12.0).color(egui::Color32::GRAY));
+            }
+
+            if ui.small_button("Prev").on_hover_text("Shift+Enter").clicked() {
+                self.step(false);
+            }
+            if ui.small_button("Next").on_hover_text("Enter").clicked() {
+                self.step(true);
+            }
+            if ui.small_button("×").on_hover_text("Close (Esc)").clicked() {
+                self.close();
+            }
+        });
+    }
+}
+
+/// Gives the marked byte ranges of `job.text` a highlighted background,
+/// splitting sections where needed. Ranges that don't fit the text, as
+/// after an edit not yet searched, are ignored.
+pub fn mark(job: &mut egui::text::LayoutJob, marks: &[(Range<usize>, bool)]) {
+    let text = &job.text;
+    let marks: Vec<&(Range<usize>, bool)> = marks
+        .iter()
+        .filter(|(r, _)| r.end <= text.len() && text.is_char_boundary(r.start) && text.is_char_boundary(r.end))
+        .collect();
+    if marks.is_empty() {
+        return;
+    }
+
+    let mut sections = Vec::with_capacity(job.sections.len() + marks.len() * 2);
+    for section in job.sections.drain(..) {
+        let (start, end) = (section.byte_range.start, section.byte_range.end);
+        let mut cuts = vec![start, end];
+        cuts.extend(marks.iter().flat_map(|(r, _)| [r.start, r.end]).filter(|&b| b > start && b < end));
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for pair in cuts.windows(2) {
+            let mut piece = section.clone();
+            piece.byte_range = pair[0]..pair[1];
+            if pair[0] != start {
+                piece.leading_space = 0.0;
+            }
+            if let Some((_, current)) = marks.iter().find(|(r, _)| r.start <= pair[0] && pair[1] <= r.end) {
+                if *current {
+                    piece.format.background = egui::Color32::from_rgb(255, 180, 0);
+                    piece.format.color = egui::Color32::BLACK;
+                } else {
+                    piece.format.background = egui::Color32::from_rgb(90, 80, 20);
+                }
+            }
+            sections.push(piece);
+        }
+    }
+    job.sections = sections;
+}
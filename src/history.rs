@@ -0,0 +1,533 @@
+use crate::config::Config;
+use crate::{config_file, HistoryItem};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const MAX_HISTORY_ITEMS: usize = 100;
+/// Deadline for each call to Supabase, so a sync with an unreachable
+/// server gives up rather than hanging.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where request history lives. Items are returned newest first.
+pub trait HistoryStore {
+    fn load(&self) -> Result<Vec<HistoryItem>, String>;
+    /// Stores the item and returns it as stored, with its id filled in.
+    fn save(&self, item: HistoryItem) -> Result<HistoryItem, String>;
+    fn delete(&self, id: &str) -> Result<(), String>;
+}
+
+/// History kept on disk as one JSON item per line, oldest first. Each
+/// account has its own file, so nobody sees another user's history.
+pub struct LocalHistory {
+    path: PathBuf,
+    user_id: String,
+}
+
+impl LocalHistory {
+    /// The history of `user_id`, or of no account when it's empty.
+    pub fn new(user_id: &str) -> Self {
+        let shared = Self {
+            path: config_file("history.jsonl"),
+            user_id: user_id.to_string(),
+        };
+        if user_id.is_empty() {
+            return shared;
+        }
+        let local = Self {
+            path: config_file(&format!("history-{}.jsonl", user_id)),
+            user_id: user_id.to_string(),
+        };
+        // Accounts used to share the one file; take over this user's items
+        if !local.path.exists() {
+            let items = shared.read_all();
+            if !items.is_empty() {
+                local.write_all(&items).ok();
+            }
+        }
+        local
+    }
+
+    fn read_all(&self) -> Vec<HistoryItem> {
+        fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str::<HistoryItem>(line).ok())
+            .filter(|item| item.user_id == self.user_id)
+            .collect()
+    }
+
+    /// Rewrites the file. Credentials are dropped on the way, which also
+    /// clears them from items saved before history stopped keeping them.
+    fn write_all(&self, items: &[HistoryItem]) -> Result<(), String> {
+        let mut data = String::new();
+        for item in items {
+            let item = HistoryItem {
+                auth: item.auth.without_secrets(),
+                ..item.clone()
+            };
+            data.push_str(&serde_json::to_string(&item).map_err(|e| e.to_string())?);
+            data.push('\n');
+        }
+        fs::write(&self.path, data).map_err(|e| e.to_string())
+    }
+
+    /// Replaces items by id. Returns the ids of the replacements for items
+    /// that were deleted meanwhile.
+    fn replace(&self, items: &[(String, HistoryItem)]) -> Result<Vec<String>, String> {
+        let mut all = self.read_all();
+        let mut replaced = false;
+        let mut deleted = Vec::new();
+        for (id, item) in items {
+            match all.iter_mut().find(|existing| existing.id.as_deref() == Some(id.as_str())) {
+                Some(existing) => {
+                    *existing = item.clone();
+                    replaced = true;
+                }
+                None => deleted.extend(item.id.clone()),
+            }
+        }
+        if replaced {
+            self.write_all(&all)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Adds items that aren't stored yet, keeping the file in date order.
+    fn merge(&self, items: Vec<HistoryItem>) -> Result<(), String> {
+        let mut all = self.read_all();
+        let mut added = false;
+        for item in items {
+            if !all.iter().any(|existing| existing.id.is_some() && existing.id == item.id) {
+                all.push(item);
+                added = true;
+            }
+        }
+        if added {
+            all.sort_by_key(|item| item.created_at);
+            let excess = all.len().saturating_sub(MAX_HISTORY_ITEMS);
+            all.drain(..excess);
+            self.write_all(&all)?;
+        }
+        Ok(())
+    }
+}
+
+impl HistoryStore for LocalHistory {
+    fn load(&self) -> Result<Vec<HistoryItem>, String> {
+        let mut items = self.read_all();
+        items.reverse();
+        items.truncate(MAX_HISTORY_ITEMS);
+        Ok(items)
+    }
+
+    fn save(&self, mut item: HistoryItem) -> Result<HistoryItem, String> {
+        let now = Utc::now();
+        if item.id.is_none() {
+            item.id = Some(format!("local-{}", now.timestamp_nanos_opt().unwrap_or_default()));
+        }
+        if item.created_at.is_none() {
+            item.created_at = Some(now);
+        }
+
+        let line = serde_json::to_string(&item).map_err(|e| e.to_string())?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+
+        // Compact once the file has grown well past the cap
+        let items = self.read_all();
+        if items.len() > MAX_HISTORY_ITEMS * 2 {
+            self.write_all(&items[items.len() - MAX_HISTORY_ITEMS..])?;
+        }
+
+        Ok(item)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        let mut items = self.read_all();
+        items.retain(|item| item.id.as_deref() != Some(id));
+        self.write_all(&items)
+    }
+}
+
+/// A row of the Supabase `history` table. Only the columns of the original
+/// schema are sent, since PostgREST rejects unknown ones; headers, auth and
+/// the rest of an item are kept locally only.
+#[derive(Serialize, Deserialize)]
+struct RemoteItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    user_id: String,
+    method: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    status: String,
+    response: String,
+    time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl From<&HistoryItem> for RemoteItem {
+    fn from(item: &HistoryItem) -> Self {
+        Self {
+            id: item.id.clone(),
+            user_id: item.user_id.clone(),
+            method: item.method.clone(),
+            url: item.url.clone(),
+            body: item.body.clone(),
+            status: item.status.clone(),
+            response: item.response.clone(),
+            time: item.time.clone(),
+            created_at: item.created_at,
+        }
+    }
+}
+
+impl From<RemoteItem> for HistoryItem {
+    fn from(item: RemoteItem) -> Self {
+        Self {
+            id: item.id,
+            user_id: item.user_id,
+            method: item.method,
+            url: item.url,
+            headers: Vec::new(),
+            body: item.body,
+            body_type: Default::default(),
+            auth: Default::default(),
+            status: item.status,
+            response: item.response,
+            response_headers: Vec::new(),
+            time: item.time,
+            created_at: item.created_at,
+        }
+    }
+}
+
+/// History stored in the Supabase `history` table for a logged in user.
+#[derive(Clone)]
+pub struct SupabaseHistory {
+    pub config: Config,
+    pub token: String,
+    pub user_id: String,
+    pub unauthorized: Cell<bool>,
+}
+
+impl SupabaseHistory {
+    /// True once the backend has rejected our access token.
+    fn session_expired(&self) -> bool {
+        self.unauthorized.get()
+    }
+
+    fn check_status(&self, status: reqwest::StatusCode, action: &str) -> Result<(), String> {
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            self.unauthorized.set(true);
+        }
+        if !status.is_success() {
+            return Err(format!("Failed to {} history: {}", action, status));
+        }
+        Ok(())
+    }
+}
+
+impl HistoryStore for SupabaseHistory {
+    fn load(&self) -> Result<Vec<HistoryItem>, String> {
+        let url = format!(
+            "{}/rest/v1/history?user_id=eq.{}&order=created_at.desc&limit={}",
+            self.config.supabase_url, self.user_id, MAX_HISTORY_ITEMS
+        );
+
+        let resp = client()
+            .get(&url)
+            .header("apikey", &self.config.supabase_anon_key)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map_err(|e| e.to_string())?;
+
+        self.check_status(resp.status(), "load")?;
+        let items = resp.json::<Vec<RemoteItem>>().map_err(|e| e.to_string())?;
+        Ok(items.into_iter().map(HistoryItem::from).collect())
+    }
+
+    fn save(&self, mut item: HistoryItem) -> Result<HistoryItem, String> {
+        item.user_id = self.user_id.clone();
+
+        let resp = client()
+            .post(format!("{}/rest/v1/history", self.config.supabase_url))
+            .header("apikey", &self.config.supabase_anon_key)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=representation")
+            .json(&RemoteItem::from(&item))
+            .send()
+            .map_err(|e| e.to_string())?;
+
+        self.check_status(resp.status(), "save")?;
+        let saved = resp
+            .json::<Vec<RemoteItem>>()
+            .map_err(|e| e.to_string())?
+            .pop()
+            .ok_or_else(|| "Empty response when saving history".to_string())?;
+        item.id = saved.id;
+        item.created_at = saved.created_at;
+        Ok(item)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        let resp = client()
+            .delete(format!("{}/rest/v1/history?id=eq.{}", self.config.supabase_url, id))
+            .header("apikey", &self.config.supabase_anon_key)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map_err(|e| e.to_string())?;
+
+        self.check_status(resp.status(), "delete")
+    }
+}
+
+fn client() -> Client {
+    Client::builder().timeout(SYNC_TIMEOUT).build().unwrap_or_default()
+}
+
+/// Work for one background sync with the account.
+pub struct SyncJob {
+    /// Server ids of items deleted locally.
+    pub deletes: Vec<String>,
+    /// Whether to fetch the account's history.
+    pub load: bool,
+    /// Items that only have a `local-` id, oldest first.
+    pub pending: Vec<HistoryItem>,
+}
+
+/// What a sync got done. It stops at the first failure and leaves the rest
+/// for the next one.
+#[derive(Default)]
+pub struct SyncResult {
+    pub deleted: Vec<String>,
+    pub loaded: Option<Vec<HistoryItem>>,
+    /// The `local-` id of each uploaded item and the item as the server
+    /// stored it.
+    pub uploaded: Vec<(String, HistoryItem)>,
+    pub failed: bool,
+    /// True if the server rejected the access token.
+    pub unauthorized: bool,
+}
+
+/// Deletes, then loads, then uploads. Blocks, so it runs on a background
+/// thread.
+pub fn sync(remote: &SupabaseHistory, job: SyncJob) -> SyncResult {
+    let mut result = SyncResult::default();
+    let run = || -> Result<(), String> {
+        for id in job.deletes {
+            remote.delete(&id)?;
+            result.deleted.push(id);
+        }
+        if job.load {
+            result.loaded = Some(remote.load()?);
+        }
+        for item in job.pending {
+            let Some(local_id) = item.id.clone() else { continue };
+            let saved = remote.save(HistoryItem { id: None, ..item })?;
+            result.uploaded.push((local_id, saved));
+        }
+        Ok(())
+    };
+    if let Err(e) = run() {
+        eprintln!("History sync failed: {}", e);
+        result.failed = true;
+    }
+    result.unauthorized = remote.session_expired();
+    result
+}
+
+/// Local history with an optional Supabase account synced on top. The local
+/// store is always the source of truth, so history keeps working offline.
+/// Everything here is local; talking to the account is left to `sync`.
+pub struct SyncedHistory {
+    local: LocalHistory,
+    remote: Option<SupabaseHistory>,
+}
+
+impl SyncedHistory {
+    pub fn new(user_id: &str, remote: Option<SupabaseHistory>) -> Self {
+        Self {
+            local: LocalHistory::new(user_id),
+            remote,
+        }
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.local.user_id
+    }
+
+    pub fn remote(&self) -> Option<&SupabaseHistory> {
+        self.remote.as_ref()
+    }
+
+    /// Items that only have a `local-` id because they haven't been
+    /// uploaded yet, oldest first.
+    pub fn pending(&self) -> Vec<HistoryItem> {
+        self.local
+            .read_all()
+            .into_iter()
+            .filter(|item| is_local_id(item.id.as_deref()))
+            .collect()
+    }
+
+    /// Gives uploaded items the server's id. Returns the server ids of items
+    /// deleted while they were being uploaded.
+    pub fn uploaded(&self, items: &[(String, HistoryItem)]) -> Result<Vec<String>, String> {
+        self.local.replace(items)
+    }
+
+    /// Adds items loaded from the account that aren't stored yet.
+    pub fn merge(&self, items: Vec<HistoryItem>) -> Result<(), String> {
+        self.local.merge(items)
+    }
+}
+
+/// Whether an item was only ever saved locally.
+pub fn is_local_id(id: Option<&str>) -> bool {
+    id.is_some_and(|id| id.starts_with("local-"))
+}
+
+impl HistoryStore for SyncedHistory {
+    fn load(&self) -> Result<Vec<HistoryItem>, String> {
+        self.local.load()
+    }
+
+    /// The item gets a `local-` id and is uploaded by a later `sync`.
+    fn save(&self, item: HistoryItem) -> Result<HistoryItem, String> {
+        self.local.save(item)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        self.local.delete(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// A Supabase stand-in on a loopback port. Each request is answered with
+    /// the next of `responses` (status, JSON body), and its request line is
+    /// passed on.
+    fn server(responses: Vec<(u16, &'static str)>) -> (SupabaseHistory, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote = SupabaseHistory {
+            config: Config {
+                supabase_url: format!("http://{}", listener.local_addr().unwrap()),
+                supabase_anon_key: "anon".to_string(),
+                overrides: Vec::new(),
+            },
+            token: "token".to_string(),
+            user_id: "user".to_string(),
+            unauthorized: Cell::new(false),
+        };
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&mut stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                tx.send(request_line.trim().to_string()).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (remote, rx)
+    }
+
+    fn item(id: &str) -> HistoryItem {
+        HistoryItem::from(RemoteItem {
+            id: Some(id.to_string()),
+            user_id: "user".to_string(),
+            method: "GET".to_string(),
+            url: "https://example.com".to_string(),
+            body: None,
+            status: "200".to_string(),
+            response: String::new(),
+            time: "1ms".to_string(),
+            created_at: None,
+        })
+    }
+
+    #[test]
+    fn sync_deletes_loads_and_uploads_in_order() {
+        let (remote, requests) = server(vec![
+            (204, ""),
+            (200, r#"[{"id":"7","user_id":"user","method":"GET","url":"u","status":"200","response":"","time":"1ms"}]"#),
+            (201, r#"[{"id":"8","user_id":"user","method":"GET","url":"u","status":"200","response":"","time":"1ms"}]"#),
+        ]);
+        let result = sync(
+            &remote,
+            SyncJob {
+                deletes: vec!["5".to_string()],
+                load: true,
+                pending: vec![item("local-1")],
+            },
+        );
+        assert_eq!(result.deleted, ["5"]);
+        assert_eq!(result.loaded.unwrap()[0].id.as_deref(), Some("7"));
+        assert_eq!(result.uploaded[0].0, "local-1");
+        assert_eq!(result.uploaded[0].1.id.as_deref(), Some("8"));
+        assert!(!result.failed && !result.unauthorized);
+
+        let requests: Vec<String> = requests.iter().collect();
+        assert!(requests[0].starts_with("DELETE /rest/v1/history?id=eq.5 "));
+        assert!(requests[1].starts_with("GET /rest/v1/history?user_id=eq.user"));
+        assert!(requests[2].starts_with("POST /rest/v1/history "));
+    }
+
+    #[test]
+    fn sync_stops_at_a_rejected_token() {
+        let (remote, requests) = server(vec![(401, "{}")]);
+        let result = sync(
+            &remote,
+            SyncJob {
+                deletes: Vec::new(),
+                load: true,
+                pending: vec![item("local-1")],
+            },
+        );
+        assert!(result.loaded.is_none() && result.uploaded.is_empty());
+        assert!(result.failed && result.unauthorized);
+        assert_eq!(requests.iter().count(), 1);
+    }
+}
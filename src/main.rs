@@ -1,18 +1,72 @@
 use eframe::egui;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::cell::Cell;
 use std::fs;
+use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
 use chrono::{DateTime, Utc};
 
-const SUPABASE_URL: &str = "https://drtejwkmjuwyqugpdspe.supabase.co";
-const SUPABASE_ANON_KEY: &str = "sb_publishable_0zSJqibEWNDVan_BOpvJDg_yYMdp9lO";
+mod auth;
+mod body;
+mod codegen;
+mod collections;
+mod config;
+mod curl;
+mod environments;
+mod filter;
+mod find;
+mod history;
+mod oauth;
+mod query;
+mod secrets;
+mod stream;
+mod tabs;
+mod viewer;
+mod websocket;
+
+use auth::{Auth, DigestCredentials};
+use body::{BodyType, Payload};
+use config::Config;
+use collections::{Collections, Node, NodePath, SavedRequest, TreeAction, TreeState};
+use environments::{Environment, Environments};
+use find::Find;
+use history::{HistoryStore, SupabaseHistory, SyncedHistory, MAX_HISTORY_ITEMS};
+use oauth::OAuth2Config;
+use tabs::{SavedTab, SavedTabs, TabAction, TabLabel};
+
 const MAX_RESPONSE_SIZE: usize = 100_000; // 100 KB
 const MAX_BODY_SIZE: usize = 10_000; // 10 KB
+/// How much of a response body is kept in memory and shown at a time.
+/// Anything beyond it is downloaded to a temporary file.
+const PREVIEW_BYTES: usize = 8 * 1024 * 1024; // 8 MB
+/// Only connecting has a deadline; once connected, a request runs until it
+/// finishes or is cancelled.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often a request waiting on the server checks whether it was cancelled.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long tab edits wait before they're written to disk, so typing
+/// doesn't save on every keystroke.
+const TABS_SAVE_DELAY: Duration = Duration::from_secs(1);
 
 fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", config::USAGE);
+        return Ok(());
+    }
+    let config = match Config::load(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, config::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 700.0])
@@ -22,7 +76,7 @@ fn main() -> eframe::Result<()> {
 
     eframe::run_native("restty", options, Box::new(|cc| {
         setup_custom_style(&cc.egui_ctx);
-        Box::new(App::new())
+        Box::new(App::new(config, args))
     }))
 }
 
@@ -55,15 +109,127 @@ struct HistoryItem {
     user_id: String,
     method: String,
     url: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<KeyValue>,
     #[serde(skip_serializing_if = "Option::is_none")]
     body: Option<String>,
+    #[serde(default, skip_serializing_if = "BodyType::is_json")]
+    body_type: BodyType,
+    #[serde(default, skip_serializing_if = "Auth::is_none")]
+    auth: Auth,
     status: String,
     response: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    response_headers: Vec<(String, String)>,
     time: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     created_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct KeyValue {
+    enabled: bool,
+    key: String,
+    value: String,
+}
+
+impl KeyValue {
+    fn new() -> Self {
+        Self {
+            enabled: true,
+            key: String::new(),
+            value: String::new(),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+struct ResponseMeta {
+    http_version: String,
+    remote_addr: Option<String>,
+    content_length: Option<u64>,
+    body_size: usize,
+}
+
+struct Cookie {
+    name: String,
+    value: String,
+    attributes: Vec<(String, String)>,
+}
+
+impl Cookie {
+    fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let attributes = parts
+            .map(str::trim)
+            .filter(|attr| !attr.is_empty())
+            .map(|attr| match attr.split_once('=') {
+                Some((k, v)) => (k.trim().to_string(), v.trim().to_string()),
+                None => (attr.to_string(), String::new()),
+            })
+            .collect();
+
+        Some(Self {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            attributes,
+        })
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum ResponseTab {
+    Body,
+    Headers,
+    Cookies,
+    Timing,
+}
+
+struct ResolvedRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Payload>,
+    /// Digest needs a challenge from the server first, so it is applied by
+    /// the worker rather than as a header.
+    digest: Option<(String, String)>,
+    /// OAuth 2.0 may have to fetch a token first, which is also left to the
+    /// worker.
+    oauth: Option<OAuth2Config>,
+}
+
+struct SaveDialog {
+    name: String,
+    folder: Option<NodePath>,
+    new_collection: String,
+}
+
+/// A sync of history with the account, running on a background thread.
+struct HistorySync {
+    rx: mpsc::Receiver<history::SyncResult>,
+    /// Whose history it is; the user may have changed by the time it ends.
+    user_id: String,
+    /// Whether it fetches the account's history.
+    load: bool,
+    /// More history work came up while it ran.
+    again: bool,
+    /// The session was already refreshed for a rejected sync, so another
+    /// rejection isn't retried.
+    refreshed: bool,
+}
+
+/// The "Save response" dialog: where to write the active tab's body.
+struct ResponseSave {
+    path: String,
+    error: String,
+}
+
 #[derive(Serialize)]
 struct SupabaseAuthRequest {
     email: String,
@@ -73,9 +239,30 @@ struct SupabaseAuthRequest {
 #[derive(Deserialize)]
 struct SupabaseAuthResponse {
     access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<i64>,
     user: SupabaseUser,
 }
 
+#[derive(Serialize)]
+struct SupabaseRefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Credentials {
+    token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    /// Unix timestamp (seconds) when `token` stops being valid.
+    #[serde(default)]
+    expires_at: Option<i64>,
+    user_id: String,
+    email: String,
+}
+
 #[derive(Deserialize)]
 struct SupabaseUser {
     id: String,
@@ -86,11 +273,19 @@ struct SupabaseUser {
 enum View {
     Login,
     Main,
+    Settings,
 }
 
 struct App {
     view: View,
+    config: Config,
+    cli_args: Vec<String>,
+    settings_url: String,
+    settings_anon_key: String,
+    settings_message: String,
     token: Option<String>,
+    refresh_token: Option<String>,
+    expires_at: Option<i64>,
     user_id: Option<String>,
     email: Option<String>,
 
@@ -99,21 +294,75 @@ struct App {
     login_error: String,
     is_signup: bool,
 
+    tabs: Vec<RequestTab>,
+    active_tab: usize,
+    oauth_tokens: oauth::TokenCache,
+
+    environments: Environments,
+    show_environments: bool,
+    new_environment_name: String,
+
+    collections: Collections,
+    collections_tree: TreeState,
+    show_collections: bool,
+    save_dialog: Option<SaveDialog>,
+    curl_import: Option<String>,
+    export_language: Option<codegen::Language>,
+    response_save: Option<ResponseSave>,
+
+    history: Vec<HistoryItem>,
+    history_store: SyncedHistory,
+    history_sync: Option<HistorySync>,
+    /// Server ids of items deleted locally but not yet from the account.
+    history_deletes: Vec<String>,
+    /// The next sync should fetch the account's history.
+    history_load: bool,
+    show_history: bool,
+
+    url_field_focused: bool,
+    /// When the tabs first changed since they were last saved.
+    tabs_changed: Option<Instant>,
+}
+
+/// One request in the tab strip, together with its response.
+struct RequestTab {
     url: String,
     method: Method,
+    /// WebSocket mode: connect to the URL instead of sending a request.
+    /// Saved with "WS" as the method.
+    websocket: bool,
+    socket: Option<websocket::Session>,
+    composer: websocket::Composer,
+    params: Vec<KeyValue>,
+    headers: Vec<KeyValue>,
     body: String,
+    body_type: BodyType,
+    body_find: Find,
+    auth: Auth,
+    insecure: bool,
     response: String,
+    response_view: viewer::ResponseView,
+    /// The full body on disk, when it's larger than what's shown.
+    response_spool: Option<Spool>,
+    /// More of the spooled body being read in for "Load more".
+    loading_more: Option<mpsc::Receiver<Result<SpoolPrefix, String>>>,
+    /// Set instead of a body for event streams.
+    stream: Option<stream::Stream>,
     status: String,
     time: String,
-
-    history: Vec<HistoryItem>,
-    show_history: bool,
+    response_headers: Vec<(String, String)>,
+    response_meta: ResponseMeta,
+    response_tab: ResponseTab,
+    in_flight: Option<InFlightRequest>,
+    curl_error: String,
     selected_history_id: Option<String>,
-
-    url_field_focused: bool,
+    /// The request as last loaded or saved, to tell whether the tab has
+    /// unsaved changes. Named if it came from a collection.
+    baseline: SavedRequest,
 }
 
-#[derive(Default, PartialEq, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Default, PartialEq, Clone)]
 enum Method {
     #[default]
     GET,
@@ -121,9 +370,24 @@ enum Method {
     PUT,
     DELETE,
     PATCH,
+    HEAD,
+    OPTIONS,
+    TRACE,
+    Custom(String),
 }
 
 impl Method {
+    const STANDARD: [Method; 8] = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::PATCH,
+        Method::HEAD,
+        Method::OPTIONS,
+        Method::TRACE,
+    ];
+
     fn as_str(&self) -> &str {
         match self {
             Method::GET => "GET",
@@ -131,40 +395,91 @@ impl Method {
             Method::PUT => "PUT",
             Method::DELETE => "DELETE",
             Method::PATCH => "PATCH",
+            Method::HEAD => "HEAD",
+            Method::OPTIONS => "OPTIONS",
+            Method::TRACE => "TRACE",
+            Method::Custom(name) => name,
         }
     }
+
+    fn parse(name: &str) -> Self {
+        let name = name.trim();
+        Method::STANDARD
+            .into_iter()
+            .find(|method| method.as_str().eq_ignore_ascii_case(name))
+            .unwrap_or_else(|| Method::Custom(name.to_string()))
+    }
+}
+
+fn method_color(method: &str, green: egui::Color32) -> egui::Color32 {
+    match method {
+        "GET" => egui::Color32::from_rgb(100, 180, 255),
+        "POST" => green,
+        "PUT" => egui::Color32::from_rgb(255, 180, 100),
+        "DELETE" => egui::Color32::from_rgb(255, 100, 100),
+        "PATCH" => egui::Color32::from_rgb(200, 150, 255),
+        "HEAD" => egui::Color32::from_rgb(100, 220, 220),
+        "OPTIONS" => egui::Color32::from_rgb(240, 220, 100),
+        "TRACE" => egui::Color32::from_rgb(180, 180, 140),
+        "WS" => egui::Color32::from_rgb(255, 140, 200),
+        _ => egui::Color32::GRAY,
+    }
 }
 
 impl App {
-    fn new() -> Self {
-        let (token, user_id, email) = load_credentials();
+    fn new(config: Config, cli_args: Vec<String>) -> Self {
+        let credentials = load_credentials();
         let view = View::Main;
 
         let mut app = Self {
             view,
-            token: token.clone(),
-            user_id: user_id.clone(),
-            email: email.clone(),
+            settings_url: config.supabase_url.clone(),
+            settings_anon_key: config.supabase_anon_key.clone(),
+            settings_message: String::new(),
+            config,
+            cli_args,
+            token: credentials.as_ref().map(|c| c.token.clone()),
+            refresh_token: credentials.as_ref().and_then(|c| c.refresh_token.clone()),
+            expires_at: credentials.as_ref().and_then(|c| c.expires_at),
+            user_id: credentials.as_ref().map(|c| c.user_id.clone()),
+            email: credentials.as_ref().map(|c| c.email.clone()),
             login_email: String::new(),
             login_password: String::new(),
             login_error: String::new(),
             is_signup: false,
-            url: String::new(),
-            method: Method::GET,
-            body: String::new(),
-            response: String::new(),
-            status: String::new(),
-            time: String::new(),
+            tabs: Vec::new(),
+            active_tab: 0,
+            oauth_tokens: oauth::TokenCache::default(),
+            environments: Environments::load(),
+            show_environments: false,
+            new_environment_name: String::new(),
+            collections: Collections::load(),
+            collections_tree: TreeState::default(),
+            show_collections: false,
+            save_dialog: None,
+            curl_import: None,
+            export_language: None,
+            response_save: None,
             history: Vec::new(),
-            show_history: false,
-            selected_history_id: None,
+            history_store: SyncedHistory::new("", None),
+            history_sync: None,
+            history_deletes: Vec::new(),
+            history_load: false,
+            show_history: true,
             url_field_focused: false,
+            tabs_changed: None,
         };
 
-        if token.is_some() {
-            app.show_history = true;
-            app.load_history();
+        let saved_tabs = SavedTabs::load();
+        app.tabs = saved_tabs.tabs.iter().map(RequestTab::restore).collect();
+        if app.tabs.is_empty() {
+            app.tabs.push(RequestTab::new());
         }
+        app.active_tab = saved_tabs.active.min(app.tabs.len() - 1);
+
+        app.refresh_session_if_needed();
+        app.reset_history_store();
+        app.load_history();
 
         app
     }
@@ -179,7 +494,7 @@ impl App {
 
     fn signup_then_login(&mut self) {
         let client = Client::new();
-        let signup_url = format!("{}/auth/v1/signup", SUPABASE_URL);
+        let signup_url = format!("{}/auth/v1/signup", self.config.supabase_url);
 
         let req = SupabaseAuthRequest {
             email: self.login_email.clone(),
@@ -188,7 +503,7 @@ impl App {
 
         eprintln!("Attempting signup...");
         match client.post(&signup_url)
-            .header("apikey", SUPABASE_ANON_KEY)
+            .header("apikey", &self.config.supabase_anon_key)
             .header("Content-Type", "application/json")
             .json(&req)
             .send() {
@@ -231,7 +546,7 @@ impl App {
 
     fn do_login(&mut self) {
         let client = Client::new();
-        let login_url = format!("{}/auth/v1/token?grant_type=password", SUPABASE_URL);
+        let login_url = format!("{}/auth/v1/token?grant_type=password", self.config.supabase_url);
 
         let req = SupabaseAuthRequest {
             email: self.login_email.clone(),
@@ -240,7 +555,7 @@ impl App {
 
         eprintln!("Attempting login...");
         match client.post(&login_url)
-            .header("apikey", SUPABASE_ANON_KEY)
+            .header("apikey", &self.config.supabase_anon_key)
             .header("Content-Type", "application/json")
             .json(&req)
             .send() {
@@ -252,13 +567,11 @@ impl App {
                     match resp.json::<SupabaseAuthResponse>() {
                         Ok(auth_resp) => {
                             eprintln!("Login successful!");
-                            self.token = Some(auth_resp.access_token.clone());
-                            self.user_id = Some(auth_resp.user.id.clone());
-                            self.email = Some(auth_resp.user.email.clone());
-                            save_credentials(&auth_resp.access_token, &auth_resp.user.id, &auth_resp.user.email);
+                            self.set_session(auth_resp);
                             self.view = View::Main;
                             self.show_history = true;
                             self.login_error.clear();
+                            self.reset_history_store();
                             self.load_history();
                         }
                         Err(e) => {
@@ -284,269 +597,1281 @@ impl App {
         }
     }
 
-    fn logout(&mut self) {
+    fn set_session(&mut self, auth_resp: SupabaseAuthResponse) {
+        let credentials = Credentials {
+            token: auth_resp.access_token,
+            refresh_token: auth_resp.refresh_token,
+            expires_at: auth_resp.expires_in.map(|secs| Utc::now().timestamp() + secs),
+            user_id: auth_resp.user.id,
+            email: auth_resp.user.email,
+        };
+        save_credentials(&credentials);
+
+        self.token = Some(credentials.token);
+        self.refresh_token = credentials.refresh_token;
+        self.expires_at = credentials.expires_at;
+        self.user_id = Some(credentials.user_id);
+        self.email = Some(credentials.email);
+    }
+
+    fn clear_session(&mut self) {
         self.token = None;
+        self.refresh_token = None;
+        self.expires_at = None;
         self.user_id = None;
         self.email = None;
-        self.view = View::Main;
-        self.show_history = false;
-        self.history.clear();
         clear_credentials();
     }
 
-    fn load_history(&mut self) {
-        if let (Some(token), Some(user_id)) = (&self.token, &self.user_id) {
-            let client = Client::new();
-            let url = format!("{}/rest/v1/history?user_id=eq.{}&order=created_at.desc&limit=100", SUPABASE_URL, user_id);
-
-            match client.get(&url)
-                .header("apikey", SUPABASE_ANON_KEY)
-                .header("Authorization", format!("Bearer {}", token))
-                .send() {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        if let Ok(history) = resp.json::<Vec<HistoryItem>>() {
-                            self.history = history;
-                        }
-                    }
+    fn logout(&mut self) {
+        self.clear_session();
+        self.view = View::Main;
+        self.reset_history_store();
+        self.load_history();
+    }
+
+    /// Refreshes the access token if it expires within a minute. Returns
+    /// false if there is a session and it couldn't be kept alive.
+    fn refresh_session_if_needed(&mut self) -> bool {
+        if self.token.is_none() {
+            return true;
+        }
+        match self.expires_at {
+            Some(expires_at) if expires_at - Utc::now().timestamp() > 60 => true,
+            // Sessions saved before expiry was tracked; find out on the first 401
+            None if self.refresh_token.is_none() => true,
+            _ => self.refresh_session(),
+        }
+    }
+
+    fn refresh_session(&mut self) -> bool {
+        let Some(refresh_token) = self.refresh_token.clone() else {
+            self.expire_session();
+            return false;
+        };
+
+        let url = format!("{}/auth/v1/token?grant_type=refresh_token", self.config.supabase_url);
+        let result = Client::new()
+            .post(&url)
+            .header("apikey", &self.config.supabase_anon_key)
+            .header("Content-Type", "application/json")
+            .json(&SupabaseRefreshRequest { refresh_token })
+            .send();
+
+        match result {
+            Ok(resp) if resp.status().is_success() => match resp.json::<SupabaseAuthResponse>() {
+                Ok(auth_resp) => {
+                    self.set_session(auth_resp);
+                    self.reset_history_store();
+                    true
                 }
                 Err(e) => {
-                    eprintln!("Failed to load history: {}", e);
+                    eprintln!("Token refresh parse error: {}", e);
+                    self.expire_session();
+                    false
                 }
+            },
+            Ok(resp) => {
+                eprintln!("Token refresh failed: {}", resp.status());
+                self.expire_session();
+                false
+            }
+            Err(e) => {
+                // Offline: keep the session, history still works locally
+                eprintln!("Token refresh connection error: {}", e);
+                true
             }
         }
     }
 
-    fn save_to_history(&mut self) {
-        if let (Some(token), Some(user_id)) = (&self.token, &self.user_id) {
-            // Truncate body and response to prevent flooding database
-            let truncated_body = if self.body.is_empty() {
-                None
-            } else {
-                Some(truncate_string(&self.body, MAX_BODY_SIZE))
-            };
-
-            let truncated_response = truncate_string(&self.response, MAX_RESPONSE_SIZE);
+    /// The session is gone for good; send the user back to the login screen.
+    fn expire_session(&mut self) {
+        self.login_email = self.email.clone().unwrap_or_default();
+        self.clear_session();
+        self.reset_history_store();
+        self.login_error = "Your session has expired. Please log in again.".to_string();
+        self.is_signup = false;
+        self.view = View::Login;
+    }
 
-            let item = HistoryItem {
-                id: None,
+    /// History is always kept locally; a logged in account is synced on top.
+    fn reset_history_store(&mut self) {
+        let remote = match (&self.token, &self.user_id) {
+            (Some(token), Some(user_id)) => Some(SupabaseHistory {
+                config: self.config.clone(),
+                token: token.clone(),
                 user_id: user_id.clone(),
-                method: self.method.as_str().to_string(),
-                url: self.url.clone(),
-                body: truncated_body,
-                status: self.status.clone(),
-                response: truncated_response,
-                time: self.time.clone(),
-                created_at: None,
-            };
+                unauthorized: Cell::new(false),
+            }),
+            _ => None,
+        };
+        let user_id = self.user_id.clone().unwrap_or_default();
+        if user_id != self.history_store.user_id() {
+            self.history_deletes.clear();
+        }
+        self.history_store = SyncedHistory::new(&user_id, remote);
+    }
 
-            let client = Client::new();
-            match client.post(&format!("{}/rest/v1/history", SUPABASE_URL))
-                .header("apikey", SUPABASE_ANON_KEY)
-                .header("Authorization", format!("Bearer {}", token))
-                .header("Content-Type", "application/json")
-                .header("Prefer", "return=representation")
-                .json(&item)
-                .send() {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        if let Ok(mut saved_items) = resp.json::<Vec<HistoryItem>>() {
-                            if let Some(saved_item) = saved_items.pop() {
-                                self.history.insert(0, saved_item);
-                                if self.history.len() > 100 {
-                                    self.history.truncate(100);
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Failed to save history: {}", e);
-                }
+    /// Shows the local history at once; the account's is fetched in the
+    /// background and merged in when it arrives.
+    fn load_history(&mut self) {
+        match self.history_store.load() {
+            Ok(history) => self.history = history,
+            Err(e) => eprintln!("Failed to load history: {}", e),
+        }
+        self.history_load = true;
+        self.sync_history(false);
+    }
+
+    /// Saves locally straight away; uploading to the account happens in the
+    /// background so a slow or unreachable server can't stall the UI.
+    fn save_to_history(&mut self, mut item: HistoryItem) {
+        item.user_id = self.user_id.clone().unwrap_or_default();
+        match self.history_store.save(item) {
+            Ok(saved_item) => {
+                self.history.insert(0, saved_item);
+                self.history.truncate(MAX_HISTORY_ITEMS);
             }
+            Err(e) => eprintln!("Failed to save history: {}", e),
         }
+        self.sync_history(false);
     }
 
+    /// Deletes locally straight away and from the account in the background.
     fn delete_history_item(&mut self, id: &str) {
-        if let Some(token) = &self.token {
-            let client = Client::new();
-            let url = format!("{}/rest/v1/history?id=eq.{}", SUPABASE_URL, id);
-
-            match client.delete(&url)
-                .header("apikey", SUPABASE_ANON_KEY)
-                .header("Authorization", format!("Bearer {}", token))
-                .send() {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        self.history.retain(|item| {
-                            item.id.as_ref().map(|item_id| item_id.as_str()) != Some(id)
-                        });
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Failed to delete history: {}", e);
-                }
-            }
+        if let Err(e) = self.history_store.delete(id) {
+            eprintln!("Failed to delete history: {}", e);
+            return;
+        }
+        self.history.retain(|item| item.id.as_deref() != Some(id));
+        // Items saved while offline only exist locally
+        if self.history_store.remote().is_some() && !history::is_local_id(Some(id)) {
+            self.history_deletes.push(id.to_string());
+            self.sync_history(false);
         }
     }
 
-    fn load_history_item(&mut self, item: &HistoryItem) {
-        self.url = item.url.clone();
-        self.method = match item.method.as_str() {
-            "POST" => Method::POST,
-            "PUT" => Method::PUT,
-            "DELETE" => Method::DELETE,
-            "PATCH" => Method::PATCH,
-            _ => Method::GET,
+    /// Starts syncing pending deletes, loads and uploads with the account,
+    /// or queues another sync if one is already running.
+    fn sync_history(&mut self, refreshed: bool) {
+        if let Some(sync) = &mut self.history_sync {
+            sync.again = true;
+            return;
+        }
+        let Some(remote) = self.history_store.remote().cloned() else {
+            self.history_load = false;
+            return;
         };
-        self.body = item.body.clone().unwrap_or_default();
-        self.response = item.response.clone();
-        self.status = item.status.clone();
-        self.time = item.time.clone();
-        self.selected_history_id = item.id.clone();
-    }
+        let job = history::SyncJob {
+            deletes: self.history_deletes.clone(),
+            load: std::mem::take(&mut self.history_load),
+            pending: self.history_store.pending(),
+        };
+        if job.deletes.is_empty() && !job.load && job.pending.is_empty() {
+            return;
+        }
 
-    fn send_request(&mut self) {
-        let client = Client::new();
-        let start = Instant::now();
+        let load = job.load;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            tx.send(history::sync(&remote, job)).ok();
+        });
+        self.history_sync = Some(HistorySync {
+            rx,
+            user_id: self.history_store.user_id().to_string(),
+            load,
+            again: false,
+            refreshed,
+        });
+    }
 
-        let result = match self.method {
-            Method::GET => client.get(&self.url).send(),
-            Method::POST => {
-                let mut req = client.post(&self.url);
-                if !self.body.is_empty() {
-                    req = req.header("Content-Type", "application/json").body(self.body.clone());
-                }
-                req.send()
-            }
-            Method::PUT => {
-                let mut req = client.put(&self.url);
-                if !self.body.is_empty() {
-                    req = req.header("Content-Type", "application/json").body(self.body.clone());
-                }
-                req.send()
-            }
-            Method::DELETE => client.delete(&self.url).send(),
-            Method::PATCH => {
-                let mut req = client.patch(&self.url);
-                if !self.body.is_empty() {
-                    req = req.header("Content-Type", "application/json").body(self.body.clone());
-                }
-                req.send()
+    /// Picks up a finished sync: drops deletes that went through, merges
+    /// the loaded history and gives uploaded items the server's ids.
+    fn poll_history_sync(&mut self) {
+        let Some(sync) = self.history_sync.take() else {
+            return;
+        };
+        let result = match sync.rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => {
+                self.history_sync = Some(sync);
+                return;
             }
+            Err(mpsc::TryRecvError::Disconnected) => return,
         };
 
-        let duration = start.elapsed();
-        self.time = format!("{:.0?}", duration);
-
-        match result {
-            Ok(resp) => {
-                self.status = resp.status().to_string();
-                if let Ok(text) = resp.text() {
-                    if let Ok(json) = serde_json::from_str::<Value>(&text) {
-                        self.response = serde_json::to_string_pretty(&json).unwrap_or(text);
-                    } else {
-                        self.response = text;
-                    }
-                }
-                if self.token.is_some() {
-                    self.save_to_history();
-                }
+        if sync.user_id != self.history_store.user_id() {
+            // Still record the uploads, so they don't go up twice
+            if let Err(e) = SyncedHistory::new(&sync.user_id, None).uploaded(&result.uploaded) {
+                eprintln!("Failed to save history: {}", e);
             }
-            Err(e) => {
-                self.status = "Error".to_string();
-                self.response = e.to_string();
+            if sync.again {
+                self.sync_history(false);
             }
+            return;
         }
-    }
-}
-
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let green = egui::Color32::from_rgb(0, 200, 120);
 
-        ctx.input(|i| {
-            if i.key_pressed(egui::Key::L) && i.modifiers.command {
-                self.url_field_focused = true;
-            }
-            if i.key_pressed(egui::Key::H) && i.modifiers.command && self.view == View::Main {
-                self.show_history = !self.show_history;
+        self.history_deletes.retain(|id| !result.deleted.contains(id));
+        match self.history_store.uploaded(&result.uploaded) {
+            // Deleted while uploading, so it goes again
+            Ok(deleted) => self.history_deletes.extend(deleted),
+            Err(e) => eprintln!("Failed to save history: {}", e),
+        }
+        for (local_id, saved) in &result.uploaded {
+            if let Some(item) = self.history.iter_mut().find(|item| item.id.as_deref() == Some(local_id.as_str())) {
+                *item = saved.clone();
             }
-            if i.key_pressed(egui::Key::Enter) && i.modifiers.command && self.view == View::Main {
-                if !self.url.is_empty() {
-                    self.send_request();
+            for tab in &mut self.tabs {
+                if tab.selected_history_id.as_deref() == Some(local_id.as_str()) {
+                    tab.selected_history_id = saved.id.clone();
                 }
             }
-        });
+        }
+        if let Some(mut loaded) = result.loaded {
+            loaded.retain(|item| !item.id.as_ref().is_some_and(|id| self.history_deletes.contains(id)));
+            if let Err(e) = self.history_store.merge(loaded) {
+                eprintln!("Failed to save history: {}", e);
+            }
+            match self.history_store.load() {
+                Ok(history) => self.history = history,
+                Err(e) => eprintln!("Failed to load history: {}", e),
+            }
+        }
 
-        match self.view {
-            View::Login => self.show_login(ctx, green),
-            View::Main => self.show_main(ctx, green),
+        if result.unauthorized {
+            // One refresh per sync; after that the work waits for the next one
+            if !sync.refreshed && self.refresh_session() {
+                self.history_load |= sync.load;
+                self.sync_history(true);
+            }
+        } else if !result.failed && (sync.again || !self.history_deletes.is_empty()) {
+            self.sync_history(false);
         }
     }
-}
 
-impl App {
-    fn show_login(&mut self, ctx: &egui::Context, green: egui::Color32) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                ui.add_space(100.0);
+    fn tab(&self) -> &RequestTab {
+        &self.tabs[self.active_tab]
+    }
 
-                ui.label(egui::RichText::new("restty").size(48.0).color(green));
-                ui.add_space(10.0);
-                ui.label(egui::RichText::new("Dev-friendly HTTP client").size(16.0).color(egui::Color32::GRAY));
+    fn tab_mut(&mut self) -> &mut RequestTab {
+        &mut self.tabs[self.active_tab]
+    }
 
-                ui.add_space(50.0);
+    fn open_tab(&mut self, tab: RequestTab) {
+        self.tabs.push(tab);
+        self.active_tab = self.tabs.len() - 1;
+        self.url_field_focused = true;
+        self.save_tabs();
+    }
 
-                ui.horizontal(|ui| {
-                    if ui.selectable_label(!self.is_signup, "Login").clicked() {
-                        self.is_signup = false;
-                        self.login_error.clear();
-                    }
-                    if ui.selectable_label(self.is_signup, "Sign Up").clicked() {
-                        self.is_signup = true;
-                        self.login_error.clear();
-                    }
-                });
+    fn close_tab(&mut self, index: usize) {
+        if index >= self.tabs.len() {
+            return;
+        }
+        self.tabs[index].cancel();
+        self.tabs.remove(index);
+        if self.tabs.is_empty() {
+            self.tabs.push(RequestTab::new());
+        }
+        if self.active_tab > index {
+            self.active_tab -= 1;
+        }
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+        self.save_tabs();
+    }
 
-                ui.add_space(20.0);
+    fn cycle_tab(&mut self, forward: bool) {
+        let count = self.tabs.len();
+        self.active_tab = if forward {
+            (self.active_tab + 1) % count
+        } else {
+            (self.active_tab + count - 1) % count
+        };
+    }
 
-                ui.label("Email");
-                let email_field = egui::TextEdit::singleline(&mut self.login_email)
-                    .hint_text("dev@test.com")
-                    .desired_width(300.0);
-                ui.add(email_field);
+    fn save_tabs(&mut self) {
+        SavedTabs {
+            active: self.active_tab,
+            tabs: self.tabs.iter().map(RequestTab::to_saved).collect(),
+        }
+        .save();
+        self.tabs_changed = None;
+    }
 
-                ui.add_space(10.0);
+    /// Schedules saving the tabs; `update` does it after `TABS_SAVE_DELAY`.
+    fn mark_tabs_changed(&mut self) {
+        self.tabs_changed.get_or_insert_with(Instant::now);
+    }
 
-                ui.label("Password");
-                let password_field = egui::TextEdit::singleline(&mut self.login_password)
-                    .password(true)
-                    .hint_text("password123")
-                    .desired_width(300.0);
-                if ui.add(password_field).lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                    self.login();
-                }
+    fn load_history_item(&mut self, item: &HistoryItem) {
+        self.tab_mut().load_history_item(item);
+        self.mark_tabs_changed();
+    }
 
-                ui.add_space(20.0);
+    /// Opens a saved request in its own tab, or switches to the tab that
+    /// already has it. An untouched blank tab is reused.
+    fn open_saved_request(&mut self, request: &SavedRequest) {
+        if let Some(i) = self.tabs.iter().position(|tab| tab.baseline == *request) {
+            self.active_tab = i;
+            return;
+        }
+        if !self.tab().is_blank() {
+            self.open_tab(RequestTab::new());
+        }
+        self.tab_mut().load_saved_request(request);
+        self.save_tabs();
+    }
 
-                let btn_text = if self.is_signup { "Sign Up" } else { "Login" };
-                let login_btn = egui::Button::new(
-                    egui::RichText::new(btn_text).color(egui::Color32::BLACK)
-                ).fill(green).min_size(egui::vec2(300.0, 35.0));
+    fn send_request(&mut self, ctx: &egui::Context) {
+        let vars = self.environments.variables();
+        let tokens = self.oauth_tokens.clone();
+        self.tabs[self.active_tab].send(ctx, &vars, tokens);
+        self.save_tabs();
+    }
 
-                if ui.add(login_btn).clicked() {
-                    self.login();
-                }
+    /// Picks up finished requests in every tab, not just the visible one.
+    fn poll_requests(&mut self) {
+        let finished: Vec<HistoryItem> = self.tabs
+            .iter_mut()
+            .filter_map(|tab| tab.poll().then(|| tab.history_item()))
+            .collect();
+        for item in finished {
+            self.save_to_history(item);
+        }
+    }
+}
 
-                if !self.login_error.is_empty() {
-                    ui.add_space(10.0);
-                    ui.label(egui::RichText::new(&self.login_error).color(egui::Color32::from_rgb(255, 80, 80)));
-                }
+impl RequestTab {
+    fn new() -> Self {
+        let mut tab = Self {
+            url: String::new(),
+            method: Method::GET,
+            websocket: false,
+            socket: None,
+            composer: websocket::Composer::default(),
+            params: Vec::new(),
+            headers: vec![KeyValue::new()],
+            body: String::new(),
+            body_type: BodyType::Json,
+            body_find: Find::new("body_find"),
+            auth: Auth::None,
+            insecure: false,
+            response: String::new(),
+            response_view: viewer::ResponseView::default(),
+            response_spool: None,
+            loading_more: None,
+            stream: None,
+            status: String::new(),
+            time: String::new(),
+            response_headers: Vec::new(),
+            response_meta: ResponseMeta::default(),
+            response_tab: ResponseTab::Body,
+            in_flight: None,
+            curl_error: String::new(),
+            selected_history_id: None,
+            baseline: SavedRequest::default(),
+        };
+        tab.baseline = tab.current_as_saved_request(String::new());
+        tab
+    }
 
-                ui.add_space(20.0);
+    /// Rebuilds a tab left open in the previous session.
+    fn restore(saved: &SavedTab) -> Self {
+        let mut tab = Self::new();
+        tab.set_request(&saved.request);
+        tab.insecure = saved.insecure;
+        tab.baseline = saved.baseline.clone();
+        tab
+    }
 
-                let skip_btn = egui::Button::new(
+    fn to_saved(&self) -> SavedTab {
+        SavedTab {
+            request: self.current_as_saved_request(self.baseline.name.clone()),
+            baseline: self.baseline.clone(),
+            insecure: self.insecure,
+        }
+    }
+
+    fn title(&self) -> String {
+        if !self.baseline.name.is_empty() {
+            return self.baseline.name.clone();
+        }
+        let url = self.url.trim();
+        let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+        if url.is_empty() {
+            "New request".to_string()
+        } else {
+            url.to_string()
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.current_as_saved_request(self.baseline.name.clone()) != self.baseline
+    }
+
+    /// A fresh tab nobody has typed into or sent yet.
+    fn is_blank(&self) -> bool {
+        self.baseline.url.is_empty() && !self.is_dirty() && self.status.is_empty() && !self.is_busy()
+    }
+
+    /// Waiting on a response, reading a stream or connected.
+    fn is_busy(&self) -> bool {
+        self.in_flight.is_some()
+            || self.stream.as_ref().is_some_and(|stream| stream.is_live())
+            || self.socket.as_ref().is_some_and(|socket| socket.is_live())
+    }
+
+    /// "WS" in WebSocket mode, else the HTTP method.
+    fn method_label(&self) -> &str {
+        if self.websocket {
+            "WS"
+        } else {
+            self.method.as_str()
+        }
+    }
+
+    fn set_request(&mut self, request: &SavedRequest) {
+        self.websocket = request.method == "WS";
+        if !self.websocket {
+            self.method = Method::parse(&request.method);
+        }
+        self.url = request.url.clone();
+        self.params = query::parse_params(&self.url);
+        self.headers = request.headers.clone();
+        if self.headers.is_empty() {
+            self.headers.push(KeyValue::new());
+        }
+        self.body = request.body.clone();
+        self.body_type = request.body_type.clone();
+        self.auth = request.auth.clone();
+    }
+
+    fn load_history_item(&mut self, item: &HistoryItem) {
+        self.cancel();
+        self.set_request(&SavedRequest {
+            name: String::new(),
+            method: item.method.clone(),
+            url: item.url.clone(),
+            headers: item.headers.clone(),
+            body: item.body.clone().unwrap_or_default(),
+            body_type: item.body_type.clone(),
+            auth: item.auth.clone(),
+        });
+        self.set_response(item.response.clone(), viewer::Document::new(&item.response, content_type(&item.response_headers)));
+        self.status = item.status.clone();
+        self.time = item.time.clone();
+        self.response_headers = item.response_headers.clone();
+        self.response_meta = ResponseMeta::default();
+        self.response_meta.body_size = self.response.len();
+        self.selected_history_id = item.id.clone();
+        self.baseline = self.current_as_saved_request(String::new());
+    }
+
+    fn load_saved_request(&mut self, request: &SavedRequest) {
+        self.cancel();
+        self.set_request(request);
+        self.selected_history_id = None;
+        self.baseline = request.clone();
+    }
+
+    fn import_curl(&mut self, cmd: &str) -> Result<(), String> {
+        let req = curl::parse(cmd)?;
+
+        self.cancel();
+        self.websocket = false;
+        self.method = Method::parse(&req.method);
+        self.url = req.url;
+        self.params = query::parse_params(&self.url);
+        self.headers = req.headers;
+        if self.headers.is_empty() {
+            self.headers.push(KeyValue::new());
+        }
+        self.body = req.body;
+        self.body_type = req.body_type;
+        self.auth = req.auth;
+        self.insecure = req.insecure;
+        self.selected_history_id = None;
+        self.curl_error.clear();
+        Ok(())
+    }
+
+    fn current_as_saved_request(&self, name: String) -> SavedRequest {
+        SavedRequest {
+            name,
+            method: self.method_label().to_string(),
+            url: self.url.clone(),
+            headers: self.headers.iter().filter(|h| !h.key.is_empty()).cloned().collect(),
+            body: self.body.clone(),
+            body_type: self.body_type.clone(),
+            auth: self.auth.clone(),
+        }
+    }
+
+    fn history_item(&self) -> HistoryItem {
+        // Truncate body and response to prevent flooding database
+        let truncated_body = if self.body.is_empty() {
+            None
+        } else {
+            Some(truncate_string(&self.body, MAX_BODY_SIZE))
+        };
+
+        HistoryItem {
+            id: None,
+            user_id: String::new(),
+            method: self.method_label().to_string(),
+            url: self.url.clone(),
+            headers: self.headers.iter().filter(|h| !h.key.is_empty()).cloned().collect(),
+            body: truncated_body,
+            body_type: self.body_type.clone(),
+            auth: self.auth.without_secrets(),
+            status: self.status.clone(),
+            response: truncate_string(&self.response, MAX_RESPONSE_SIZE),
+            response_headers: self.response_headers.clone(),
+            time: self.time.clone(),
+            created_at: None,
+        }
+    }
+
+    fn substituted_auth(&self, vars: &[(String, String)]) -> Auth {
+        let mut auth = self.auth.clone();
+        // A credential the secret store couldn't give back isn't sent
+        for secret in auth.secrets_mut() {
+            if secrets::is_unresolved(secret) {
+                secret.clear();
+            }
+        }
+        for field in auth.fields_mut() {
+            *field = environments::substitute(field, vars);
+        }
+        auth
+    }
+
+    /// The request as it goes on the wire: environment variables substituted,
+    /// disabled headers dropped and the default Content-Type added.
+    fn resolved_request(&self, vars: &[(String, String)]) -> ResolvedRequest {
+        let headers: Vec<(String, String)> = self.headers
+            .iter()
+            .filter(|h| h.enabled && !h.key.trim().is_empty())
+            .map(|h| (environments::substitute(h.key.trim(), vars), environments::substitute(&h.value, vars)))
+            .collect();
+
+        let mut resolved = ResolvedRequest {
+            method: self.method.as_str().to_string(),
+            url: environments::substitute(&self.url, vars),
+            headers,
+            body: None,
+            digest: None,
+            oauth: None,
+        };
+
+        let authorization = match self.substituted_auth(vars) {
+            Auth::None => None,
+            Auth::Basic { username, password } => Some(auth::basic_authorization(&username, &password)),
+            Auth::Bearer { token } => Some(format!("Bearer {}", token.trim())),
+            Auth::ApiKey { key, value, location } => {
+                match location {
+                    auth::ApiKeyLocation::Header => resolved.headers.push((key.trim().to_string(), value)),
+                    auth::ApiKeyLocation::Query => resolved.url = query::append_param(&resolved.url, key.trim(), &value),
+                }
+                None
+            }
+            Auth::Digest { username, password } => {
+                resolved.digest = Some((username, password));
+                None
+            }
+            Auth::OAuth2(config) => {
+                resolved.headers.retain(|(key, _)| !key.eq_ignore_ascii_case("authorization"));
+                resolved.oauth = Some(*config);
+                None
+            }
+        };
+        if let Some(authorization) = authorization {
+            // The Auth section wins over a hand-written header
+            resolved.headers.retain(|(key, _)| !key.eq_ignore_ascii_case("authorization"));
+            resolved.headers.push(("Authorization".to_string(), authorization));
+        }
+
+        let mut body_type = self.body_type.clone();
+        for field in body_type.fields_mut() {
+            *field = environments::substitute(field, vars);
+        }
+        // WebSocket messages are sent separately, not as a body
+        let payload = if self.websocket { None } else { body_type.payload(&environments::substitute(&self.body, vars)) };
+        if let Some((payload, content_type)) = payload {
+            // A multipart body is useless without our boundary
+            if matches!(payload, Payload::Multipart { .. }) {
+                resolved.headers.retain(|(key, _)| !key.eq_ignore_ascii_case("content-type"));
+            }
+            if !resolved.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-type")) {
+                resolved.headers.push(("Content-Type".to_string(), content_type));
+            }
+            resolved.body = Some(payload);
+        }
+
+        resolved
+    }
+
+    fn send(&mut self, ctx: &egui::Context, vars: &[(String, String)], tokens: oauth::TokenCache) {
+        if self.in_flight.is_some() {
+            return;
+        }
+        if self.websocket {
+            return self.connect(ctx, vars);
+        }
+
+        let resolved = self.resolved_request(vars);
+
+        let method = match reqwest::Method::from_bytes(resolved.method.as_bytes()) {
+            Ok(method) => method,
+            Err(_) => return self.fail(format!("Invalid HTTP method: {:?}", resolved.method)),
+        };
+
+        // No overall timeout: streams and large downloads can go quiet for a
+        // while, and a request can always be cancelled
+        let client = match reqwest::Client::builder().danger_accept_invalid_certs(self.insecure).connect_timeout(CONNECT_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => return self.fail(e.to_string()),
+        };
+        let token_client = match Client::builder().danger_accept_invalid_certs(self.insecure).build() {
+            Ok(client) => client,
+            Err(e) => return self.fail(e.to_string()),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = cancelled.clone();
+        let progress = Arc::new(Progress::default());
+        let worker_progress = progress.clone();
+        let ctx = ctx.clone();
+
+        thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    tx.send(Err(e.to_string())).ok();
+                    return ctx.request_repaint();
+                }
+            };
+            // The token is fetched with the blocking client, outside the runtime
+            let token = match &resolved.oauth {
+                Some(config) => oauth::access_token(&token_client, config, &tokens, &worker_cancelled).map(Some),
+                None => Ok(None),
+            };
+            let result = token.and_then(|token| {
+                runtime.block_on(execute_request(&client, &method, &resolved, token.as_deref(), &worker_cancelled, &worker_progress, &ctx))
+            });
+            // A rejected token is no use next time either
+            if let (Some(config), Ok((resp, _))) = (&resolved.oauth, &result) {
+                if resp.status.starts_with("401") {
+                    tokens.remove(config);
+                }
+            }
+            let (result, stream) = match result {
+                Ok((resp, stream)) => (Ok(resp), stream),
+                Err(e) => (Err(e), None),
+            };
+            // The receiver is gone if the request was cancelled, so a failed send is fine
+            tx.send(result).ok();
+            ctx.request_repaint();
+            if let Some((resp, feed)) = stream {
+                runtime.block_on(read_stream(resp, feed));
+            }
+        });
+
+        self.in_flight = Some(InFlightRequest {
+            started: Instant::now(),
+            cancelled,
+            progress,
+            rx,
+        });
+    }
+
+    /// Opens the WebSocket connection, or sends the composed message if
+    /// it's already open.
+    fn connect(&mut self, ctx: &egui::Context, vars: &[(String, String)]) {
+        if let Some(socket) = &mut self.socket {
+            if socket.is_open() {
+                return self.composer.send(socket);
+            }
+            if socket.is_live() {
+                return;
+            }
+        }
+        let resolved = self.resolved_request(vars);
+        self.set_response(String::new(), viewer::Document::default());
+        self.status.clear();
+        self.time.clear();
+        self.response_headers.clear();
+        self.response_meta = ResponseMeta::default();
+        self.socket = Some(websocket::connect(&resolved.url, &resolved.headers, self.insecure, ctx));
+    }
+
+    /// Replaces the response body together with the view built from it.
+    fn set_response(&mut self, body: String, document: viewer::Document) {
+        self.response = body;
+        self.response_view.set_document(document);
+        self.response_spool = None;
+        self.loading_more = None;
+        self.stream = None;
+        self.socket = None;
+    }
+
+    /// Reads the next chunk of a spooled body in the background and shows
+    /// everything up to it.
+    fn load_more(&mut self, ctx: &egui::Context) {
+        let Some(spool) = &self.response_spool else { return };
+        let path = spool.path.clone();
+        let limit = spool.shown + PREVIEW_BYTES;
+        let content_type = content_type(&self.response_headers).to_string();
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+
+        thread::spawn(move || {
+            let result = read_prefix(&path, limit).map(|bytes| {
+                let len = bytes.len();
+                let (body, document) = viewer::Document::from_bytes(bytes, &content_type);
+                (len, body, document)
+            });
+            tx.send(result).ok();
+            ctx.request_repaint();
+        });
+        self.loading_more = Some(rx);
+    }
+
+    /// Shows an error in place of a response, for requests that never went out.
+    fn fail(&mut self, message: String) {
+        self.status = "Error".to_string();
+        self.set_response(message.clone(), viewer::Document::new(&message, ""));
+        self.response_headers.clear();
+        self.response_meta = ResponseMeta::default();
+    }
+
+    fn cancel(&mut self) {
+        if let Some(stream) = &mut self.stream {
+            stream.stop();
+        }
+        if let Some(socket) = &mut self.socket {
+            socket.close();
+        }
+        if let Some(in_flight) = self.in_flight.take() {
+            in_flight.cancelled.store(true, Ordering::Relaxed);
+            self.status = "Cancelled".to_string();
+            self.time = format!("{:.0?}", in_flight.started.elapsed());
+            self.set_response(String::new(), viewer::Document::default());
+            self.response_headers.clear();
+            self.response_meta = ResponseMeta::default();
+        }
+    }
+
+    /// Takes the response if the request finished. Returns true if it
+    /// succeeded, so it can go into history. Streams go in once they end.
+    fn poll(&mut self) -> bool {
+        if let Some(socket) = &mut self.socket {
+            let ended = socket.poll();
+            if let Some((status, headers)) = socket.take_handshake() {
+                self.status = status;
+                self.response_headers = headers;
+            }
+            // Connections that never opened aren't worth keeping
+            if ended && socket.opened() {
+                self.time = format!("{:.0?}", socket.elapsed());
+                self.response = socket.transcript();
+                self.response_meta.body_size = self.response.len();
+                return true;
+            }
+        }
+        if let Some(stream) = &mut self.stream {
+            if stream.poll() {
+                self.response = stream.text().to_string();
+                self.response_meta.body_size = stream.received();
+                return true;
+            }
+        }
+
+        if let Some(rx) = &self.loading_more {
+            match rx.try_recv() {
+                Ok(Ok((len, body, document))) => {
+                    self.response = body;
+                    self.response_view.set_document(document);
+                    if let Some(spool) = &mut self.response_spool {
+                        spool.shown = len;
+                    }
+                    self.loading_more = None;
+                }
+                Ok(Err(e)) => {
+                    eprintln!("Failed to load more of the response: {}", e);
+                    self.loading_more = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => self.loading_more = None,
+            }
+        }
+
+        let result = match &self.in_flight {
+            Some(in_flight) => match in_flight.rx.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => Err("Request worker stopped unexpectedly".to_string()),
+            },
+            None => return false,
+        };
+        self.in_flight = None;
+
+        match result {
+            Ok(resp) => {
+                self.time = format!("{:.0?}", resp.elapsed);
+                self.status = resp.status;
+                self.response_headers = resp.headers;
+                self.response_meta = resp.meta;
+                self.set_response(resp.body, resp.document);
+                self.response_spool = resp.spool;
+                match resp.stream {
+                    Some(live) => {
+                        self.stream = Some(stream::Stream::new(live));
+                        false
+                    }
+                    None => true,
+                }
+            }
+            Err(e) => {
+                self.status = "Error".to_string();
+                self.set_response(e.clone(), viewer::Document::new(&e, ""));
+                self.response_headers.clear();
+                self.response_meta = ResponseMeta::default();
+                false
+            }
+        }
+    }
+}
+
+struct InFlightRequest {
+    started: Instant,
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<Progress>,
+    rx: mpsc::Receiver<Result<HttpResponse, String>>,
+}
+
+/// How far the worker is with downloading the body.
+#[derive(Default)]
+struct Progress {
+    received: AtomicU64,
+    /// The Content-Length, or 0 if the server didn't send one.
+    expected: AtomicU64,
+}
+
+struct HttpResponse {
+    status: String,
+    headers: Vec<(String, String)>,
+    meta: ResponseMeta,
+    /// At most `PREVIEW_BYTES` of the body; the rest is in `spool`.
+    body: String,
+    document: viewer::Document,
+    spool: Option<Spool>,
+    /// For event streams, which are read on after the response arrives.
+    stream: Option<stream::Live>,
+    elapsed: Duration,
+}
+
+/// A response body too large to keep in memory, downloaded to a temporary
+/// file. The file is removed when the response is replaced.
+struct Spool {
+    path: PathBuf,
+    len: usize,
+    /// How much of it is shown.
+    shown: usize,
+}
+
+/// The start of a spooled body read back in: its length, text and view.
+type SpoolPrefix = (usize, String, viewer::Document);
+
+impl Spool {
+    fn create() -> Result<(Self, fs::File), String> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("restty-{}-{}.body", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        let file = fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        Ok((Self { path, len: 0, shown: 0 }, file))
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Up to `limit` bytes from the start of a file, cut back to whole UTF-8
+/// characters if it looks like text.
+fn read_prefix(path: &std::path::Path, limit: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)
+        .and_then(|file| file.take(limit as u64).read_to_end(&mut bytes))
+        .map_err(|e| e.to_string())?;
+    trim_partial_char(&mut bytes);
+    Ok(bytes)
+}
+
+/// Drops a UTF-8 sequence cut off at the end, so that a prefix of a text
+/// body still decodes as text.
+fn trim_partial_char(bytes: &mut Vec<u8>) {
+    if let Err(e) = std::str::from_utf8(bytes) {
+        if e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+}
+
+/// A byte count for people: "512 bytes", "3.4 KB", "120.0 MB".
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} bytes", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Builds the request to send. Called again for the Digest retry, since a
+/// body streamed from a file can't be cloned.
+fn build_request(client: &reqwest::Client, method: &reqwest::Method, request: &ResolvedRequest, token: Option<&str>) -> Result<reqwest::RequestBuilder, String> {
+    let mut req = client.request(method.clone(), &request.url);
+    for (key, value) in &request.headers {
+        req = req.header(key, value);
+    }
+    if let Some(token) = token {
+        req = req.bearer_auth(token);
+    }
+    match &request.body {
+        Some(Payload::Text(text)) => req = req.body(text.clone()),
+        Some(Payload::Multipart { boundary, fields }) => req = req.body(body::encode_multipart(boundary, fields)?),
+        Some(Payload::File(path)) => {
+            let file = fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+            req = req.body(tokio::fs::File::from_std(file));
+        }
+        None => {}
+    }
+    Ok(req)
+}
+
+/// Waits for `future` unless `stop` is set first. The flag is checked every
+/// `POLL_INTERVAL`, so a cancel takes effect while the server is quiet
+/// rather than whenever it next sends something.
+async fn unless_stopped<F: std::future::Future>(stop: &AtomicBool, future: F) -> Result<F::Output, String> {
+    let mut future = std::pin::pin!(future);
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        if let Ok(output) = tokio::time::timeout(POLL_INTERVAL, &mut future).await {
+            return Ok(output);
+        }
+    }
+}
+
+/// A streaming response and where to pass on what is read from it.
+type StreamBody = (reqwest::Response, stream::Feed);
+
+/// Runs on the worker thread. The body is read in chunks so that a cancel
+/// drops the connection instead of waiting for the download to finish, and
+/// only the first `PREVIEW_BYTES` of it are kept in memory. Streams are
+/// returned unread, to be read on once the response has been shown.
+async fn execute_request(
+    client: &reqwest::Client,
+    method: &reqwest::Method,
+    request: &ResolvedRequest,
+    token: Option<&str>,
+    cancelled: &AtomicBool,
+    progress: &Progress,
+    ctx: &egui::Context,
+) -> Result<(HttpResponse, Option<StreamBody>), String> {
+    let start = Instant::now();
+    let send = |req: reqwest::RequestBuilder| unless_stopped(cancelled, req.send());
+    let mut resp = send(build_request(client, method, request, token)?).await?.map_err(|e| e.to_string())?;
+
+    if let Some((username, password)) = &request.digest {
+        let challenge = resp
+            .headers()
+            .get_all(reqwest::header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find(|value| value.trim_start().to_ascii_lowercase().starts_with("digest"));
+        if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(challenge) = challenge {
+                let creds = DigestCredentials {
+                    username: username.clone(),
+                    password: password.clone(),
+                    method: request.method.clone(),
+                    url: request.url.clone(),
+                };
+                let authorization = auth::digest_authorization(&creds, challenge)?;
+                let retry = build_request(client, method, request, token)?.header(reqwest::header::AUTHORIZATION, authorization);
+                resp = send(retry).await?.map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    let headers: Vec<(String, String)> = resp
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    let mut meta = ResponseMeta {
+        http_version: format!("{:?}", resp.version()),
+        remote_addr: resp.remote_addr().map(|addr| addr.to_string()),
+        content_length: resp.content_length(),
+        body_size: 0,
+    };
+
+    // Streams never finish, so they're handed over to be read as they arrive
+    if let Some(format) = stream::Format::detect(content_type(&headers)) {
+        let (feed, live) = stream::channel(format, ctx.clone());
+        let response = HttpResponse {
+            status: resp.status().to_string(),
+            headers,
+            meta,
+            body: String::new(),
+            document: viewer::Document::default(),
+            spool: None,
+            stream: Some(live),
+            elapsed: start.elapsed(),
+        };
+        return Ok((response, Some((resp, feed))));
+    }
+
+    progress.expected.store(meta.content_length.unwrap_or(0), Ordering::Relaxed);
+
+    let mut bytes = Vec::new();
+    let mut spool: Option<(Spool, fs::File)> = None;
+    let mut received = 0;
+    while let Some(chunk) = unless_stopped(cancelled, resp.chunk()).await?.map_err(|e| e.to_string())? {
+        let n = chunk.len();
+        received += n;
+        progress.received.store(received as u64, Ordering::Relaxed);
+
+        if spool.is_none() && received > PREVIEW_BYTES {
+            let (new_spool, mut file) = Spool::create()?;
+            file.write_all(&bytes).map_err(|e| e.to_string())?;
+            spool = Some((new_spool, file));
+        }
+        match &mut spool {
+            Some((_, file)) => {
+                file.write_all(&chunk).map_err(|e| e.to_string())?;
+                let room = PREVIEW_BYTES.saturating_sub(bytes.len());
+                bytes.extend_from_slice(&chunk[..room.min(n)]);
+            }
+            None => bytes.extend_from_slice(&chunk),
+        }
+    }
+
+    meta.body_size = received;
+    let spool = spool.map(|(mut spool, _)| {
+        trim_partial_char(&mut bytes);
+        spool.len = received;
+        spool.shown = bytes.len();
+        spool
+    });
+    let (body, document) = viewer::Document::from_bytes(bytes, content_type(&headers));
+
+    let response = HttpResponse {
+        status: resp.status().to_string(),
+        headers,
+        meta,
+        body,
+        document,
+        spool,
+        stream: None,
+        elapsed: start.elapsed(),
+    };
+    Ok((response, None))
+}
+
+/// Reads a streaming body until the server closes it or it is stopped.
+/// Returning drops the response, which closes the connection.
+async fn read_stream(mut resp: reqwest::Response, mut feed: stream::Feed) {
+    loop {
+        match unless_stopped(feed.stopped(), resp.chunk()).await {
+            Ok(Ok(Some(chunk))) => {
+                if !feed.push(&chunk) {
+                    return;
+                }
+            }
+            Ok(Ok(None)) => return feed.finish(),
+            Ok(Err(e)) => return feed.fail(e.to_string()),
+            Err(_) => return,
+        }
+    }
+}
+
+/// The first value of a response header, matched case-insensitively.
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+}
+
+fn content_type(headers: &[(String, String)]) -> &str {
+    header(headers, "content-type").unwrap_or_default()
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let green = egui::Color32::from_rgb(0, 200, 120);
+
+        let active_tab = self.active_tab;
+        self.poll_requests();
+        self.poll_history_sync();
+        if self.tabs.iter().any(|tab| tab.in_flight.is_some()) || self.history_sync.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        let mut send = false;
+        let mut cancel = false;
+        let mut new_tab = false;
+        let mut close_tab = false;
+        let mut cycle_tab = None;
+        let mut find = false;
+        ctx.input_mut(|i| {
+            if self.view != View::Main {
+                return;
+            }
+            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::Tab) {
+                cycle_tab = Some(false);
+            } else if i.consume_key(egui::Modifiers::CTRL, egui::Key::Tab) {
+                cycle_tab = Some(true);
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, egui::Key::T) {
+                new_tab = true;
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, egui::Key::W) {
+                close_tab = true;
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, egui::Key::F) {
+                find = true;
+            }
+        });
+        ctx.input(|i| {
+            if i.key_pressed(egui::Key::L) && i.modifiers.command {
+                self.url_field_focused = true;
+            }
+            if i.key_pressed(egui::Key::H) && i.modifiers.command && self.view == View::Main {
+                self.show_history = !self.show_history;
+            }
+            if i.key_pressed(egui::Key::Enter) && i.modifiers.command && self.view == View::Main && !self.tab().url.is_empty() {
+                send = true;
+            }
+            if i.key_pressed(egui::Key::Escape) && self.tab().is_busy() {
+                cancel = true;
+            }
+        });
+
+        if send {
+            self.send_request(ctx);
+        }
+        if cancel {
+            self.tab_mut().cancel();
+        }
+        if new_tab {
+            self.open_tab(RequestTab::new());
+        }
+        if close_tab {
+            self.close_tab(self.active_tab);
+        }
+        if let Some(forward) = cycle_tab {
+            self.cycle_tab(forward);
+        }
+        if find {
+            // Find in the body while editing it, in the response otherwise
+            let tab = self.tab_mut();
+            let in_body = ctx.memory(|m| m.has_focus(body::editor_id())) || tab.body_find.has_focus(ctx);
+            if in_body && tab.body_type.uses_text() {
+                tab.body_find.open();
+            } else {
+                tab.response_view.open_find();
+            }
+        }
+
+        match self.view {
+            View::Login => self.show_login(ctx, green),
+            View::Main => self.show_main(ctx, green),
+            View::Settings => self.show_settings(ctx, green),
+        }
+
+        if self.active_tab != active_tab {
+            self.mark_tabs_changed();
+        }
+        if let Some(changed) = self.tabs_changed {
+            let wait = TABS_SAVE_DELAY.saturating_sub(changed.elapsed());
+            if wait.is_zero() {
+                self.save_tabs();
+            } else {
+                ctx.request_repaint_after(wait);
+            }
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_tabs();
+    }
+}
+
+impl App {
+    fn show_login(&mut self, ctx: &egui::Context, green: egui::Color32) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(100.0);
+
+                ui.label(egui::RichText::new("restty").size(48.0).color(green));
+                ui.add_space(10.0);
+                ui.label(egui::RichText::new("Dev-friendly HTTP client").size(16.0).color(egui::Color32::GRAY));
+
+                ui.add_space(50.0);
+
+                ui.horizontal(|ui| {
+                    if ui.selectable_label(!self.is_signup, "Login").clicked() {
+                        self.is_signup = false;
+                        self.login_error.clear();
+                    }
+                    if ui.selectable_label(self.is_signup, "Sign Up").clicked() {
+                        self.is_signup = true;
+                        self.login_error.clear();
+                    }
+                });
+
+                ui.add_space(20.0);
+
+                ui.label("Email");
+                let email_field = egui::TextEdit::singleline(&mut self.login_email)
+                    .hint_text("dev@test.com")
+                    .desired_width(300.0);
+                ui.add(email_field);
+
+                ui.add_space(10.0);
+
+                ui.label("Password");
+                let password_field = egui::TextEdit::singleline(&mut self.login_password)
+                    .password(true)
+                    .hint_text("password123")
+                    .desired_width(300.0);
+                if ui.add(password_field).lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    self.login();
+                }
+
+                ui.add_space(20.0);
+
+                let btn_text = if self.is_signup { "Sign Up" } else { "Login" };
+                let login_btn = egui::Button::new(
+                    egui::RichText::new(btn_text).color(egui::Color32::BLACK)
+                ).fill(green).min_size(egui::vec2(300.0, 35.0));
+
+                if ui.add(login_btn).clicked() {
+                    self.login();
+                }
+
+                if !self.login_error.is_empty() {
+                    ui.add_space(10.0);
+                    ui.label(egui::RichText::new(&self.login_error).color(egui::Color32::from_rgb(255, 80, 80)));
+                }
+
+                ui.add_space(20.0);
+
+                let skip_btn = egui::Button::new(
                     egui::RichText::new("Continue without login").color(egui::Color32::GRAY)
                 ).fill(egui::Color32::from_rgb(40, 40, 40)).min_size(egui::vec2(300.0, 30.0));
 
@@ -555,14 +1880,134 @@ impl App {
                 }
 
                 ui.add_space(20.0);
-                ui.label(egui::RichText::new("Login to save and sync request history").size(12.0).color(egui::Color32::DARK_GRAY));
+                ui.label(egui::RichText::new("Login to sync request history across devices").size(12.0).color(egui::Color32::DARK_GRAY));
                 ui.add_space(10.0);
                 ui.label(egui::RichText::new("Hint: Use dev@test.com / password123").size(12.0).color(egui::Color32::DARK_GRAY));
             });
         });
     }
 
+    fn open_settings(&mut self) {
+        self.settings_url = self.config.supabase_url.clone();
+        self.settings_anon_key = self.config.supabase_anon_key.clone();
+        self.settings_message.clear();
+        self.view = View::Settings;
+    }
+
+    fn apply_settings(&mut self, config: Config) {
+        let previous_url = self.config.supabase_url.clone();
+        if let Err(e) = config.save() {
+            self.settings_message = format!("Failed to save settings: {}", e);
+            return;
+        }
+
+        // Overrides from the environment or flags still win for this session
+        self.config = Config::load(&self.cli_args).unwrap_or(config);
+
+        if self.config.supabase_url != previous_url && self.token.is_some() {
+            // The session belongs to the previous backend
+            self.logout();
+        } else {
+            self.reset_history_store();
+        }
+        self.settings_message = "Settings saved".to_string();
+    }
+
+    fn show_settings(&mut self, ctx: &egui::Context, green: egui::Color32) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add_space(20.0);
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("Settings").size(24.0).color(green));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.add_space(20.0);
+                    if ui.button("Back").clicked() {
+                        self.view = View::Main;
+                    }
+                });
+            });
+
+            ui.add_space(20.0);
+
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                ui.vertical(|ui| {
+                    ui.label(egui::RichText::new("Backend").size(16.0));
+                    ui.label(egui::RichText::new("Point restty at your own Supabase project or a compatible PostgREST server.").size(12.0).color(egui::Color32::GRAY));
+                    ui.add_space(10.0);
+
+                    ui.label("Supabase URL");
+                    ui.add(egui::TextEdit::singleline(&mut self.settings_url).hint_text("https://<project>.supabase.co").desired_width(500.0));
+                    ui.add_space(10.0);
+
+                    ui.label("Anon key");
+                    ui.add(egui::TextEdit::singleline(&mut self.settings_anon_key).desired_width(500.0));
+                    ui.add_space(10.0);
+
+                    if !self.config.overrides.is_empty() {
+                        ui.label(egui::RichText::new(format!("Overridden for this session by: {}", self.config.overrides.join(", "))).size(12.0).color(egui::Color32::from_rgb(255, 180, 0)));
+                        ui.add_space(10.0);
+                    }
+
+                    ui.horizontal(|ui| {
+                        let save_btn = egui::Button::new(
+                            egui::RichText::new("Save").color(egui::Color32::BLACK)
+                        ).fill(green);
+                        if ui.add(save_btn).clicked() {
+                            let config = Config {
+                                supabase_url: self.settings_url.trim().trim_end_matches('/').to_string(),
+                                supabase_anon_key: self.settings_anon_key.trim().to_string(),
+                                overrides: Vec::new(),
+                            };
+                            self.apply_settings(config);
+                        }
+                        if ui.button("Reset to default").clicked() {
+                            let config = Config::default();
+                            self.settings_url = config.supabase_url.clone();
+                            self.settings_anon_key = config.supabase_anon_key.clone();
+                            self.apply_settings(config);
+                        }
+                    });
+
+                    if !self.settings_message.is_empty() {
+                        ui.add_space(10.0);
+                        ui.label(egui::RichText::new(&self.settings_message).color(egui::Color32::GRAY));
+                    }
+                });
+            });
+        });
+    }
+
     fn show_main(&mut self, ctx: &egui::Context, green: egui::Color32) {
+        if self.show_environments {
+            self.show_environments_window(ctx);
+        }
+
+        if self.save_dialog.is_some() {
+            self.show_save_dialog(ctx);
+        }
+
+        if self.curl_import.is_some() {
+            self.show_curl_import(ctx);
+        }
+
+        if self.export_language.is_some() {
+            self.show_export(ctx);
+        }
+
+        if self.response_save.is_some() {
+            self.show_response_save(ctx);
+        }
+
+        if self.show_collections {
+            egui::SidePanel::left("collections_panel")
+                .resizable(true)
+                .default_width(300.0)
+                .show(ctx, |ui| {
+                    self.show_collections_panel(ui, green);
+                });
+        }
+
         if self.show_history {
             egui::SidePanel::left("history_panel")
                 .resizable(false)
@@ -578,98 +2023,286 @@ impl App {
             ui.horizontal(|ui| {
                 ui.add_space(20.0);
                 ui.label(egui::RichText::new("restty").size(24.0).color(green));
+                ui.add_space(20.0);
+                self.show_environment_selector(ui);
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.add_space(20.0);
 
+                    let collections_btn_text = if self.show_collections { "Hide Collections" } else { "Collections" };
+
+                    if ui.button("Settings").clicked() {
+                        self.open_settings();
+                    }
+
                     if self.token.is_some() {
                         if ui.button("Logout").clicked() {
                             self.logout();
                         }
                         ui.label(egui::RichText::new(self.email.as_ref().unwrap_or(&"".to_string())).color(egui::Color32::GRAY));
+                    } else if ui.button("Login").clicked() {
+                        self.view = View::Login;
+                    }
 
-                        let history_btn_text = if self.show_history { "Hide History" } else { "Show History" };
-                        if ui.button(history_btn_text).clicked() {
-                            self.show_history = !self.show_history;
-                        }
-                    } else {
-                        if ui.button("Login").clicked() {
-                            self.view = View::Login;
-                        }
+                    let history_btn_text = if self.show_history { "Hide History" } else { "Show History" };
+                    if ui.button(history_btn_text).clicked() {
+                        self.show_history = !self.show_history;
+                    }
+
+                    if ui.button(collections_btn_text).clicked() {
+                        self.show_collections = !self.show_collections;
                     }
                 });
             });
 
             ui.add_space(20.0);
 
+            let mut tab_action = None;
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                let labels: Vec<TabLabel> = self.tabs
+                    .iter()
+                    .map(|tab| TabLabel {
+                        method: tab.method_label().to_string(),
+                        title: tab.title(),
+                        dirty: tab.is_dirty(),
+                        busy: tab.is_busy(),
+                    })
+                    .collect();
+                tab_action = tabs::show_strip(ui, &labels, self.active_tab, green);
+                ui.add_space(20.0);
+            });
+            match tab_action {
+                Some(TabAction::Select(i)) => self.active_tab = i,
+                Some(TabAction::Close(i)) => self.close_tab(i),
+                Some(TabAction::New) => self.open_tab(RequestTab::new()),
+                None => {}
+            }
+
+            ui.add_space(10.0);
+
+            let vars = self.environments.variables();
+            let mut send = false;
+            let mut save_response = false;
+            let mut load_more = false;
+            // Any change to the request, so the tabs get saved
+            let mut edited = false;
+            let tab = &mut self.tabs[self.active_tab];
+
             ui.horizontal(|ui| {
                 ui.add_space(20.0);
 
-                egui::ComboBox::from_id_source("method")
-                    .selected_text(egui::RichText::new(self.method.as_str()).color(green))
-                    .width(80.0)
+                egui::ComboBox::from_id_source("protocol")
+                    .selected_text(if tab.websocket { "WebSocket" } else { "HTTP" })
+                    .width(90.0)
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.method, Method::GET, "GET");
-                        ui.selectable_value(&mut self.method, Method::POST, "POST");
-                        ui.selectable_value(&mut self.method, Method::PUT, "PUT");
-                        ui.selectable_value(&mut self.method, Method::DELETE, "DELETE");
-                        ui.selectable_value(&mut self.method, Method::PATCH, "PATCH");
+                        edited |= ui.selectable_value(&mut tab.websocket, false, "HTTP").changed();
+                        edited |= ui.selectable_value(&mut tab.websocket, true, "WebSocket").changed();
                     });
 
+                if !tab.websocket {
+                    egui::ComboBox::from_id_source("method")
+                        .selected_text(egui::RichText::new(tab.method.as_str()).color(green))
+                        .width(80.0)
+                        .show_ui(ui, |ui| {
+                            for method in Method::STANDARD {
+                                let label = method.as_str().to_string();
+                                edited |= ui.selectable_value(&mut tab.method, method, label).changed();
+                            }
+                            let is_custom = matches!(tab.method, Method::Custom(_));
+                            if ui.selectable_label(is_custom, "Custom...").clicked() && !is_custom {
+                                tab.method = Method::Custom(String::new());
+                                edited = true;
+                            }
+                        });
+
+                    if let Method::Custom(name) = &mut tab.method {
+                        edited |= ui.add(
+                            egui::TextEdit::singleline(name)
+                                .hint_text("METHOD")
+                                .desired_width(90.0)
+                        ).changed();
+                    }
+                }
+
+                let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                    let job = environments::highlight(ui, text, &vars, wrap_width, false);
+                    ui.fonts(|f| f.layout_job(job))
+                };
                 let url_response = ui.add(
-                    egui::TextEdit::singleline(&mut self.url)
+                    egui::TextEdit::singleline(&mut tab.url)
                         .hint_text("Enter URL... (Cmd+L to focus)")
-                        .desired_width(ui.available_width() - 100.0)
+                        .desired_width(ui.available_width() - 320.0)
+                        .layouter(&mut layouter)
                 );
 
+                // Only a pasted command is imported; typing one is left alone
+                // until it's complete, and can be imported from the cURL button
+                let pasted = url_response.has_focus() && ui.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Paste(_))));
+                if url_response.changed() {
+                    edited = true;
+                    if pasted && curl::looks_like_curl(&tab.url) {
+                        let cmd = tab.url.clone();
+                        if let Err(e) = tab.import_curl(&cmd) {
+                            tab.curl_error = e;
+                        }
+                    } else if !curl::looks_like_curl(&tab.url) {
+                        tab.curl_error.clear();
+                        tab.sync_params_from_url();
+                    }
+                }
+
                 if self.url_field_focused {
                     url_response.request_focus();
                     self.url_field_focused = false;
                 }
 
-                let send_btn = egui::Button::new(
-                    egui::RichText::new("Send").color(egui::Color32::BLACK)
-                ).fill(green);
+                if tab.socket.as_ref().is_some_and(|socket| socket.is_live()) {
+                    let disconnect_btn = egui::Button::new(
+                        egui::RichText::new("Disconnect").color(egui::Color32::BLACK)
+                    ).fill(egui::Color32::from_rgb(255, 80, 80));
+
+                    if ui.add(disconnect_btn).clicked() {
+                        tab.cancel();
+                    }
+                } else if tab.in_flight.is_some() {
+                    let cancel_btn = egui::Button::new(
+                        egui::RichText::new("Cancel").color(egui::Color32::BLACK)
+                    ).fill(egui::Color32::from_rgb(255, 80, 80));
+
+                    if ui.add(cancel_btn).clicked() {
+                        tab.cancel();
+                    }
+                } else {
+                    let send_btn = egui::Button::new(
+                        egui::RichText::new(if tab.websocket { "Connect" } else { "Send" }).color(egui::Color32::BLACK)
+                    ).fill(green);
+
+                    if ui.add(send_btn).clicked() && !tab.url.is_empty() {
+                        send = true;
+                    }
+                }
+
+                if ui.button("cURL").on_hover_text("Import a curl command").clicked() {
+                    self.curl_import = Some(String::new());
+                }
+
+                if !tab.websocket && ui.button("Export").on_hover_text("Export as code").clicked() {
+                    self.export_language = Some(codegen::Language::Curl);
+                }
 
-                if ui.add(send_btn).clicked() && !self.url.is_empty() {
-                    self.send_request();
+                if ui.button("Save").clicked() {
+                    self.save_dialog = Some(SaveDialog {
+                        name: if tab.baseline.name.is_empty() { tab.url.clone() } else { tab.baseline.name.clone() },
+                        folder: self.collections.folder_paths().first().map(|(path, _)| path.clone()),
+                        new_collection: String::new(),
+                    });
                 }
 
                 ui.add_space(20.0);
             });
 
-            if self.method == Method::POST || self.method == Method::PUT || self.method == Method::PATCH {
-                ui.add_space(10.0);
+            if !tab.curl_error.is_empty() {
                 ui.horizontal(|ui| {
                     ui.add_space(20.0);
-                    ui.label("Body:");
+                    ui.label(egui::RichText::new(format!("cURL import failed: {}", tab.curl_error)).size(12.0).color(egui::Color32::from_rgb(255, 80, 80)));
                 });
+            }
+
+            let mut unresolved = environments::unresolved(&tab.url, &vars);
+            for header in tab.headers.iter().filter(|h| h.enabled) {
+                unresolved.extend(environments::unresolved(&header.key, &vars));
+                unresolved.extend(environments::unresolved(&header.value, &vars));
+            }
+            if tab.body_type.uses_text() {
+                unresolved.extend(environments::unresolved(&tab.body, &vars));
+            }
+            for field in tab.body_type.clone().fields_mut() {
+                unresolved.extend(environments::unresolved(field, &vars));
+            }
+            for field in tab.auth.clone().fields_mut() {
+                unresolved.extend(environments::unresolved(field, &vars));
+            }
+            unresolved.sort();
+            unresolved.dedup();
+            if !unresolved.is_empty() {
                 ui.horizontal(|ui| {
                     ui.add_space(20.0);
-                    let body_field = egui::TextEdit::multiline(&mut self.body)
-                        .hint_text("JSON body...")
-                        .desired_width(ui.available_width() - 40.0)
-                        .desired_rows(4);
-                    ui.add(body_field);
-                    ui.add_space(20.0);
+                    let names = unresolved.iter().map(|name| format!("{{{{{}}}}}", name)).collect::<Vec<_>>().join(", ");
+                    ui.label(egui::RichText::new(format!("Unresolved variables: {}", names)).size(12.0).color(egui::Color32::from_rgb(255, 80, 80)));
                 });
             }
 
+            ui.add_space(10.0);
+            edited |= tab.show_params_editor(ui);
+            edited |= tab.show_headers_editor(ui);
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                let title = if tab.auth.is_none() { "Auth".to_string() } else { format!("Auth ({})", tab.auth.label()) };
+                egui::CollapsingHeader::new(title)
+                    .id_source("auth_editor")
+                    .show(ui, |ui| {
+                        edited |= auth::show_editor(ui, &mut tab.auth);
+                        if let Auth::OAuth2(config) = tab.substituted_auth(&vars) {
+                            oauth::show_token_status(ui, &self.oauth_tokens, &config);
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                egui::CollapsingHeader::new("Options")
+                    .id_source("request_options")
+                    .show(ui, |ui| {
+                        edited |= ui.checkbox(&mut tab.insecure, "Skip TLS certificate verification").changed();
+                    });
+            });
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                ui.vertical(|ui| {
+                    if tab.websocket {
+                        tab.composer.show(ui, tab.socket.as_mut());
+                    } else {
+                        edited |= body::show_editor(ui, &mut tab.body_type, &mut tab.body, &vars, &mut tab.body_find);
+                    }
+                });
+                ui.add_space(20.0);
+            });
+
             ui.add_space(20.0);
 
-            if !self.status.is_empty() {
+            if let Some(in_flight) = &tab.in_flight {
+                ui.horizontal(|ui| {
+                    ui.add_space(20.0);
+                    ui.spinner();
+                    let elapsed = in_flight.started.elapsed().as_secs_f32();
+                    let received = in_flight.progress.received.load(Ordering::Relaxed) as usize;
+                    let expected = in_flight.progress.expected.load(Ordering::Relaxed) as usize;
+                    if received == 0 {
+                        ui.label(egui::RichText::new(format!("Sending... {:.1}s", elapsed)).color(egui::Color32::GRAY));
+                    } else if expected == 0 {
+                        ui.label(egui::RichText::new(format!("Downloading... {} in {:.1}s", format_size(received), elapsed)).color(egui::Color32::GRAY));
+                    } else {
+                        ui.add(egui::ProgressBar::new(received as f32 / expected as f32).desired_width(160.0));
+                        ui.label(egui::RichText::new(format!("Downloading... {} of {} in {:.1}s", format_size(received), format_size(expected), elapsed)).color(egui::Color32::GRAY));
+                    }
+                    ui.add_space(20.0);
+                    ui.label(egui::RichText::new("Esc to cancel").size(12.0).color(egui::Color32::DARK_GRAY));
+                });
+            } else if !tab.status.is_empty() {
                 ui.horizontal(|ui| {
                     ui.add_space(20.0);
-                    let status_color = if self.status.starts_with("2") {
+                    let status_color = if tab.status.starts_with('1') || tab.status.starts_with('2') {
                         green
-                    } else if self.status.starts_with("4") {
+                    } else if tab.status.starts_with('4') {
                         egui::Color32::from_rgb(255, 180, 0)
                     } else {
                         egui::Color32::from_rgb(255, 80, 80)
                     };
-                    ui.label(egui::RichText::new(&format!("Status: {}", self.status)).color(status_color));
+                    ui.label(egui::RichText::new(format!("Status: {}", tab.status)).color(status_color));
                     ui.add_space(20.0);
-                    ui.label(egui::RichText::new(&self.time).color(egui::Color32::GRAY));
+                    ui.label(egui::RichText::new(&tab.time).color(egui::Color32::GRAY));
                 });
             }
 
@@ -678,20 +2311,357 @@ impl App {
             ui.horizontal(|ui| {
                 ui.add_space(20.0);
                 ui.label("Response:");
+                ui.add_space(10.0);
+                ui.selectable_value(&mut tab.response_tab, ResponseTab::Body, "Body");
+                let headers_label = format!("Headers ({})", tab.response_headers.len());
+                ui.selectable_value(&mut tab.response_tab, ResponseTab::Headers, headers_label);
+                ui.selectable_value(&mut tab.response_tab, ResponseTab::Cookies, "Cookies");
+                ui.selectable_value(&mut tab.response_tab, ResponseTab::Timing, "Timing");
             });
 
-            egui::ScrollArea::vertical().max_height(ui.available_height() - 20.0).show(ui, |ui| {
+            if tab.response_tab == ResponseTab::Body {
+                if let Some(spool) = &tab.response_spool {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        let shown = format!("Showing the first {} of {}", format_size(spool.shown), format_size(spool.len));
+                        ui.label(egui::RichText::new(shown).size(12.0).color(egui::Color32::from_rgb(255, 180, 0)));
+                        if tab.loading_more.is_some() {
+                            ui.spinner();
+                        } else if spool.shown < spool.len && ui.small_button("Load more").clicked() {
+                            load_more = true;
+                        }
+                    });
+                }
+                // The viewer scrolls by itself, laying out only what's on screen
                 ui.horizontal(|ui| {
                     ui.add_space(20.0);
-                    let mut response_clone = self.response.clone();
-                    let response_field = egui::TextEdit::multiline(&mut response_clone)
-                        .font(egui::TextStyle::Monospace)
-                        .desired_width(ui.available_width() - 40.0);
-                    ui.add(response_field);
-                    ui.add_space(20.0);
+                    ui.vertical(|ui| {
+                        ui.set_max_width(ui.available_width() - 20.0);
+                        ui.set_max_height(ui.available_height() - 20.0);
+                        save_response = match (&mut tab.socket, &mut tab.stream) {
+                            (Some(socket), _) => {
+                                socket.show_log(ui);
+                                false
+                            }
+                            (None, Some(stream)) => stream.show(ui),
+                            (None, None) => tab.response_view.show(ui, &tab.response),
+                        };
+                    });
+                });
+            } else {
+                egui::ScrollArea::vertical().max_height(ui.available_height() - 20.0).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        match tab.response_tab {
+                            ResponseTab::Body => {}
+                            ResponseTab::Headers => tab.show_response_headers(ui),
+                            ResponseTab::Cookies => tab.show_response_cookies(ui),
+                            ResponseTab::Timing => tab.show_response_timing(ui),
+                        }
+                        ui.add_space(20.0);
+                    });
+                });
+            }
+
+            if edited {
+                self.mark_tabs_changed();
+            }
+            if send {
+                self.send_request(ui.ctx());
+            }
+            if load_more {
+                self.tab_mut().load_more(ui.ctx());
+            }
+            if save_response {
+                let tab = self.tab();
+                let url = tab.resolved_request(&self.environments.variables()).url;
+                let name = tab.response_view.file_name(&url, header(&tab.response_headers, "content-disposition"));
+                let path = dirs::download_dir().map(|dir| dir.join(&name)).unwrap_or_else(|| PathBuf::from(&name));
+                self.response_save = Some(ResponseSave {
+                    path: path.display().to_string(),
+                    error: String::new(),
+                });
+            }
+        });
+    }
+
+    fn show_environment_selector(&mut self, ui: &mut egui::Ui) {
+        let selected = self.environments.active.clone().unwrap_or_else(|| "No environment".to_string());
+        let mut changed = false;
+
+        egui::ComboBox::from_id_source("environment")
+            .selected_text(selected)
+            .width(140.0)
+            .show_ui(ui, |ui| {
+                changed |= ui.selectable_value(&mut self.environments.active, None, "No environment").changed();
+                for env in &self.environments.environments {
+                    changed |= ui.selectable_value(&mut self.environments.active, Some(env.name.clone()), &env.name).changed();
+                }
+            });
+
+        if ui.button("Manage").clicked() {
+            self.show_environments = true;
+        }
+
+        if changed {
+            self.environments.save();
+        }
+    }
+
+    fn show_environments_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_environments;
+        let mut changed = false;
+        let mut to_delete = None;
+
+        egui::Window::new("Environments")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.new_environment_name).hint_text("New environment name").desired_width(200.0));
+                    let name = self.new_environment_name.trim().to_string();
+                    let exists = self.environments.environments.iter().any(|env| env.name == name);
+                    if ui.add_enabled(!name.is_empty() && !exists, egui::Button::new("Add")).clicked() {
+                        self.environments.environments.push(Environment {
+                            name,
+                            variables: vec![KeyValue::new()],
+                        });
+                        self.new_environment_name.clear();
+                        changed = true;
+                    }
+                });
+                ui.label(egui::RichText::new("Use {{name}} in the URL, headers or body").size(12.0).color(egui::Color32::GRAY));
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for (i, env) in self.environments.environments.iter_mut().enumerate() {
+                        let is_active = self.environments.active.as_ref() == Some(&env.name);
+                        let title = if is_active { format!("{} (active)", env.name) } else { env.name.clone() };
+
+                        egui::CollapsingHeader::new(title)
+                            .id_source(("environment", i))
+                            .show(ui, |ui| {
+                                changed |= key_value_editor(ui, &format!("environment_grid_{}", i), &mut env.variables, "Variable", "+ Add variable", false);
+                                if ui.small_button("Delete environment").clicked() {
+                                    to_delete = Some(i);
+                                }
+                            });
+                    }
                 });
             });
+
+        if let Some(i) = to_delete {
+            let removed = self.environments.environments.remove(i);
+            if self.environments.active.as_ref() == Some(&removed.name) {
+                self.environments.active = None;
+            }
+            changed = true;
+        }
+
+        if changed {
+            self.environments.save();
+        }
+        self.show_environments = open;
+    }
+
+    fn show_collections_panel(&mut self, ui: &mut egui::Ui, green: egui::Color32) {
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("Collections").size(18.0).color(green));
         });
+        ui.add_space(5.0);
+        ui.separator();
+
+        let mut action = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            action = collections::show_tree(ui, &mut self.collections, &mut self.collections_tree, green);
+        });
+
+        match action {
+            Some(TreeAction::Load(request)) => self.open_saved_request(&request),
+            None => {}
+        }
+    }
+
+    fn show_curl_import(&mut self, ctx: &egui::Context) {
+        let Some(cmd) = &mut self.curl_import else { return };
+        let mut open = true;
+        let mut import = false;
+        let tab = &mut self.tabs[self.active_tab];
+
+        egui::Window::new("Import cURL")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(cmd)
+                        .hint_text("curl -X POST https://api.example.com -H 'Authorization: Bearer ...' -d '{...}'")
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(8)
+                );
+                if !tab.curl_error.is_empty() {
+                    ui.label(egui::RichText::new(&tab.curl_error).color(egui::Color32::from_rgb(255, 80, 80)));
+                }
+                if ui.add_enabled(!cmd.trim().is_empty(), egui::Button::new("Import")).clicked() {
+                    import = true;
+                }
+            });
+
+        if import {
+            let cmd = self.curl_import.clone().unwrap_or_default();
+            match tab.import_curl(&cmd) {
+                Ok(()) => {
+                    self.curl_import = None;
+                    self.mark_tabs_changed();
+                }
+                Err(e) => tab.curl_error = e,
+            }
+        } else if !open {
+            self.curl_import = None;
+            tab.curl_error.clear();
+        }
+    }
+
+    fn show_export(&mut self, ctx: &egui::Context) {
+        let Some(mut language) = self.export_language else { return };
+        let mut code = codegen::render(language, &self.tab().resolved_request(&self.environments.variables()));
+        let mut open = true;
+
+        egui::Window::new("Export as...")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for lang in codegen::Language::ALL {
+                        ui.selectable_value(&mut language, lang, lang.label());
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut code)
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(f32::INFINITY)
+                    );
+                });
+
+                if ui.button("Copy to clipboard").clicked() {
+                    ui.output_mut(|o| o.copied_text = code.clone());
+                }
+            });
+
+        self.export_language = if open { Some(language) } else { None };
+    }
+
+    fn show_response_save(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.response_save else { return };
+        let mut open = true;
+        let mut save = false;
+
+        egui::Window::new("Save response")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("File");
+                let response = ui.add(egui::TextEdit::singleline(&mut dialog.path).desired_width(420.0));
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    save = true;
+                }
+                if !dialog.error.is_empty() {
+                    ui.label(egui::RichText::new(&dialog.error).color(egui::Color32::from_rgb(255, 80, 80)));
+                }
+                if ui.add_enabled(!dialog.path.trim().is_empty(), egui::Button::new("Save")).clicked() {
+                    save = true;
+                }
+            });
+
+        if save && !dialog.path.trim().is_empty() {
+            let tab = &self.tabs[self.active_tab];
+            let path = dialog.path.trim();
+            let written = match (&tab.response_spool, &tab.stream) {
+                (Some(spool), _) => fs::copy(&spool.path, path).map(|_| ()),
+                (None, Some(stream)) => fs::write(path, stream.text()),
+                (None, None) => fs::write(path, tab.response_view.body_bytes(&tab.response)),
+            };
+            match written {
+                Ok(()) => self.response_save = None,
+                Err(e) => dialog.error = format!("Failed to save: {}", e),
+            }
+        } else if !open {
+            self.response_save = None;
+        }
+    }
+
+    fn show_save_dialog(&mut self, ctx: &egui::Context) {
+        let folders = self.collections.folder_paths();
+        let Some(dialog) = &mut self.save_dialog else { return };
+        let mut open = true;
+        let mut save = false;
+
+        egui::Window::new("Save to collection")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Name");
+                ui.add(egui::TextEdit::singleline(&mut dialog.name).desired_width(300.0));
+
+                ui.label("Folder");
+                let selected = dialog.folder
+                    .as_ref()
+                    .and_then(|path| folders.iter().find(|(p, _)| p == path))
+                    .map(|(_, label)| label.clone())
+                    .unwrap_or_else(|| "New collection".to_string());
+                egui::ComboBox::from_id_source("save_folder")
+                    .selected_text(selected)
+                    .width(300.0)
+                    .show_ui(ui, |ui| {
+                        for (path, label) in &folders {
+                            ui.selectable_value(&mut dialog.folder, Some(path.clone()), label);
+                        }
+                        ui.selectable_value(&mut dialog.folder, None, "New collection");
+                    });
+
+                if dialog.folder.is_none() {
+                    ui.add(egui::TextEdit::singleline(&mut dialog.new_collection).hint_text("Collection name").desired_width(300.0));
+                }
+
+                let valid = !dialog.name.trim().is_empty()
+                    && (dialog.folder.is_some() || !dialog.new_collection.trim().is_empty());
+                if ui.add_enabled(valid, egui::Button::new("Save")).clicked() {
+                    save = true;
+                }
+            });
+
+        if save {
+            let dialog = self.save_dialog.take().unwrap();
+            let request = self.tab().current_as_saved_request(dialog.name.trim().to_string());
+            self.tab_mut().baseline = request.clone();
+            let folder = match dialog.folder {
+                Some(path) => self.collections.folder_mut(&path),
+                None => {
+                    self.collections.collections.push(collections::Folder {
+                        name: dialog.new_collection.trim().to_string(),
+                        items: Vec::new(),
+                    });
+                    self.collections.collections.last_mut()
+                }
+            };
+            if let Some(folder) = folder {
+                folder.items.push(Node::Request(request));
+            }
+            self.collections.save();
+            self.show_collections = true;
+            self.save_tabs();
+        } else if !open {
+            self.save_dialog = None;
+        }
     }
 
     fn show_history_panel(&mut self, ui: &mut egui::Ui, green: egui::Color32) {
@@ -712,7 +2682,7 @@ impl App {
             let mut to_load = None;
 
             for item in &self.history {
-                let is_selected = self.selected_history_id.as_ref() == item.id.as_ref();
+                let is_selected = self.tab().selected_history_id.as_ref() == item.id.as_ref();
 
                 let frame = egui::Frame::none()
                     .fill(if is_selected { egui::Color32::from_rgb(30, 30, 30) } else { egui::Color32::from_rgb(18, 18, 18) })
@@ -720,19 +2690,12 @@ impl App {
 
                 frame.show(ui, |ui| {
                     ui.horizontal(|ui| {
-                        let method_color = match item.method.as_str() {
-                            "GET" => egui::Color32::from_rgb(100, 180, 255),
-                            "POST" => green,
-                            "PUT" => egui::Color32::from_rgb(255, 180, 100),
-                            "DELETE" => egui::Color32::from_rgb(255, 100, 100),
-                            "PATCH" => egui::Color32::from_rgb(200, 150, 255),
-                            _ => egui::Color32::GRAY,
-                        };
+                        let method_color = method_color(&item.method, green);
 
                         ui.label(egui::RichText::new(&item.method).color(method_color).strong());
 
-                        let url_display = if item.url.len() > 35 {
-                            format!("{}...", &item.url[..35])
+                        let url_display = if item.url.chars().count() > 35 {
+                            format!("{}...", item.url.chars().take(35).collect::<String>())
                         } else {
                             item.url.clone()
                         };
@@ -743,9 +2706,9 @@ impl App {
                     });
 
                     ui.horizontal(|ui| {
-                        let status_color = if item.status.starts_with("2") {
+                        let status_color = if item.status.starts_with('1') || item.status.starts_with('2') {
                             green
-                        } else if item.status.starts_with("4") {
+                        } else if item.status.starts_with('4') {
                             egui::Color32::from_rgb(255, 180, 0)
                         } else {
                             egui::Color32::from_rgb(255, 80, 80)
@@ -781,59 +2744,238 @@ impl App {
         ui.add_space(5.0);
         ui.horizontal(|ui| {
             ui.add_space(10.0);
-            ui.label(egui::RichText::new("Cmd+H: Toggle • Cmd+L: URL • Cmd+Enter: Send").size(10.0).color(egui::Color32::DARK_GRAY));
+            ui.label(egui::RichText::new("Cmd+H: Toggle • Cmd+L: URL • Cmd+Enter: Send • Esc: Cancel • Cmd+T/W: Tabs • Cmd+F: Find").size(10.0).color(egui::Color32::DARK_GRAY));
+        });
+    }
+}
+
+impl RequestTab {
+    /// Returns true if a param changed.
+    fn show_params_editor(&mut self, ui: &mut egui::Ui) -> bool {
+        let active = self.params.iter().filter(|p| p.enabled && !p.key.is_empty()).count();
+        let title = if active > 0 { format!("Params ({})", active) } else { "Params".to_string() };
+
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.add_space(20.0);
+            egui::CollapsingHeader::new(title)
+                .id_source("params_editor")
+                .show(ui, |ui| {
+                    if key_value_editor(ui, "params_grid", &mut self.params, "Key", "+ Add param", true) {
+                        self.url = query::with_params(&self.url, &self.params);
+                        changed = true;
+                    }
+                });
+        });
+        changed
+    }
+
+    /// Rebuilds the params table after the URL was typed in, keeping disabled
+    /// rows since they don't appear in the URL.
+    fn sync_params_from_url(&mut self) {
+        let disabled: Vec<KeyValue> = self.params.iter().filter(|p| !p.enabled).cloned().collect();
+        self.params = query::parse_params(&self.url);
+        self.params.extend(disabled);
+    }
+
+    /// Returns true if a header changed.
+    fn show_headers_editor(&mut self, ui: &mut egui::Ui) -> bool {
+        let active = self.headers.iter().filter(|h| h.enabled && !h.key.is_empty()).count();
+        let title = if active > 0 { format!("Headers ({})", active) } else { "Headers".to_string() };
+
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.add_space(20.0);
+            egui::CollapsingHeader::new(title)
+                .id_source("headers_editor")
+                .show(ui, |ui| {
+                    changed = key_value_editor(ui, "headers_grid", &mut self.headers, "Header", "+ Add header", false);
+                });
+        });
+        changed
+    }
+
+    fn show_response_headers(&self, ui: &mut egui::Ui) {
+        if self.response_headers.is_empty() {
+            ui.label(egui::RichText::new("No response headers").color(egui::Color32::GRAY));
+            return;
+        }
+
+        egui::Grid::new("response_headers_grid").num_columns(2).spacing([20.0, 4.0]).striped(true).show(ui, |ui| {
+            for (name, value) in &self.response_headers {
+                ui.label(egui::RichText::new(name).monospace().color(egui::Color32::GRAY));
+                ui.label(egui::RichText::new(value).monospace());
+                ui.end_row();
+            }
+        });
+    }
+
+    fn show_response_cookies(&self, ui: &mut egui::Ui) {
+        let cookies: Vec<Cookie> = self.response_headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+            .filter_map(|(_, value)| Cookie::parse(value))
+            .collect();
+
+        if cookies.is_empty() {
+            ui.label(egui::RichText::new("No cookies").color(egui::Color32::GRAY));
+            return;
+        }
+
+        egui::Grid::new("response_cookies_grid").num_columns(3).spacing([20.0, 4.0]).striped(true).show(ui, |ui| {
+            ui.label(egui::RichText::new("Name").strong());
+            ui.label(egui::RichText::new("Value").strong());
+            ui.label(egui::RichText::new("Attributes").strong());
+            ui.end_row();
+
+            for cookie in &cookies {
+                let attributes = cookie.attributes
+                    .iter()
+                    .map(|(k, v)| if v.is_empty() { k.clone() } else { format!("{}={}", k, v) })
+                    .collect::<Vec<_>>()
+                    .join("; ");
+
+                ui.label(egui::RichText::new(&cookie.name).monospace());
+                ui.label(egui::RichText::new(&cookie.value).monospace());
+                ui.label(egui::RichText::new(attributes).monospace().color(egui::Color32::GRAY));
+                ui.end_row();
+            }
+        });
+    }
+
+    fn show_response_timing(&self, ui: &mut egui::Ui) {
+        let meta = &self.response_meta;
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+        egui::Grid::new("response_timing_grid").num_columns(2).spacing([20.0, 4.0]).striped(true).show(ui, |ui| {
+            let rows = [
+                ("Status", self.status.clone()),
+                ("Total time", self.time.clone()),
+                ("HTTP version", or_dash(Some(meta.http_version.clone()).filter(|v| !v.is_empty()))),
+                ("Remote address", or_dash(meta.remote_addr.clone())),
+                ("Content-Length", or_dash(meta.content_length.map(|len| format!("{} bytes", len)))),
+                ("Body size", format!("{} bytes", meta.body_size)),
+            ];
+
+            for (label, value) in rows {
+                ui.label(egui::RichText::new(label).color(egui::Color32::GRAY));
+                ui.label(egui::RichText::new(value).monospace());
+                ui.end_row();
+            }
         });
     }
 }
 
+/// Editable table of key/value rows with enable checkboxes. Returns true if
+/// anything was changed this frame.
+fn key_value_editor(
+    ui: &mut egui::Ui,
+    id: &str,
+    rows: &mut Vec<KeyValue>,
+    key_hint: &str,
+    add_label: &str,
+    reorderable: bool,
+) -> bool {
+    let mut changed = false;
+    let mut to_remove = None;
+    let mut to_swap = None;
+    let value_width = (ui.available_width() - 300.0).max(150.0);
+    let row_count = rows.len();
+
+    egui::Grid::new(id).num_columns(4).spacing([8.0, 4.0]).show(ui, |ui| {
+        for (i, row) in rows.iter_mut().enumerate() {
+            changed |= ui.checkbox(&mut row.enabled, "").changed();
+            changed |= ui.add(egui::TextEdit::singleline(&mut row.key).hint_text(key_hint).desired_width(200.0)).changed();
+            changed |= ui.add(egui::TextEdit::singleline(&mut row.value).hint_text("Value").desired_width(value_width)).changed();
+            ui.horizontal(|ui| {
+                if reorderable {
+                    if ui.add_enabled(i > 0, egui::Button::new("▲").small()).clicked() {
+                        to_swap = Some((i - 1, i));
+                    }
+                    if ui.add_enabled(i + 1 < row_count, egui::Button::new("▼").small()).clicked() {
+                        to_swap = Some((i, i + 1));
+                    }
+                }
+                if ui.small_button("×").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+            ui.end_row();
+        }
+    });
+
+    if let Some((a, b)) = to_swap {
+        rows.swap(a, b);
+        changed = true;
+    }
+
+    if let Some(i) = to_remove {
+        rows.remove(i);
+        changed = true;
+    }
+
+    if ui.small_button(add_label).clicked() {
+        rows.push(KeyValue::new());
+        changed = true;
+    }
+
+    changed
+}
+
 fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
         s.to_string()
     } else {
-        let truncated = &s[..max_len];
-        format!("{}... [truncated {} bytes]", truncated, s.len() - max_len)
+        // Cut at a character boundary, not in the middle of one
+        let end = (0..=max_len).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+        format!("{}... [truncated {} bytes]", &s[..end], s.len() - end)
     }
 }
 
-fn get_config_path() -> PathBuf {
+fn config_file(name: &str) -> PathBuf {
     let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
     path.push("restty");
     fs::create_dir_all(&path).ok();
-    path.push("credentials.json");
+    path.push(name);
     path
 }
 
-fn save_credentials(token: &str, user_id: &str, email: &str) {
-    let creds = serde_json::json!({
-        "token": token,
-        "user_id": user_id,
-        "email": email,
-    });
-
-    if let Ok(json) = serde_json::to_string_pretty(&creds) {
-        fs::write(get_config_path(), json).ok();
+fn save_credentials(credentials: &Credentials) {
+    let Ok(json) = serde_json::to_string(credentials) else { return };
+    if let Err(e) = secrets::store().set(&json) {
+        eprintln!("Failed to save credentials: {}", e);
     }
 }
 
-fn load_credentials() -> (Option<String>, Option<String>, Option<String>) {
-    if let Ok(data) = fs::read_to_string(get_config_path()) {
-        if let Ok(creds) = serde_json::from_str::<Value>(&data) {
-            let token = creds["token"].as_str().map(String::from);
-            let user_id = creds["user_id"].as_str().map(String::from);
-            let email = creds["email"].as_str().map(String::from);
-            return (token, user_id, email);
+fn load_credentials() -> Option<Credentials> {
+    let store = secrets::store();
+    secrets::migrate_plaintext(store.as_ref());
+    match store.get() {
+        Ok(data) => serde_json::from_str(&data?).ok(),
+        Err(e) => {
+            eprintln!("Failed to load credentials: {}", e);
+            None
         }
     }
-    (None, None, None)
 }
 
 fn clear_credentials() {
-    fs::remove_file(get_config_path()).ok();
+    if let Err(e) = secrets::store().delete() {
+        eprintln!("Failed to clear credentials: {}", e);
+    }
 }
 
 mod dirs {
     use std::path::PathBuf;
 
+    /// Where saved responses go by default: Downloads if there is one,
+    /// else the home folder.
+    pub fn download_dir() -> Option<PathBuf> {
+        let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok().map(PathBuf::from)?;
+        let downloads = home.join("Downloads");
+        Some(if downloads.is_dir() { downloads } else { home })
+    }
+
     pub fn config_dir() -> Option<PathBuf> {
         if cfg!(target_os = "macos") {
             std::env::var("HOME").ok().map(|home| {
@@ -0,0 +1,611 @@
+use crate::query;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use eframe::egui;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// How long to wait for the browser to come back to the loopback listener.
+const CALLBACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grant {
+    #[default]
+    ClientCredentials,
+    Password,
+    RefreshToken,
+    AuthorizationCode,
+}
+
+impl Grant {
+    pub const ALL: [Grant; 4] = [Grant::ClientCredentials, Grant::Password, Grant::RefreshToken, Grant::AuthorizationCode];
+
+    pub fn label(&self) -> &str {
+        match self {
+            Grant::ClientCredentials => "Client credentials",
+            Grant::Password => "Password",
+            Grant::RefreshToken => "Refresh token",
+            Grant::AuthorizationCode => "Authorization code (PKCE)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OAuth2Config {
+    pub grant: Grant,
+    pub token_url: String,
+    pub auth_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: String,
+    pub username: String,
+    pub password: String,
+    pub refresh_token: String,
+}
+
+impl OAuth2Config {
+    /// Every text field, for `{{var}}` substitution.
+    pub fn fields_mut(&mut self) -> Vec<&mut String> {
+        vec![
+            &mut self.token_url,
+            &mut self.auth_url,
+            &mut self.client_id,
+            &mut self.client_secret,
+            &mut self.scope,
+            &mut self.username,
+            &mut self.password,
+            &mut self.refresh_token,
+        ]
+    }
+
+    /// Tokens are shared between requests with the same provider, client,
+    /// scope and user. The secrets are part of the key as a hash, so that
+    /// correcting a wrong one doesn't keep using what was fetched with it.
+    fn cache_key(&self) -> String {
+        let secrets = Sha256::digest(format!("{}\0{}\0{}", self.client_secret, self.password, self.refresh_token).as_bytes());
+        format!(
+            "{:?}|{}|{}|{}|{}|{}|{}",
+            self.grant,
+            self.token_url,
+            self.auth_url,
+            self.client_id,
+            self.scope,
+            self.username,
+            base64::engine::general_purpose::STANDARD_NO_PAD.encode(secrets)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Token {
+    /// Treat tokens as expired a little early so they don't run out mid-request.
+    fn is_fresh(&self) -> bool {
+        self.expires_at.is_none_or(|at| at > Utc::now() + Duration::seconds(30))
+    }
+}
+
+/// Access tokens fetched so far, shared with the request workers.
+#[derive(Clone, Default)]
+pub struct TokenCache(Arc<Mutex<HashMap<String, Token>>>);
+
+impl TokenCache {
+    pub fn get(&self, config: &OAuth2Config) -> Option<Token> {
+        self.0.lock().ok()?.get(&config.cache_key()).cloned()
+    }
+
+    fn insert(&self, config: &OAuth2Config, token: Token) {
+        if let Ok(mut tokens) = self.0.lock() {
+            tokens.insert(config.cache_key(), token);
+        }
+    }
+
+    pub fn remove(&self, config: &OAuth2Config) {
+        if let Ok(mut tokens) = self.0.lock() {
+            tokens.remove(&config.cache_key());
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Returns a usable access token: the cached one if it is still fresh, a
+/// refreshed one if the cached one has a refresh token, otherwise a new one
+/// from the configured grant. Runs on the request worker.
+pub fn access_token(client: &Client, config: &OAuth2Config, cache: &TokenCache, cancelled: &AtomicBool) -> Result<String, String> {
+    if config.token_url.trim().is_empty() {
+        return Err("OAuth 2.0: token URL is empty".to_string());
+    }
+
+    let cached = cache.get(config);
+    if let Some(token) = cached.as_ref().filter(|token| token.is_fresh()) {
+        return Ok(token.access_token.clone());
+    }
+
+    let refreshed = cached
+        .and_then(|token| token.refresh_token)
+        .and_then(|refresh_token| match request_token(client, config, &[("grant_type", "refresh_token"), ("refresh_token", &refresh_token)]) {
+            Ok(token) => Some(token),
+            Err(e) => {
+                eprintln!("Token refresh failed, starting over: {}", e);
+                None
+            }
+        });
+
+    let token = match refreshed {
+        Some(token) => token,
+        None => fetch_token(client, config, cancelled)?,
+    };
+    let access_token = token.access_token.clone();
+    cache.insert(config, token);
+    Ok(access_token)
+}
+
+fn fetch_token(client: &Client, config: &OAuth2Config, cancelled: &AtomicBool) -> Result<Token, String> {
+    match config.grant {
+        Grant::ClientCredentials => request_token(client, config, &[("grant_type", "client_credentials")]),
+        Grant::Password => request_token(
+            client,
+            config,
+            &[("grant_type", "password"), ("username", &config.username), ("password", &config.password)],
+        ),
+        Grant::RefreshToken => {
+            request_token(client, config, &[("grant_type", "refresh_token"), ("refresh_token", config.refresh_token.trim())])
+        }
+        Grant::AuthorizationCode => authorization_code(client, config, cancelled, open_browser),
+    }
+}
+
+/// POSTs to the token endpoint, adding the client id, secret and scope.
+fn request_token(client: &Client, config: &OAuth2Config, params: &[(&str, &str)]) -> Result<Token, String> {
+    let mut form: Vec<(&str, &str)> = params.to_vec();
+    form.push(("client_id", config.client_id.trim()));
+    if !config.client_secret.is_empty() {
+        form.push(("client_secret", &config.client_secret));
+    }
+    if !config.scope.trim().is_empty() {
+        form.push(("scope", config.scope.trim()));
+    }
+
+    let resp = client
+        .post(config.token_url.trim())
+        .header("Accept", "application/json")
+        .form(&form)
+        .send()
+        .map_err(|e| format!("OAuth 2.0 token request failed: {}", e))?;
+    let status = resp.status();
+    let text = resp.text().map_err(|e| e.to_string())?;
+
+    if !status.is_success() {
+        return Err(match serde_json::from_str::<TokenError>(&text) {
+            Ok(err) => format!("OAuth 2.0 token request failed: {} {}", err.error, err.error_description.unwrap_or_default()),
+            Err(_) => format!("OAuth 2.0 token request failed: {}\n\n{}", status, text),
+        });
+    }
+
+    let token: TokenResponse = serde_json::from_str(&text).map_err(|e| format!("Invalid OAuth 2.0 token response: {}", e))?;
+    Ok(Token {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token.expires_in.map(|secs| Utc::now() + Duration::seconds(secs)),
+    })
+}
+
+fn random_urlsafe(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buf);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// Authorization code grant with PKCE (RFC 7636). Opens the browser on the
+/// authorization URL and waits for the redirect on a loopback port
+/// (RFC 8252), then exchanges the code for a token. `open` shows the user
+/// the authorization URL.
+fn authorization_code(client: &Client, config: &OAuth2Config, cancelled: &AtomicBool, open: impl FnOnce(&str) -> Result<(), String>) -> Result<Token, String> {
+    if config.auth_url.trim().is_empty() {
+        return Err("OAuth 2.0: authorization URL is empty".to_string());
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| format!("Failed to start callback listener: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+    let verifier = random_urlsafe(32);
+    let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let state = random_urlsafe(16);
+
+    let mut url = config.auth_url.trim().to_string();
+    for (key, value) in [
+        ("response_type", "code"),
+        ("client_id", config.client_id.trim()),
+        ("redirect_uri", &redirect_uri),
+        ("scope", config.scope.trim()),
+        ("state", &state),
+        ("code_challenge", &challenge),
+        ("code_challenge_method", "S256"),
+    ] {
+        if !value.is_empty() {
+            url = query::append_param(&url, key, value);
+        }
+    }
+    open(&url)?;
+
+    let params = wait_for_callback(&listener, cancelled)?;
+    let get = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+    if let Some(error) = get("error") {
+        return Err(format!("OAuth 2.0 authorization failed: {} {}", error, get("error_description").unwrap_or_default()));
+    }
+    if get("state") != Some(state.as_str()) {
+        return Err("OAuth 2.0 callback state didn't match, ignoring it".to_string());
+    }
+    let code = get("code").ok_or_else(|| "OAuth 2.0 callback has no code".to_string())?;
+
+    request_token(
+        client,
+        config,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &redirect_uri),
+            ("code_verifier", &verifier),
+        ],
+    )
+}
+
+/// Accepts connections until one hits `/callback`, answers it with a page
+/// telling the user to go back to restty, and returns its query params.
+fn wait_for_callback(listener: &TcpListener, cancelled: &AtomicBool) -> Result<Vec<(String, String)>, String> {
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let start = Instant::now();
+
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        if start.elapsed() > CALLBACK_TIMEOUT {
+            return Err("Timed out waiting for the OAuth 2.0 redirect".to_string());
+        }
+
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        stream.set_nonblocking(false).ok();
+
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line).ok();
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+        if !path.starts_with("/callback") {
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").ok();
+            continue;
+        }
+
+        let body = "<html><body style=\"font-family: sans-serif\"><h3>restty</h3><p>Authorization finished. You can close this window.</p></body></html>";
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .ok();
+
+        return Ok(query::parse_params(path).into_iter().map(|param| (param.key, param.value)).collect());
+    }
+}
+
+fn open_browser(url: &str) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let result = std::process::Command::new("open").arg(url).spawn();
+    #[cfg(target_os = "windows")]
+    let result = std::process::Command::new("rundll32").args(["url.dll,FileProtocolHandler", url]).spawn();
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let result = std::process::Command::new("xdg-open").arg(url).spawn();
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Couldn't open a browser ({}). Open this URL to continue:\n\n{}", e, url))
+}
+
+/// The OAuth 2.0 part of the Auth editor. Returns true if anything changed.
+pub fn show_editor(ui: &mut egui::Ui, config: &mut OAuth2Config) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        ui.add_sized([90.0, 20.0], egui::Label::new("Grant"));
+        egui::ComboBox::from_id_source("oauth_grant")
+            .selected_text(config.grant.label().to_string())
+            .width(200.0)
+            .show_ui(ui, |ui| {
+                for grant in Grant::ALL {
+                    changed |= ui.selectable_value(&mut config.grant, grant, grant.label()).changed();
+                }
+            });
+    });
+
+    let field = |ui: &mut egui::Ui, label: &str, value: &mut String, password: bool| {
+        ui.horizontal(|ui| {
+            ui.add_sized([90.0, 20.0], egui::Label::new(label));
+            ui.add(egui::TextEdit::singleline(value).password(password).desired_width(300.0)).changed()
+        })
+        .inner
+    };
+
+    if config.grant == Grant::AuthorizationCode {
+        changed |= field(ui, "Auth URL", &mut config.auth_url, false);
+    }
+    changed |= field(ui, "Token URL", &mut config.token_url, false);
+    changed |= field(ui, "Client ID", &mut config.client_id, false);
+    changed |= field(ui, "Client secret", &mut config.client_secret, true);
+    changed |= field(ui, "Scope", &mut config.scope, false);
+    match config.grant {
+        Grant::Password => {
+            changed |= field(ui, "Username", &mut config.username, false);
+            changed |= field(ui, "Password", &mut config.password, true);
+        }
+        Grant::RefreshToken => {
+            changed |= field(ui, "Refresh token", &mut config.refresh_token, true);
+        }
+        Grant::ClientCredentials | Grant::AuthorizationCode => {}
+    }
+
+    changed
+}
+
+/// Shows whether a token is cached for `config`, with a button to drop it.
+pub fn show_token_status(ui: &mut egui::Ui, cache: &TokenCache, config: &OAuth2Config) {
+    ui.horizontal(|ui| {
+        ui.add_sized([90.0, 20.0], egui::Label::new("Token"));
+        match cache.get(config) {
+            Some(token) => {
+                let expiry = match token.expires_at {
+                    Some(at) if at > Utc::now() => format!("expires in {} min", (at - Utc::now()).num_minutes()),
+                    Some(_) => "expired".to_string(),
+                    None => "no expiry".to_string(),
+                };
+                ui.label(egui::RichText::new(format!("Cached, {}", expiry)).size(12.0).color(egui::Color32::GRAY));
+                if ui.small_button("Clear").clicked() {
+                    cache.remove(config);
+                }
+            }
+            None => {
+                ui.label(egui::RichText::new("Fetched when the request is sent").size(12.0).color(egui::Color32::GRAY));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpStream;
+    use std::sync::mpsc;
+    use std::thread;
+
+    type Form = Vec<(String, String)>;
+
+    /// A token endpoint on a loopback port. Each request is answered with
+    /// the next of `responses` (status, JSON body), and its form is passed
+    /// on so the test can check what was sent.
+    fn token_endpoint(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Form>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let form = read_form(&mut stream);
+                tx.send(form).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn read_form(stream: &mut TcpStream) -> Form {
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        query::parse_params(&format!("?{}", String::from_utf8(body).unwrap()))
+            .into_iter()
+            .map(|param| (param.key, param.value))
+            .collect()
+    }
+
+    fn field<'a>(form: &'a Form, name: &str) -> Option<&'a str> {
+        form.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn config(grant: Grant, token_url: &str) -> OAuth2Config {
+        OAuth2Config {
+            grant,
+            token_url: token_url.to_string(),
+            client_id: "restty".to_string(),
+            client_secret: "s3cret".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn token(cache: &TokenCache, config: &OAuth2Config) -> Result<String, String> {
+        access_token(&Client::new(), config, cache, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn client_credentials_are_fetched_once_and_cached() {
+        let (url, forms) = token_endpoint(vec![(200, r#"{"access_token":"abc","expires_in":3600}"#)]);
+        let cache = TokenCache::default();
+        let config = config(Grant::ClientCredentials, &url);
+
+        assert_eq!(token(&cache, &config).unwrap(), "abc");
+        let form = forms.recv().unwrap();
+        assert_eq!(field(&form, "grant_type"), Some("client_credentials"));
+        assert_eq!(field(&form, "client_id"), Some("restty"));
+        assert_eq!(field(&form, "client_secret"), Some("s3cret"));
+
+        // Served from the cache; the endpoint only answers once
+        assert_eq!(token(&cache, &config).unwrap(), "abc");
+    }
+
+    #[test]
+    fn password_grant_sends_the_user_credentials() {
+        let (url, forms) = token_endpoint(vec![(200, r#"{"access_token":"pw"}"#)]);
+        let config = OAuth2Config {
+            username: "ada".to_string(),
+            password: "lovelace".to_string(),
+            scope: "read write".to_string(),
+            ..config(Grant::Password, &url)
+        };
+
+        assert_eq!(token(&TokenCache::default(), &config).unwrap(), "pw");
+        let form = forms.recv().unwrap();
+        assert_eq!(field(&form, "grant_type"), Some("password"));
+        assert_eq!(field(&form, "username"), Some("ada"));
+        assert_eq!(field(&form, "password"), Some("lovelace"));
+        assert_eq!(field(&form, "scope"), Some("read write"));
+    }
+
+    #[test]
+    fn refresh_token_grant_uses_the_configured_token() {
+        let (url, forms) = token_endpoint(vec![(200, r#"{"access_token":"fresh"}"#)]);
+        let config = OAuth2Config {
+            refresh_token: " r1 ".to_string(),
+            ..config(Grant::RefreshToken, &url)
+        };
+
+        assert_eq!(token(&TokenCache::default(), &config).unwrap(), "fresh");
+        let form = forms.recv().unwrap();
+        assert_eq!(field(&form, "grant_type"), Some("refresh_token"));
+        assert_eq!(field(&form, "refresh_token"), Some("r1"));
+    }
+
+    #[test]
+    fn expired_token_is_refreshed() {
+        let (url, forms) = token_endpoint(vec![
+            (200, r#"{"access_token":"old","refresh_token":"r2","expires_in":0}"#),
+            (200, r#"{"access_token":"new","expires_in":3600}"#),
+        ]);
+        let cache = TokenCache::default();
+        let config = config(Grant::ClientCredentials, &url);
+
+        assert_eq!(token(&cache, &config).unwrap(), "old");
+        assert_eq!(token(&cache, &config).unwrap(), "new");
+        forms.recv().unwrap();
+        let form = forms.recv().unwrap();
+        assert_eq!(field(&form, "grant_type"), Some("refresh_token"));
+        assert_eq!(field(&form, "refresh_token"), Some("r2"));
+    }
+
+    #[test]
+    fn corrected_secret_is_not_served_from_the_cache() {
+        let (url, forms) = token_endpoint(vec![
+            (200, r#"{"access_token":"one","expires_in":3600}"#),
+            (200, r#"{"access_token":"two","expires_in":3600}"#),
+        ]);
+        let cache = TokenCache::default();
+        let mut config = config(Grant::ClientCredentials, &url);
+
+        assert_eq!(token(&cache, &config).unwrap(), "one");
+        config.client_secret = "corrected".to_string();
+        assert_eq!(token(&cache, &config).unwrap(), "two");
+        forms.recv().unwrap();
+        assert_eq!(field(&forms.recv().unwrap(), "client_secret"), Some("corrected"));
+    }
+
+    #[test]
+    fn token_errors_are_reported() {
+        let (url, _forms) = token_endpoint(vec![(401, r#"{"error":"invalid_client","error_description":"bad secret"}"#)]);
+        let cache = TokenCache::default();
+        let config = config(Grant::ClientCredentials, &url);
+
+        let error = token(&cache, &config).unwrap_err();
+        assert!(error.contains("invalid_client bad secret"), "{}", error);
+        assert!(cache.get(&config).is_none());
+    }
+
+    #[test]
+    fn authorization_code_with_pkce() {
+        let (url, forms) = token_endpoint(vec![(200, r#"{"access_token":"pkce"}"#)]);
+        let config = OAuth2Config {
+            auth_url: "https://auth.example/authorize".to_string(),
+            scope: "openid".to_string(),
+            ..config(Grant::AuthorizationCode, &url)
+        };
+
+        // Stands in for the browser: checks the authorization URL and
+        // follows the redirect back with a code
+        let challenge = Arc::new(Mutex::new(String::new()));
+        let seen = challenge.clone();
+        let browser = move |auth_url: &str| {
+            let params: Vec<(String, String)> = query::parse_params(auth_url).into_iter().map(|param| (param.key, param.value)).collect();
+            assert_eq!(field(&params, "response_type"), Some("code"));
+            assert_eq!(field(&params, "code_challenge_method"), Some("S256"));
+            *seen.lock().unwrap() = field(&params, "code_challenge").unwrap().to_string();
+            let redirect = format!("{}?code=xyz&state={}", field(&params, "redirect_uri").unwrap(), field(&params, "state").unwrap());
+            thread::spawn(move || Client::new().get(redirect).send().unwrap());
+            Ok(())
+        };
+
+        let token = authorization_code(&Client::new(), &config, &AtomicBool::new(false), browser).unwrap();
+        assert_eq!(token.access_token, "pkce");
+
+        let form = forms.recv().unwrap();
+        assert_eq!(field(&form, "grant_type"), Some("authorization_code"));
+        assert_eq!(field(&form, "code"), Some("xyz"));
+        let verifier = field(&form, "code_verifier").unwrap();
+        let expected = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        assert_eq!(*challenge.lock().unwrap(), expected);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::KeyValue;
+
+/// Splits a URL into the part before the query, the raw query and the
+/// fragment (including the leading `#`).
+fn split_url(url: &str) -> (&str, Option<&str>, &str) {
+    let (rest, fragment) = match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    match rest.split_once('?') {
+        Some((base, query)) => (base, Some(query), fragment),
+        None => (rest, None, fragment),
+    }
+}
+
+/// The non-empty `key=value` pairs of a raw query, still encoded.
+fn raw_pairs(query: Option<&str>) -> impl Iterator<Item = &str> {
+    query.unwrap_or("").split('&').filter(|pair| !pair.is_empty())
+}
+
+fn decode_pair(pair: &str) -> (String, String) {
+    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+    (decode(key), decode(value))
+}
+
+/// Parses the query string of `url` into percent-decoded rows.
+pub fn parse_params(url: &str) -> Vec<KeyValue> {
+    let (_, query, _) = split_url(url);
+    raw_pairs(query)
+        .map(|pair| {
+            let (key, value) = decode_pair(pair);
+            KeyValue { enabled: true, key, value }
+        })
+        .collect()
+}
+
+/// Rewrites the query string of `url` from the enabled rows, keeping the
+/// base and fragment untouched. The URL is left as typed unless a param
+/// actually changed, and pairs that are still there keep their encoding.
+pub fn with_params(url: &str, params: &[KeyValue]) -> String {
+    let rows: Vec<&KeyValue> = params
+        .iter()
+        .filter(|p| p.enabled && !(p.key.is_empty() && p.value.is_empty()))
+        .collect();
+    let current = parse_params(url);
+    if current.len() == rows.len() && current.iter().zip(&rows).all(|(a, b)| a.key == b.key && a.value == b.value) {
+        return url.to_string();
+    }
+
+    let (base, query, fragment) = split_url(url);
+    let mut unused: Vec<Option<&str>> = raw_pairs(query).map(Some).collect();
+    let query = rows
+        .iter()
+        .map(|p| {
+            let typed = unused
+                .iter_mut()
+                .find(|raw| {
+                    raw.is_some_and(|raw| {
+                        let (key, value) = decode_pair(raw);
+                        key == p.key && value == p.value
+                    })
+                })
+                .and_then(Option::take);
+            match typed {
+                Some(raw) => raw.to_string(),
+                None if p.value.is_empty() => encode(&p.key),
+                None => format!("{}={}", encode(&p.key), encode(&p.value)),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    if query.is_empty() {
+        format!("{}{}", base, fragment)
+    } else {
+        format!("{}?{}{}", base, query, fragment)
+    }
+}
+
+/// Adds one `key=value` pair to the query of `url`.
+pub fn append_param(url: &str, key: &str, value: &str) -> String {
+    let (base, query, fragment) = split_url(url);
+    let pair = format!("{}={}", encode(key), encode(value));
+    match query {
+        Some(query) if !query.is_empty() => format!("{}?{}&{}{}", base, query, pair, fragment),
+        _ => format!("{}?{}{}", base, pair, fragment),
+    }
+}
+
+/// Percent-encodes only what would change how the query is split, so that
+/// the URL bar stays readable and `{{var}}` placeholders survive.
+pub fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'&' | b'=' | b'#' | b'+' | b'%' | b' ' | b'"' | b'<' | b'>' | b'`' => {
+                out.push_str(&format!("%{:02X}", b));
+            }
+            0x00..=0x1F | 0x7F..=0xFF => out.push_str(&format!("%{:02X}", b)),
+            _ => out.push(b as char),
+        }
+    }
+    out
+}
+
+pub fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                (Some(hi), Some(lo)) => {
+                    out.push(hi << 4 | lo);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
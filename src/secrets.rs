@@ -0,0 +1,364 @@
+use crate::config_file;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, OnceLock};
+
+const SERVICE: &str = "restty";
+const ACCOUNT: &str = "credentials";
+/// Marks a credential that was moved into a `Vault`.
+const REFERENCE_PREFIX: &str = "secret:";
+
+/// Somewhere to keep a single secret string (the serialized credentials).
+pub trait SecretStore {
+    fn get(&self) -> Result<Option<String>, String>;
+    fn set(&self, secret: &str) -> Result<(), String>;
+    fn delete(&self) -> Result<(), String>;
+}
+
+/// macOS Keychain, Windows Credential Manager or the Secret Service on Linux.
+pub struct KeyringStore {
+    entry: keyring::Entry,
+}
+
+impl KeyringStore {
+    fn new(account: &str) -> Option<Self> {
+        keyring::Entry::new(SERVICE, account).ok().map(|entry| Self { entry })
+    }
+
+    /// Whether there is a usable keyring on this machine.
+    fn probe() -> bool {
+        let Some(store) = Self::new(ACCOUNT) else { return false };
+        match store.entry.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => true,
+            Err(e) => {
+                eprintln!("Keyring unavailable, using encrypted file: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn get(&self) -> Result<Option<String>, String> {
+        match self.entry.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn set(&self, secret: &str) -> Result<(), String> {
+        self.entry.set_password(secret).map_err(|e| e.to_string())
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// AES-256-GCM encrypted file, with a random key kept in a separate file.
+/// Both are only readable by the current user. This keeps the token out of
+/// plain sight and out of backups of a single file; it doesn't protect
+/// against someone who can already read the user's files.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(account: &str) -> Self {
+        Self {
+            path: config_file(&format!("{}.enc", account)),
+            key_path: config_file("secret.key"),
+        }
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, String> {
+        let key = match fs::read(&self.key_path) {
+            Ok(key) if key.len() == 32 => key,
+            _ => {
+                let key = Aes256Gcm::generate_key(OsRng);
+                write_private(&self.key_path, &key)?;
+                key.to_vec()
+            }
+        };
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn get(&self) -> Result<Option<String>, String> {
+        let Ok(data) = fs::read_to_string(&self.path) else { return Ok(None) };
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data.trim())
+            .map_err(|e| e.to_string())?;
+        if bytes.len() < 12 {
+            return Err("Encrypted credentials file is corrupt".to_string());
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(12);
+        let plaintext = self.cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt credentials".to_string())?;
+        String::from_utf8(plaintext).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn set(&self, secret: &str) -> Result<(), String> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = self.cipher()?
+            .encrypt(&nonce, secret.as_bytes())
+            .map_err(|_| "Failed to encrypt credentials".to_string())?;
+
+        let mut bytes = nonce.to_vec();
+        bytes.extend_from_slice(&ciphertext);
+        write_private(&self.path, base64::engine::general_purpose::STANDARD.encode(bytes).as_bytes())
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Writes a file that only the current user can read (0600 on Unix).
+fn write_private(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to new files
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+        }
+    }
+    let mut file = options.open(path).map_err(|e| e.to_string())?;
+    file.write_all(data).map_err(|e| e.to_string())
+}
+
+/// The keyring when there is one, otherwise the encrypted file. The keyring
+/// is only probed the first time, at startup, and the choice is kept.
+pub fn store() -> Box<dyn SecretStore> {
+    open(ACCOUNT)
+}
+
+fn open(account: &str) -> Box<dyn SecretStore> {
+    static KEYRING: OnceLock<bool> = OnceLock::new();
+    let keyring = *KEYRING.get_or_init(KeyringStore::probe);
+    match keyring.then(|| KeyringStore::new(account)).flatten() {
+        Some(store) => Box::new(store),
+        None => Box::new(EncryptedFileStore::new(account)),
+    }
+}
+
+/// Vault contents as last read from or written to the secret store, by
+/// name. Saves compare against it so they don't keep going to the keyring.
+static STORED: Mutex<BTreeMap<String, BTreeMap<String, String>>> = Mutex::new(BTreeMap::new());
+/// References that `Vault::restore` couldn't resolve.
+static UNRESOLVED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Whether `value` is a reference left in place because its vault didn't
+/// have it. Such values are never sent as credentials.
+pub fn is_unresolved(value: &str) -> bool {
+    value.starts_with(REFERENCE_PREFIX) && UNRESOLVED.lock().unwrap_or_else(|e| e.into_inner()).contains(value)
+}
+
+/// Credentials of saved requests and tabs. The JSON files only hold a
+/// random `secret:<id>` reference in their place, and the values live in the
+/// secret store under the vault's name.
+#[derive(Default)]
+pub struct Vault {
+    secrets: BTreeMap<String, String>,
+    /// References handed out by `stash` since loading.
+    stashed: BTreeSet<String>,
+}
+
+impl Vault {
+    /// The stored vault. Fails if the secret store can't be read, and then
+    /// nothing that refers to the vault should be written either.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let mut stored = STORED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(secrets) = stored.get(name) {
+            return Ok(Self {
+                secrets: secrets.clone(),
+                ..Default::default()
+            });
+        }
+        let secrets: BTreeMap<String, String> = match open(name).get()? {
+            Some(data) => serde_json::from_str(&data).map_err(|e| e.to_string())?,
+            None => BTreeMap::new(),
+        };
+        stored.insert(name.to_string(), secrets.clone());
+        Ok(Self {
+            secrets,
+            ..Default::default()
+        })
+    }
+
+    /// Writes the secrets stashed since loading, unless that's what the
+    /// store already has. The rest are no longer referenced.
+    pub fn save(&self, name: &str) {
+        let secrets: BTreeMap<String, String> = self
+            .secrets
+            .iter()
+            .filter(|(reference, _)| self.stashed.contains(*reference))
+            .map(|(reference, secret)| (reference.clone(), secret.clone()))
+            .collect();
+        let mut stored = STORED.lock().unwrap_or_else(|e| e.into_inner());
+        if stored.get(name) == Some(&secrets) {
+            return;
+        }
+        let Ok(json) = serde_json::to_string(&secrets) else { return };
+        let result = if secrets.is_empty() { open(name).delete() } else { open(name).set(&json) };
+        match result {
+            Ok(()) => {
+                stored.insert(name.to_string(), secrets);
+            }
+            Err(e) => eprintln!("Failed to save {} secrets: {}", name, e),
+        }
+    }
+
+    /// Moves `secret` into the vault, leaving a reference in its place.
+    /// Anything non-empty is stashed, even if it looks like a reference,
+    /// except references that couldn't be restored, which stay as they are.
+    ///
+    /// References say nothing about the secret. A stored one not yet handed
+    /// out again is reused for the same value, so unchanged secrets keep
+    /// their reference, but the same value stashed twice gets two.
+    pub fn stash(&mut self, secret: &mut String) {
+        if secret.is_empty() || is_unresolved(secret) {
+            return;
+        }
+        let reused = self
+            .secrets
+            .iter()
+            .find(|(reference, value)| *value == secret && !self.stashed.contains(*reference))
+            .map(|(reference, _)| reference.clone());
+        let reference = reused.unwrap_or_else(|| {
+            let mut id = [0u8; 12];
+            OsRng.fill_bytes(&mut id);
+            format!("{}{}", REFERENCE_PREFIX, id.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+        });
+        self.stashed.insert(reference.clone());
+        self.secrets.insert(reference.clone(), std::mem::replace(secret, reference));
+    }
+
+    /// Puts a stashed secret back. Only references this vault issued are
+    /// replaced; any other value is left as it is, and one that looks like
+    /// a reference is remembered as unresolved.
+    pub fn restore(&self, secret: &mut String) {
+        match self.secrets.get(secret.as_str()) {
+            Some(value) => *secret = value.clone(),
+            None if secret.starts_with(REFERENCE_PREFIX) => {
+                UNRESOLVED.lock().unwrap_or_else(|e| e.into_inner()).insert(secret.clone());
+            }
+            None => {}
+        }
+    }
+}
+
+/// Moves credentials from the old plaintext `credentials.json` into the
+/// secret store and deletes the plaintext file.
+pub fn migrate_plaintext(store: &dyn SecretStore) {
+    let path = config_file("credentials.json");
+    let Ok(data) = fs::read_to_string(&path) else { return };
+
+    match store.set(&data) {
+        Ok(()) => {
+            fs::remove_file(&path).ok();
+            eprintln!("Moved plaintext credentials into secure storage");
+        }
+        Err(e) => eprintln!("Failed to migrate plaintext credentials: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(value: &str) {
+        let mut vault = Vault::default();
+        let mut secret = value.to_string();
+        vault.stash(&mut secret);
+        if !value.is_empty() {
+            assert!(secret.starts_with(REFERENCE_PREFIX));
+            assert_ne!(secret, value);
+        }
+        vault.restore(&mut secret);
+        assert_eq!(secret, value);
+    }
+
+    #[test]
+    fn stash_and_restore() {
+        round_trip("");
+        round_trip("hunter2");
+        round_trip("secret:hunter2");
+    }
+
+    #[test]
+    fn restore_keeps_foreign_values() {
+        let mut vault = Vault::default();
+        let mut stashed = "hunter2".to_string();
+        vault.stash(&mut stashed);
+
+        for value in ["secret:foreign", "plain"] {
+            let mut secret = value.to_string();
+            vault.restore(&mut secret);
+            assert_eq!(secret, value);
+        }
+        assert!(is_unresolved("secret:foreign"));
+        assert!(!is_unresolved("plain"));
+        assert!(!is_unresolved("secret:hunter2"));
+    }
+
+    #[test]
+    fn unresolved_references_stay_as_they_are() {
+        let mut secret = "secret:0123456789abcdef01234567".to_string();
+        Vault::default().restore(&mut secret);
+        let mut vault = Vault::default();
+        vault.stash(&mut secret);
+        assert_eq!(secret, "secret:0123456789abcdef01234567");
+        assert!(vault.secrets.is_empty());
+    }
+
+    #[test]
+    fn references_are_random_and_reused() {
+        let mut vault = Vault::default();
+        let mut first = "hunter2".to_string();
+        let mut second = "hunter2".to_string();
+        vault.stash(&mut first);
+        vault.stash(&mut second);
+        assert_ne!(first, second);
+
+        // Saving again hands out the same references
+        vault.stashed.clear();
+        let mut again = ["hunter2".to_string(), "hunter2".to_string(), "new".to_string()];
+        for secret in &mut again {
+            vault.stash(secret);
+        }
+        assert_eq!(BTreeSet::from([again[0].clone(), again[1].clone()]), BTreeSet::from([first, second]));
+        assert_eq!(vault.secrets.len(), 3);
+    }
+
+    #[test]
+    fn only_stashed_secrets_are_kept() {
+        let mut vault = Vault::default();
+        vault.secrets.insert("secret:old".to_string(), "gone".to_string());
+        let mut secret = "kept".to_string();
+        vault.stash(&mut secret);
+        assert_eq!(vault.stashed, BTreeSet::from([secret]));
+    }
+}
@@ -0,0 +1,482 @@
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+/// Older events are dropped past this, so an endless stream doesn't grow
+/// without bound.
+const MAX_EVENTS: usize = 50_000;
+/// How much of the raw stream is kept for history and saving.
+const MAX_TEXT: usize = 8 * 1024 * 1024;
+
+/// Response bodies that never finish on their own and are shown as they
+/// arrive instead.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /// `text/event-stream`
+    Sse,
+    /// Newline-delimited JSON, one event per line.
+    Lines,
+}
+
+impl Format {
+    pub fn detect(content_type: &str) -> Option<Self> {
+        let kind = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match kind.as_str() {
+            "text/event-stream" => Some(Format::Sse),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" | "application/x-jsonlines" | "application/stream+json" => Some(Format::Lines),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Format::Sse => "Server-Sent Events",
+            Format::Lines => "NDJSON",
+        }
+    }
+}
+
+/// One event, or one line of a line-delimited stream.
+pub struct Event {
+    /// Wall-clock time of the chunk it arrived in.
+    time: String,
+    /// SSE `event` field; empty for the default "message".
+    name: String,
+    /// SSE `id` field.
+    id: String,
+    data: String,
+}
+
+/// What the worker sends for each chunk read.
+struct Chunk {
+    len: usize,
+    text: String,
+    events: Vec<Event>,
+}
+
+/// Splits a stream into events. Bytes are held back until they make a
+/// whole line, so chunks can split anywhere, even inside a character.
+struct Decoder {
+    format: Format,
+    pending: Vec<u8>,
+    name: String,
+    id: String,
+    data: Vec<String>,
+}
+
+impl Decoder {
+    fn new(format: Format) -> Self {
+        Self {
+            format,
+            pending: Vec::new(),
+            name: String::new(),
+            id: String::new(),
+            data: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8], time: &str) -> Vec<Event> {
+        self.pending.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n' || b == b'\r') {
+            // A \r\n pair is one line ending, but the \n may not be here yet
+            if self.pending[end] == b'\r' && end + 1 == self.pending.len() {
+                break;
+            }
+            let skip = if self.pending[end] == b'\r' && self.pending[end + 1] == b'\n' { 2 } else { 1 };
+            let line: Vec<u8> = self.pending.drain(..end + skip).take(end).collect();
+            self.line(&String::from_utf8_lossy(&line), time, &mut events);
+        }
+        events
+    }
+
+    /// Whatever is left when the stream closes.
+    fn finish(&mut self, time: &str) -> Vec<Event> {
+        let mut events = Vec::new();
+        if !self.pending.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned();
+            self.line(&line, time, &mut events);
+        }
+        if self.format == Format::Sse {
+            self.line("", time, &mut events);
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str, time: &str, events: &mut Vec<Event>) {
+        if self.format == Format::Lines {
+            if !line.trim().is_empty() {
+                events.push(Event {
+                    time: time.to_string(),
+                    name: String::new(),
+                    id: String::new(),
+                    data: line.to_string(),
+                });
+            }
+            return;
+        }
+
+        // A blank line dispatches the event; data-less events are ignored
+        if line.is_empty() {
+            if !self.data.is_empty() {
+                events.push(Event {
+                    time: time.to_string(),
+                    name: std::mem::take(&mut self.name),
+                    id: self.id.clone(),
+                    data: std::mem::take(&mut self.data).join("\n"),
+                });
+            }
+            self.name.clear();
+            return;
+        }
+        if line.starts_with(':') {
+            return;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.name = value.to_string(),
+            "data" => self.data.push(value.to_string()),
+            // The id carries over to later events until changed
+            "id" if !value.contains('\0') => self.id = value.to_string(),
+            _ => {}
+        }
+    }
+}
+
+/// A streaming body being read by the request's worker. Dropping it stops
+/// the worker, which closes the connection.
+pub struct Live {
+    format: Format,
+    rx: mpsc::Receiver<Result<Chunk, String>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The worker's end of a `Live`: decodes what is read and passes it on.
+pub struct Feed {
+    decoder: Decoder,
+    /// The start of a character split across chunks, for the raw text.
+    partial: Vec<u8>,
+    tx: mpsc::Sender<Result<Chunk, String>>,
+    stop: Arc<AtomicBool>,
+    ctx: egui::Context,
+}
+
+impl Feed {
+    /// Set once the stream is stopped or no longer shown.
+    pub fn stopped(&self) -> &AtomicBool {
+        &self.stop
+    }
+
+    /// Returns false once nobody is listening anymore.
+    pub fn push(&mut self, bytes: &[u8]) -> bool {
+        let chunk = Chunk {
+            len: bytes.len(),
+            text: decode_text(&mut self.partial, bytes),
+            events: self.decoder.feed(bytes, &now()),
+        };
+        self.ctx.request_repaint();
+        self.tx.send(Ok(chunk)).is_ok()
+    }
+
+    /// The server closed the stream.
+    pub fn finish(mut self) {
+        let events = self.decoder.finish(&now());
+        let text = String::from_utf8_lossy(&self.partial).into_owned();
+        self.tx.send(Ok(Chunk { len: 0, text, events })).ok();
+    }
+
+    pub fn fail(self, error: String) {
+        self.tx.send(Err(error)).ok();
+    }
+}
+
+impl Drop for Feed {
+    fn drop(&mut self) {
+        self.ctx.request_repaint();
+    }
+}
+
+/// Decodes a chunk as UTF-8 after what `partial` held back from the last
+/// one. An incomplete character at the end is held back in turn; invalid
+/// bytes become U+FFFD.
+fn decode_text(partial: &mut Vec<u8>, bytes: &[u8]) -> String {
+    partial.extend_from_slice(bytes);
+    let mut text = String::new();
+    let mut rest = partial.as_slice();
+    while !rest.is_empty() {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                rest = &[];
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                match e.error_len() {
+                    Some(len) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &after[len..];
+                    }
+                    None => {
+                        rest = after;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    *partial = rest.to_vec();
+    text
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%H:%M:%S%.3f").to_string()
+}
+
+/// Connects a reader of `format` to the view that shows it.
+pub fn channel(format: Format, ctx: egui::Context) -> (Feed, Live) {
+    let (tx, rx) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let feed = Feed {
+        decoder: Decoder::new(format),
+        partial: Vec::new(),
+        tx,
+        stop: stop.clone(),
+        ctx,
+    };
+    (feed, Live { format, rx, stop })
+}
+
+/// The events of a streaming response, live or finished.
+pub struct Stream {
+    format: Format,
+    /// None once the stream ended or was stopped.
+    live: Option<Live>,
+    events: Vec<Event>,
+    /// Events dropped from the front to stay under `MAX_EVENTS`.
+    dropped: usize,
+    received: usize,
+    text: String,
+    /// Why the stream ended, once it has.
+    end: String,
+    /// Set when it ended and `poll` hasn't said so yet.
+    ended: bool,
+    /// Keep the newest event in view.
+    follow: bool,
+}
+
+impl Stream {
+    pub fn new(live: Live) -> Self {
+        Self {
+            format: live.format,
+            live: Some(live),
+            events: Vec::new(),
+            dropped: 0,
+            received: 0,
+            text: String::new(),
+            end: String::new(),
+            ended: false,
+            follow: true,
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.live.is_some()
+    }
+
+    /// The raw stream as received, up to `MAX_TEXT`.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    pub fn stop(&mut self) {
+        if self.live.take().is_some() {
+            self.end = "Stopped".to_string();
+            self.ended = true;
+        }
+    }
+
+    /// Takes whatever arrived. Returns true once after the stream ended or
+    /// was stopped.
+    pub fn poll(&mut self) -> bool {
+        let Some(live) = &self.live else { return std::mem::take(&mut self.ended) };
+        let mut end = None;
+        loop {
+            match live.rx.try_recv() {
+                Ok(Ok(chunk)) => {
+                    self.received += chunk.len;
+                    if self.text.len() < MAX_TEXT {
+                        self.text.push_str(&chunk.text);
+                    }
+                    self.events.extend(chunk.events);
+                }
+                Ok(Err(e)) => end = Some(format!("Error: {}", e)),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    end = end.or_else(|| Some("Ended".to_string()));
+                    break;
+                }
+            }
+        }
+        if self.events.len() > MAX_EVENTS {
+            let excess = self.events.len() - MAX_EVENTS;
+            self.events.drain(..excess);
+            self.dropped += excess;
+        }
+
+        if let Some(end) = end {
+            self.live = None;
+            self.end = end;
+            self.ended = true;
+        }
+        std::mem::take(&mut self.ended)
+    }
+
+    /// The event log, one row per event. Returns true when "Save to file…"
+    /// was clicked.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut save = false;
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(self.format.label()).size(12.0).color(egui::Color32::GRAY));
+            ui.add_space(10.0);
+            if self.is_live() {
+                ui.spinner();
+                ui.label(egui::RichText::new("Live").size(12.0).color(egui::Color32::from_rgb(0, 200, 120)));
+                if ui.small_button("Stop").on_hover_text("Esc").clicked() {
+                    self.stop();
+                }
+            } else {
+                let color = if self.end.starts_with("Error") { egui::Color32::from_rgb(255, 80, 80) } else { egui::Color32::GRAY };
+                ui.label(egui::RichText::new(&self.end).size(12.0).color(color));
+            }
+            ui.add_space(10.0);
+            let count = self.events.len() + self.dropped;
+            let dropped = if self.dropped > 0 { format!(", oldest {} dropped", self.dropped) } else { String::new() };
+            ui.label(egui::RichText::new(format!("{} events, {} bytes{}", count, self.received, dropped)).size(12.0).color(egui::Color32::GRAY));
+            ui.add_space(10.0);
+            ui.toggle_value(&mut self.follow, "Follow").on_hover_text("Keep the newest event in view");
+            save = ui.small_button("Save to file…").clicked();
+        });
+        ui.add_space(4.0);
+
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .id_source("response_stream")
+            .auto_shrink([false; 2])
+            .stick_to_bottom(self.follow)
+            .show_rows(ui, row_height, self.events.len(), |ui, rows| {
+                for event in &self.events[rows] {
+                    let mut job = egui::text::LayoutJob::default();
+                    let mut append = |text: &str, color: egui::Color32| {
+                        job.append(text, 0.0, egui::TextFormat::simple(font.clone(), color));
+                    };
+                    append(&format!("{}  ", event.time), egui::Color32::DARK_GRAY);
+                    if !event.name.is_empty() {
+                        append(&format!("{} ", event.name), egui::Color32::from_rgb(100, 180, 255));
+                    }
+                    if !event.id.is_empty() {
+                        append(&format!("#{} ", event.id), egui::Color32::from_rgb(200, 150, 255));
+                    }
+                    // One row per event; the full data is on hover
+                    append(&event.data.replace('\n', " ⏎ "), ui.visuals().text_color());
+
+                    let hover = if event.data.contains('\n') { event.data.as_str() } else { "Click to copy the data" };
+                    if ui.add(egui::Label::new(job).wrap(false).sense(egui::Sense::click())).on_hover_text(hover).clicked() {
+                        ui.output_mut(|o| o.copied_text = event.data.clone());
+                    }
+                }
+            });
+        save
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds the chunks and closes the stream, returning (name, id, data)
+    /// of each event.
+    fn decode(format: Format, chunks: &[&[u8]]) -> Vec<(String, String, String)> {
+        let mut decoder = Decoder::new(format);
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(decoder.feed(chunk, ""));
+        }
+        events.extend(decoder.finish(""));
+        events.into_iter().map(|event| (event.name, event.id, event.data)).collect()
+    }
+
+    fn event(name: &str, id: &str, data: &str) -> (String, String, String) {
+        (name.to_string(), id.to_string(), data.to_string())
+    }
+
+    #[test]
+    fn sse_fields() {
+        let stream = b"event: update\nid: 1\ndata: {\"a\":1}\n\n: comment\ndata:no space\n\nid\ndata\n\nretry: 10\nfoo: bar\n\n";
+        assert_eq!(
+            decode(Format::Sse, &[stream]),
+            [event("update", "1", "{\"a\":1}"), event("", "1", "no space"), event("", "", "")]
+        );
+    }
+
+    #[test]
+    fn sse_multi_line_data() {
+        assert_eq!(
+            decode(Format::Sse, &[b"data: one\ndata:  two\ndata\n\n"]),
+            [event("", "", "one\n two\n")]
+        );
+    }
+
+    #[test]
+    fn sse_line_endings() {
+        let expected = [event("a", "", "x"), event("", "", "y")];
+        assert_eq!(decode(Format::Sse, &[b"event: a\r\ndata: x\r\n\r\ndata: y\r\n\r\n"]), expected);
+        assert_eq!(decode(Format::Sse, &[b"event: a\rdata: x\r\rdata: y\r\r"]), expected);
+        // A \r\n split over two chunks is still one line ending
+        assert_eq!(decode(Format::Sse, &[b"event: a\r", b"\ndata: x\r", b"\n\r", b"\ndata: y"]), expected);
+    }
+
+    #[test]
+    fn sse_split_character() {
+        let bytes = "data: é\n\n".as_bytes();
+        assert_eq!(decode(Format::Sse, &[&bytes[..7], &bytes[7..]]), [event("", "", "é")]);
+    }
+
+    #[test]
+    fn ndjson_lines() {
+        assert_eq!(
+            decode(Format::Lines, &[b"{\"a\":1}\n\n  \r\n{\"b\"", b":2}\r\n{\"c\":3}"]),
+            [event("", "", "{\"a\":1}"), event("", "", "{\"b\":2}"), event("", "", "{\"c\":3}")]
+        );
+    }
+
+    #[test]
+    fn text_across_chunks() {
+        let bytes = "aé日🙂".as_bytes();
+        for split in 0..=bytes.len() {
+            let mut partial = Vec::new();
+            let mut text = decode_text(&mut partial, &bytes[..split]);
+            text.push_str(&decode_text(&mut partial, &bytes[split..]));
+            assert_eq!(text, "aé日🙂");
+            assert!(partial.is_empty());
+        }
+
+        let mut partial = Vec::new();
+        assert_eq!(decode_text(&mut partial, b"a\xffb\xe6\x97"), "a\u{fffd}b");
+        assert_eq!(partial, b"\xe6\x97");
+        assert_eq!(decode_text(&mut partial, b"x"), "\u{fffd}x");
+        assert!(partial.is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::collections::SavedRequest;
+use crate::secrets::Vault;
+use crate::{config_file, method_color};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const MAX_TITLE_CHARS: usize = 28;
+
+/// A tab as written to disk: the request being edited and what it looked
+/// like when last loaded or saved. Responses aren't kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedTab {
+    pub request: SavedRequest,
+    pub baseline: SavedRequest,
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+/// Open tabs, restored on the next launch.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedTabs {
+    #[serde(default)]
+    pub active: usize,
+    #[serde(default)]
+    pub tabs: Vec<SavedTab>,
+}
+
+impl SavedTabs {
+    pub fn load() -> Self {
+        let mut saved: Self = fs::read_to_string(config_file("tabs.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        match Vault::load("tabs") {
+            Ok(vault) => {
+                for tab in &mut saved.tabs {
+                    tab.request.restore_secrets(&vault);
+                    tab.baseline.restore_secrets(&vault);
+                }
+            }
+            Err(e) => eprintln!("Failed to load tab secrets: {}", e),
+        }
+        saved
+    }
+
+    /// Credentials go to the secret store; the file only references them.
+    /// Nothing is written while the stored secrets can't be read, since that
+    /// would replace them.
+    pub fn save(&self) {
+        let mut vault = match Vault::load("tabs") {
+            Ok(vault) => vault,
+            Err(e) => {
+                eprintln!("Not saving tabs, their secrets can't be read: {}", e);
+                return;
+            }
+        };
+        let mut stashed = SavedTabs {
+            active: self.active,
+            tabs: self.tabs.clone(),
+        };
+        for tab in &mut stashed.tabs {
+            tab.request.stash_secrets(&mut vault);
+            tab.baseline.stash_secrets(&mut vault);
+        }
+        if let Ok(json) = serde_json::to_string_pretty(&stashed) {
+            vault.save("tabs");
+            fs::write(config_file("tabs.json"), json).ok();
+        }
+    }
+}
+
+/// What the strip needs to know to draw one tab.
+pub struct TabLabel {
+    pub method: String,
+    pub title: String,
+    pub dirty: bool,
+    pub busy: bool,
+}
+
+/// What the strip wants the app to do after a frame.
+pub enum TabAction {
+    Select(usize),
+    Close(usize),
+    New,
+}
+
+/// Draws the tab strip. Middle-click or "×" closes a tab.
+pub fn show_strip(ui: &mut egui::Ui, labels: &[TabLabel], active: usize, green: egui::Color32) -> Option<TabAction> {
+    let mut action = None;
+
+    egui::ScrollArea::horizontal().id_source("tab_strip").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 4.0;
+
+            for (i, label) in labels.iter().enumerate() {
+                let fill = if i == active { egui::Color32::from_rgb(40, 40, 40) } else { egui::Color32::from_rgb(24, 24, 24) };
+                let frame = egui::Frame::none()
+                    .fill(fill)
+                    .rounding(4.0)
+                    .inner_margin(egui::Margin::symmetric(8.0, 4.0));
+
+                frame.show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 6.0;
+                        ui.label(egui::RichText::new(&label.method).size(11.0).color(method_color(&label.method, green)).strong());
+
+                        let mut title = if label.title.chars().count() > MAX_TITLE_CHARS {
+                            format!("{}…", label.title.chars().take(MAX_TITLE_CHARS).collect::<String>())
+                        } else {
+                            label.title.clone()
+                        };
+                        if label.dirty {
+                            title.push_str(" ●");
+                        }
+                        let text = if i == active { egui::RichText::new(title) } else { egui::RichText::new(title).color(egui::Color32::GRAY) };
+
+                        let response = ui
+                            .add(egui::Label::new(text).sense(egui::Sense::click()))
+                            .on_hover_text(&label.title);
+                        if response.clicked() {
+                            action = Some(TabAction::Select(i));
+                        }
+                        if response.middle_clicked() {
+                            action = Some(TabAction::Close(i));
+                        }
+
+                        if label.busy {
+                            ui.spinner();
+                        }
+                        if ui.small_button("×").on_hover_text("Close tab (Cmd+W)").clicked() {
+                            action = Some(TabAction::Close(i));
+                        }
+                    });
+                });
+            }
+
+            if ui.small_button("+").on_hover_text("New tab (Cmd+T)").clicked() {
+                action = Some(TabAction::New);
+            }
+        });
+    });
+
+    action
+}
@@ -0,0 +1,1076 @@
+use crate::filter;
+use crate::find::{self, Find, Match};
+use eframe::egui;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Raw lines longer than this are split over several rows. Laying out one
+/// enormous line every frame is what makes minified bodies crawl.
+const RAW_ROW_CHARS: usize = 1000;
+/// Longer strings are cut short in the pretty and tree views. Raw shows
+/// them whole.
+const MAX_STRING_CHARS: usize = 2000;
+/// Children listed per page in the tree view.
+const TREE_PAGE: usize = 200;
+/// Larger images are scaled down to fit a texture.
+const MAX_IMAGE_SIDE: u32 = 4096;
+/// Markup text up to this long stays on its element's line.
+const INLINE_TEXT_CHARS: usize = 100;
+/// How long the filter waits after the last keystroke before it runs.
+const FILTER_DELAY: Duration = Duration::from_millis(300);
+/// HTML elements that never have a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Key,
+    String,
+    Number,
+    Literal,
+    Punct,
+    Text,
+    Comment,
+}
+
+impl Token {
+    fn color(self) -> egui::Color32 {
+        match self {
+            Token::Key => egui::Color32::from_rgb(100, 180, 255),
+            Token::String => egui::Color32::from_rgb(0, 200, 120),
+            Token::Number => egui::Color32::from_rgb(255, 180, 100),
+            Token::Literal => egui::Color32::from_rgb(200, 150, 255),
+            Token::Punct => egui::Color32::GRAY,
+            Token::Text => egui::Color32::from_rgb(220, 220, 220),
+            Token::Comment => egui::Color32::DARK_GRAY,
+        }
+    }
+}
+
+/// One line of pretty-printed JSON or markup.
+struct Line {
+    depth: usize,
+    tokens: Vec<(Token, String)>,
+    /// For a line opening a non-empty object, array or element: the line
+    /// that closes it and how many children it has.
+    fold: Option<(usize, usize)>,
+}
+
+/// A row of the raw view. Only the first row of a split line is numbered.
+struct RawRow {
+    number: Option<usize>,
+    range: Range<usize>,
+}
+
+/// A decoded image body.
+struct Image {
+    pixels: egui::ColorImage,
+    format: String,
+    width: u32,
+    height: u32,
+}
+
+/// A response body prepared for display. Built on the worker thread so
+/// that parsing and formatting a large body doesn't stall the UI.
+#[derive(Default)]
+pub struct Document {
+    /// Lowercase, without parameters.
+    content_type: String,
+    /// Shared with filters running on a worker thread.
+    json: Option<Arc<Value>>,
+    /// Pretty-printed JSON or markup.
+    lines: Vec<Line>,
+    raw_rows: Vec<RawRow>,
+    /// Set for bodies that aren't text.
+    bytes: Option<Vec<u8>>,
+    image: Option<Image>,
+}
+
+impl Document {
+    /// A text body. JSON is recognised by parsing; XML and HTML by the
+    /// Content-Type or how the text starts.
+    pub fn new(text: &str, content_type: &str) -> Self {
+        let content_type = essence(content_type);
+        if let Ok(json) = serde_json::from_str::<Value>(text) {
+            return Self {
+                content_type,
+                ..Self::from_json(json, text)
+            };
+        }
+        let start: String = text.trim_start().chars().take(15).collect::<String>().to_ascii_lowercase();
+        let html = content_type.contains("html") || start.starts_with("<!doctype html") || start.starts_with("<html");
+        let markup = html || content_type.contains("xml") || start.starts_with("<?xml") || start.starts_with("<svg");
+        Self {
+            lines: if markup { markup_lines(text, html) } else { Vec::new() },
+            raw_rows: raw_rows(text),
+            content_type,
+            ..Default::default()
+        }
+    }
+
+    /// A body as received. Anything that isn't text is kept as bytes for
+    /// the image preview, hex dump and saving; the returned string stands
+    /// in for it in history.
+    pub fn from_bytes(bytes: Vec<u8>, content_type: &str) -> (String, Self) {
+        let kind = essence(content_type);
+        let bytes = if is_binary_type(&kind) {
+            bytes
+        } else {
+            match String::from_utf8(bytes) {
+                Ok(text) => {
+                    let doc = Self::new(&text, content_type);
+                    return (text, doc);
+                }
+                Err(e) => e.into_bytes(),
+            }
+        };
+
+        let body = format!("[{} bytes of {}]", bytes.len(), if kind.is_empty() { "binary data" } else { &kind });
+        let doc = Self {
+            content_type: kind,
+            image: decode_image(&bytes),
+            bytes: Some(bytes),
+            ..Default::default()
+        };
+        (body, doc)
+    }
+
+    /// `text` is what the raw view shows for `json`.
+    fn from_json(json: Value, text: &str) -> Self {
+        let mut lines = Vec::new();
+        push_value(&mut lines, 0, None, &json, false);
+        Self {
+            json: Some(Arc::new(json)),
+            lines,
+            raw_rows: raw_rows(text),
+            ..Default::default()
+        }
+    }
+
+    /// The views that make sense for this body, the default first.
+    fn modes(&self) -> &'static [ViewMode] {
+        if self.bytes.is_some() {
+            if self.image.is_some() {
+                &[ViewMode::Preview, ViewMode::Hex]
+            } else {
+                &[ViewMode::Hex]
+            }
+        } else if self.json.is_some() {
+            &[ViewMode::Pretty, ViewMode::Raw, ViewMode::Tree]
+        } else if !self.lines.is_empty() {
+            &[ViewMode::Pretty, ViewMode::Raw]
+        } else {
+            &[ViewMode::Raw]
+        }
+    }
+}
+
+/// The media type of a Content-Type header, lowercase and without
+/// parameters.
+fn essence(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase()
+}
+
+/// Types that are never worth showing as text, even if they decode.
+fn is_binary_type(kind: &str) -> bool {
+    (kind.starts_with("image/") && !kind.contains("svg"))
+        || ["audio/", "video/", "font/"].iter().any(|prefix| kind.starts_with(prefix))
+        || matches!(kind, "application/octet-stream" | "application/pdf" | "application/zip" | "application/gzip" | "application/wasm")
+}
+
+fn decode_image(bytes: &[u8]) -> Option<Image> {
+    let format = image::guess_format(bytes).ok()?;
+    let mut decoded = image::load_from_memory_with_format(bytes, format).ok()?;
+    let (width, height) = (decoded.width(), decoded.height());
+    if width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE {
+        decoded = decoded.thumbnail(MAX_IMAGE_SIDE, MAX_IMAGE_SIDE);
+    }
+    let rgba = decoded.to_rgba8();
+    Some(Image {
+        pixels: egui::ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw()),
+        format: format!("{:?}", format).to_uppercase(),
+        width,
+        height,
+    })
+}
+
+fn raw_rows(text: &str) -> Vec<RawRow> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    for (number, line) in text.split('\n').enumerate() {
+        let mut row_start = start;
+        let mut numbered = false;
+        for (count, (i, _)) in line.char_indices().enumerate() {
+            if count > 0 && count % RAW_ROW_CHARS == 0 {
+                rows.push(RawRow {
+                    number: (!numbered).then_some(number + 1),
+                    range: row_start..start + i,
+                });
+                numbered = true;
+                row_start = start + i;
+            }
+        }
+        rows.push(RawRow {
+            number: (!numbered).then_some(number + 1),
+            range: row_start..start + line.len(),
+        });
+        start += line.len() + 1;
+    }
+    rows
+}
+
+fn push_value(lines: &mut Vec<Line>, depth: usize, key: Option<&str>, value: &Value, comma: bool) {
+    let mut tokens = Vec::new();
+    if let Some(key) = key {
+        tokens.push((Token::Key, quote(key)));
+        tokens.push((Token::Punct, ": ".to_string()));
+    }
+
+    let (open, close, children): (&str, &str, Vec<(Option<&str>, &Value)>) = match value {
+        Value::Object(map) if !map.is_empty() => ("{", "}", map.iter().map(|(k, v)| (Some(k.as_str()), v)).collect()),
+        Value::Array(items) if !items.is_empty() => ("[", "]", items.iter().map(|v| (None, v)).collect()),
+        scalar => {
+            tokens.push(scalar_token(scalar));
+            if comma {
+                tokens.push((Token::Punct, ",".to_string()));
+            }
+            lines.push(Line { depth, tokens, fold: None });
+            return;
+        }
+    };
+
+    tokens.push((Token::Punct, open.to_string()));
+    let opener = lines.len();
+    lines.push(Line { depth, tokens, fold: None });
+
+    let count = children.len();
+    for (i, (key, child)) in children.into_iter().enumerate() {
+        push_value(lines, depth + 1, key, child, i + 1 < count);
+    }
+
+    let close = if comma { format!("{},", close) } else { close.to_string() };
+    lines.push(Line {
+        depth,
+        tokens: vec![(Token::Punct, close)],
+        fold: None,
+    });
+    lines[opener].fold = Some((lines.len() - 1, count));
+}
+
+fn scalar_token(value: &Value) -> (Token, String) {
+    match value {
+        Value::String(s) => (Token::String, quote(s)),
+        Value::Number(n) => (Token::Number, n.to_string()),
+        Value::Bool(b) => (Token::Literal, b.to_string()),
+        Value::Null => (Token::Literal, "null".to_string()),
+        Value::Object(_) => (Token::Punct, "{}".to_string()),
+        Value::Array(_) => (Token::Punct, "[]".to_string()),
+    }
+}
+
+/// JSON-quotes a string, shortening it past `MAX_STRING_CHARS`.
+fn quote(s: &str) -> String {
+    if s.chars().count() <= MAX_STRING_CHARS {
+        return serde_json::to_string(s).unwrap_or_default();
+    }
+    let cut: String = s.chars().take(MAX_STRING_CHARS).collect();
+    let quoted = serde_json::to_string(&cut).unwrap_or_default();
+    format!("{}…\"", &quoted[..quoted.len() - 1])
+}
+
+/// A piece of XML or HTML, as far as indenting it goes.
+enum Markup<'a> {
+    /// Name, tokens and whether it closes itself.
+    Open(&'a str, Vec<(Token, String)>, bool),
+    Close(&'a str, Vec<(Token, String)>),
+    Text(&'a str),
+    /// Comments, CDATA, doctypes and processing instructions.
+    Other(Token, &'a str),
+}
+
+/// Splits markup into tags and text. Lenient: whatever doesn't look like
+/// a tag is text, and unterminated constructs run to the end.
+fn markup_events(text: &str, html: bool) -> Vec<Markup<'_>> {
+    let mut events = Vec::new();
+    let mut i = 0;
+    let until = |from: usize, end: &str| text[from..].find(end).map_or(text.len(), |j| from + j + end.len());
+
+    while i < text.len() {
+        let rest = &text[i..];
+        let next = rest.chars().nth(1);
+        let end = if rest.starts_with("<!--") {
+            let end = until(i + 4, "-->");
+            events.push(Markup::Other(Token::Comment, &text[i..end]));
+            end
+        } else if rest.starts_with("<![CDATA[") {
+            let end = until(i + 9, "]]>");
+            events.push(Markup::Other(Token::Text, &text[i..end]));
+            end
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = until(i + 2, ">");
+            events.push(Markup::Other(Token::Literal, &text[i..end]));
+            end
+        } else if rest.starts_with("</") {
+            let end = until(i + 2, ">");
+            let name = text[i + 2..end].trim_end_matches('>').trim();
+            let tokens = vec![(Token::Punct, "</".to_string()), (Token::Key, name.to_string()), (Token::Punct, ">".to_string())];
+            events.push(Markup::Close(name, tokens));
+            end
+        } else if rest.starts_with('<') && next.is_some_and(|c| c.is_alphabetic()) {
+            let end = tag_end(text, i + 1);
+            let (name, tokens, self_closing) = tag_tokens(text[i + 1..end].trim_end_matches('>'));
+            events.push(Markup::Open(name, tokens, self_closing));
+            // Script and style contents aren't markup
+            let lower = name.to_ascii_lowercase();
+            if html && !self_closing && (lower == "script" || lower == "style") {
+                let close = format!("</{}", lower);
+                let stop = text[end..].to_ascii_lowercase().find(&close).map_or(text.len(), |j| end + j);
+                events.push(Markup::Text(&text[end..stop]));
+                stop
+            } else {
+                end
+            }
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let end = rest[first..].find('<').map_or(text.len(), |j| i + first + j);
+            events.push(Markup::Text(&text[i..end]));
+            end
+        };
+        i = end;
+    }
+    events
+}
+
+/// Where a tag starting at `from` ends, past its `>`, skipping quoted
+/// attribute values.
+fn tag_end(text: &str, from: usize) -> usize {
+    let mut quote = None;
+    for (j, c) in text[from..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return from + j + 1,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+/// Tokens for the inside of an opening tag, e.g. `a href="/x"`.
+fn tag_tokens(inner: &str) -> (&str, Vec<(Token, String)>, bool) {
+    let self_closing = inner.ends_with('/');
+    let inner = inner.trim_end_matches('/');
+    let name_end = inner.find(|c: char| c.is_whitespace()).unwrap_or(inner.len());
+    let name = &inner[..name_end];
+    let mut tokens = vec![(Token::Punct, "<".to_string()), (Token::Key, name.to_string())];
+
+    let mut rest = inner[name_end..].trim_start();
+    while !rest.is_empty() {
+        let attr_end = rest.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(rest.len());
+        tokens.push((Token::Punct, " ".to_string()));
+        tokens.push((Token::Literal, rest[..attr_end].to_string()));
+        rest = rest[attr_end..].trim_start();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let value_end = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => after[1..].find(q).map_or(after.len(), |j| j + 2),
+                _ => after.find(char::is_whitespace).unwrap_or(after.len()),
+            };
+            tokens.push((Token::Punct, "=".to_string()));
+            tokens.push((Token::String, after[..value_end].to_string()));
+            rest = after[value_end..].trim_start();
+        }
+    }
+    tokens.push((Token::Punct, if self_closing { "/>" } else { ">" }.to_string()));
+    (name, tokens, self_closing)
+}
+
+/// Indents markup one tag per line, keeping short text on its element's
+/// line. Elements left open, as HTML allows, are closed by their parent.
+fn markup_lines(text: &str, html: bool) -> Vec<Line> {
+    let events = markup_events(text, html);
+    let mut lines = Vec::new();
+    // Name, opening line and child count of each open element
+    let mut stack: Vec<(&str, usize, usize)> = Vec::new();
+
+    let mut i = 0;
+    while i < events.len() {
+        let depth = stack.len();
+        let push = |lines: &mut Vec<Line>, tokens: Vec<(Token, String)>, stack: &mut Vec<(&str, usize, usize)>| {
+            if let Some(parent) = stack.last_mut() {
+                parent.2 += 1;
+            }
+            lines.push(Line { depth, tokens, fold: None });
+        };
+
+        match &events[i] {
+            Markup::Open(name, tokens, self_closing) => {
+                let void = html && VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str());
+                let mut tokens = tokens.clone();
+                match (events.get(i + 1), events.get(i + 2)) {
+                    _ if *self_closing || void => {}
+                    (Some(Markup::Close(close, close_tokens)), _) if close.eq_ignore_ascii_case(name) => {
+                        tokens.extend(close_tokens.iter().cloned());
+                        i += 1;
+                    }
+                    (Some(Markup::Text(inner)), Some(Markup::Close(close, close_tokens)))
+                        if close.eq_ignore_ascii_case(name) && !inner.trim().contains('\n') && inner.trim().chars().count() <= INLINE_TEXT_CHARS =>
+                    {
+                        if !inner.trim().is_empty() {
+                            tokens.push((Token::Text, inner.trim().to_string()));
+                        }
+                        tokens.extend(close_tokens.iter().cloned());
+                        i += 2;
+                    }
+                    _ => {
+                        push(&mut lines, tokens, &mut stack);
+                        stack.push((name, lines.len() - 1, 0));
+                        i += 1;
+                        continue;
+                    }
+                }
+                push(&mut lines, tokens, &mut stack);
+            }
+            Markup::Close(name, tokens) => match stack.iter().rposition(|(open, _, _)| open.eq_ignore_ascii_case(name)) {
+                Some(position) => {
+                    stack.truncate(position + 1);
+                    let (_, opener, children) = stack.pop().unwrap_or_default();
+                    lines.push(Line {
+                        depth: stack.len(),
+                        tokens: tokens.clone(),
+                        fold: None,
+                    });
+                    lines[opener].fold = Some((lines.len() - 1, children));
+                }
+                None => push(&mut lines, tokens.clone(), &mut stack),
+            },
+            Markup::Text(text) | Markup::Other(_, text) => {
+                let token = match &events[i] {
+                    Markup::Other(token, _) => *token,
+                    _ => Token::Text,
+                };
+                for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                    push(&mut lines, vec![(token, line.to_string())], &mut stack);
+                }
+            }
+        }
+        i += 1;
+    }
+    lines
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ViewMode {
+    Pretty,
+    Raw,
+    Tree,
+    Preview,
+    Hex,
+}
+
+impl ViewMode {
+    fn label(self) -> &'static str {
+        match self {
+            ViewMode::Pretty => "Pretty",
+            ViewMode::Raw => "Raw",
+            ViewMode::Tree => "Tree",
+            ViewMode::Preview => "Preview",
+            ViewMode::Hex => "Hex",
+        }
+    }
+}
+
+/// The response body viewer: pretty JSON, XML or HTML with folding, the
+/// raw text, a JSON tree, an image preview or a hex dump, depending on the
+/// body. Only the rows on screen are laid out, so multi-megabyte bodies
+/// stay responsive.
+pub struct ResponseView {
+    doc: Document,
+    /// The last view picked; used whenever the body supports it.
+    mode: ViewMode,
+    /// The image preview, uploaded on first show.
+    texture: Option<egui::TextureHandle>,
+    /// A JSONPath or jq expression applied to the body.
+    filter: String,
+    /// The filter's result and its text. Kept while a half-typed filter
+    /// fails, so the view doesn't flicker.
+    filtered: Option<(String, Document)>,
+    filter_error: String,
+    /// When the filter should next run: shortly after it was edited, or
+    /// straight away for a new body.
+    filter_due: Option<Instant>,
+    /// A filter running on a worker thread, like building a `Document`,
+    /// so large bodies don't stall the UI.
+    filtering: Option<mpsc::Receiver<Result<(String, Document), String>>>,
+    /// Opening lines of folded objects and arrays, in whichever document
+    /// is shown.
+    folded: HashSet<usize>,
+    /// Lines not hidden inside a fold.
+    visible: Vec<usize>,
+    /// Bumped per document so the tree doesn't inherit open nodes.
+    generation: u64,
+    /// Searches the pretty or raw view, whichever is showing.
+    find: Find,
+}
+
+impl Default for ResponseView {
+    fn default() -> Self {
+        Self {
+            doc: Document::default(),
+            mode: ViewMode::Pretty,
+            texture: None,
+            filter: String::new(),
+            filtered: None,
+            filter_error: String::new(),
+            filter_due: None,
+            filtering: None,
+            folded: HashSet::new(),
+            visible: Vec::new(),
+            generation: 0,
+            find: Find::new("response_find"),
+        }
+    }
+}
+
+impl ResponseView {
+    pub fn set_document(&mut self, doc: Document) {
+        self.doc = doc;
+        self.texture = None;
+        self.filtering = None;
+        self.set_filtered(None);
+        self.filter_due = (!self.filter.trim().is_empty()).then(Instant::now);
+    }
+
+    /// Starts the shown document afresh.
+    fn set_filtered(&mut self, filtered: Option<(String, Document)>) {
+        self.filtered = filtered;
+        self.folded.clear();
+        self.generation += 1;
+        self.refresh();
+    }
+
+    /// Runs the filter against the full document on a worker thread. A
+    /// filter still running is abandoned.
+    fn start_filter(&mut self, ctx: &egui::Context) {
+        self.filter_due = None;
+        self.filter_error.clear();
+        let json = match &self.doc.json {
+            Some(json) if !self.filter.trim().is_empty() => json.clone(),
+            _ => {
+                self.filtering = None;
+                self.set_filtered(None);
+                return;
+            }
+        };
+
+        let expr = self.filter.clone();
+        let ctx = ctx.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = filter::apply(&expr, &json).map(|value| {
+                let text = serde_json::to_string_pretty(&value).unwrap_or_default();
+                let doc = Document::from_json(value, &text);
+                (text, doc)
+            });
+            tx.send(result).ok();
+            ctx.request_repaint();
+        });
+        self.filtering = Some(rx);
+    }
+
+    /// Starts the filter once it's due and takes its result when done.
+    fn poll_filter(&mut self, ctx: &egui::Context) {
+        if let Some(due) = self.filter_due {
+            let wait = due.saturating_duration_since(Instant::now());
+            if wait.is_zero() {
+                self.start_filter(ctx);
+            } else {
+                ctx.request_repaint_after(wait);
+            }
+        }
+        let Some(rx) = &self.filtering else { return };
+        match rx.try_recv() {
+            Ok(Ok(filtered)) => {
+                self.filtering = None;
+                self.set_filtered(Some(filtered));
+            }
+            Ok(Err(e)) => {
+                self.filtering = None;
+                self.filter_error = e;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.filtering = None,
+        }
+    }
+
+    /// Opens the find bar. The tree view can't be searched, so it switches
+    /// to the pretty one. Binary bodies have nothing to search.
+    pub fn open_find(&mut self) {
+        if self.doc.bytes.is_some() {
+            return;
+        }
+        if self.mode == ViewMode::Tree {
+            self.mode = ViewMode::Pretty;
+        }
+        self.find.open();
+    }
+
+    /// What saving the response writes: the bytes as received for binary
+    /// bodies, otherwise `text`.
+    pub fn body_bytes<'a>(&'a self, text: &'a str) -> &'a [u8] {
+        self.doc.bytes.as_deref().unwrap_or(text.as_bytes())
+    }
+
+    /// A file name to save the body as: from Content-Disposition, else the
+    /// URL's last path segment, with an extension for the content type if
+    /// it has none.
+    pub fn file_name(&self, url: &str, disposition: Option<&str>) -> String {
+        let from_disposition = disposition.and_then(|value| {
+            let params: Vec<(String, &str)> = value
+                .split(';')
+                .filter_map(|part| part.split_once('='))
+                .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim()))
+                .collect();
+            let extended = params.iter().find(|(key, _)| key == "filename*").map(|(_, value)| {
+                // charset'language'percent-encoded
+                let encoded = value.splitn(3, '\'').last().unwrap_or_default();
+                crate::query::decode(encoded)
+            });
+            extended.or_else(|| params.iter().find(|(key, _)| key == "filename").map(|(_, value)| value.trim_matches('"').to_string()))
+        });
+        let from_url = || {
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+            let path = path.split_once("://").map_or(path, |(_, rest)| rest.split_once('/').map_or("", |(_, path)| path));
+            path.rsplit('/').next().map(crate::query::decode).filter(|name| !name.is_empty())
+        };
+
+        let name = from_disposition.filter(|name| !name.trim().is_empty()).or_else(from_url).unwrap_or_else(|| "response".to_string());
+        let mut name: String = name.trim().chars().map(|c| if matches!(c, '/' | '\\' | ':') || c.is_control() { '_' } else { c }).collect();
+        if !name.contains('.') {
+            name.push_str(self.extension());
+        }
+        name
+    }
+
+    fn extension(&self) -> &'static str {
+        let kind = self.doc.content_type.as_str();
+        if let Some(image) = &self.doc.image {
+            return match image.format.as_str() {
+                "PNG" => ".png",
+                "JPEG" => ".jpg",
+                "GIF" => ".gif",
+                "WEBP" => ".webp",
+                "BMP" => ".bmp",
+                "ICO" => ".ico",
+                _ => ".bin",
+            };
+        }
+        if self.doc.json.is_some() || kind.contains("json") {
+            ".json"
+        } else if kind.contains("html") {
+            ".html"
+        } else if kind.contains("svg") {
+            ".svg"
+        } else if kind.contains("xml") {
+            ".xml"
+        } else if kind == "application/pdf" {
+            ".pdf"
+        } else if kind == "application/zip" {
+            ".zip"
+        } else if self.doc.bytes.is_some() {
+            ".bin"
+        } else {
+            ".txt"
+        }
+    }
+
+    /// The document on screen: the filter's result if there is one.
+    fn current(&self) -> &Document {
+        self.filtered.as_ref().map(|(_, doc)| doc).unwrap_or(&self.doc)
+    }
+
+    fn refresh(&mut self) {
+        self.visible.clear();
+        let lines = &self.filtered.as_ref().map(|(_, doc)| doc).unwrap_or(&self.doc).lines;
+        let mut i = 0;
+        while i < lines.len() {
+            self.visible.push(i);
+            i = match lines[i].fold {
+                Some((close, _)) if self.folded.contains(&i) => close + 1,
+                _ => i + 1,
+            };
+        }
+    }
+
+    /// Returns true when "Save to file…" was clicked.
+    pub fn show(&mut self, ui: &mut egui::Ui, text: &str) -> bool {
+        if text.is_empty() {
+            return false;
+        }
+        self.poll_filter(ui.ctx());
+        let is_json = self.doc.json.is_some();
+        let modes = self.doc.modes();
+        let mode = if modes.contains(&self.mode) { self.mode } else { modes[0] };
+        let mut save = false;
+
+        ui.horizontal(|ui| {
+            if modes.len() > 1 {
+                for &option in modes {
+                    if ui.selectable_label(mode == option, option.label()).clicked() {
+                        self.mode = option;
+                    }
+                }
+                if mode == ViewMode::Pretty {
+                    ui.add_space(10.0);
+                    if ui.small_button("Expand all").clicked() {
+                        self.folded.clear();
+                        self.refresh();
+                    }
+                    if ui.small_button("Collapse all").clicked() {
+                        let lines = &self.current().lines;
+                        self.folded = (0..lines.len()).filter(|&i| lines[i].depth > 0 && lines[i].fold.is_some()).collect();
+                        self.refresh();
+                    }
+                }
+                ui.add_space(10.0);
+            }
+            if let Some(bytes) = &self.doc.bytes {
+                let info = match &self.doc.image {
+                    Some(image) => format!("{} image, {} × {}, {} bytes", image.format, image.width, image.height, bytes.len()),
+                    None if self.doc.content_type.is_empty() => format!("{} bytes", bytes.len()),
+                    None => format!("{}, {} bytes", self.doc.content_type, bytes.len()),
+                };
+                ui.label(egui::RichText::new(info).size(12.0).color(egui::Color32::GRAY));
+                ui.add_space(10.0);
+            } else if ui.small_button("Copy").clicked() {
+                let shown = self.filtered.as_ref().map(|(text, _)| text.as_str()).unwrap_or(text);
+                ui.output_mut(|o| o.copied_text = shown.to_string());
+            }
+            save = ui.small_button("Save to file…").clicked();
+        });
+
+        if is_json {
+            ui.add_space(4.0);
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("Filter: $.items[?(@.price > 10)].name  or  .items[] | {id, name}")
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(ui.available_width()),
+            );
+            if response.changed() {
+                self.filter_due = Some(Instant::now() + FILTER_DELAY);
+            }
+            if self.filtering.is_some() {
+                ui.spinner();
+            } else if !self.filter_error.is_empty() {
+                ui.label(egui::RichText::new(&self.filter_error).size(12.0).color(egui::Color32::from_rgb(255, 80, 80)));
+            }
+        }
+
+        if self.find.open && matches!(mode, ViewMode::Pretty | ViewMode::Raw) {
+            ui.add_space(4.0);
+            self.find.show_bar(ui);
+            let doc = self.filtered.as_ref().map(|(_, doc)| doc).unwrap_or(&self.doc);
+            let shown = self.filtered.as_ref().map(|(text, _)| text.as_str()).unwrap_or(text);
+            let version = self.generation * 2 + (mode == ViewMode::Raw) as u64;
+            self.find.update(version, |re| match mode {
+                ViewMode::Raw => search_raw(re, shown, &doc.raw_rows),
+                _ => search_pretty(re, &doc.lines),
+            });
+        }
+        ui.add_space(4.0);
+
+        match mode {
+            ViewMode::Pretty => self.show_pretty(ui),
+            ViewMode::Raw => self.show_raw(ui, text),
+            ViewMode::Tree => self.show_tree(ui),
+            ViewMode::Preview => self.show_image(ui),
+            ViewMode::Hex => self.show_hex(ui),
+        }
+        save
+    }
+
+    fn show_pretty(&mut self, ui: &mut egui::Ui) {
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let digits = self.current().lines.len().to_string().len();
+        let mut toggle = None;
+
+        let mut area = egui::ScrollArea::both().id_source("response_pretty").auto_shrink([false; 2]);
+        if let Some((row, range)) = self.find.take_moved() {
+            // Unfold whatever hides the match
+            let lines = &self.filtered.as_ref().map(|(_, doc)| doc).unwrap_or(&self.doc).lines;
+            let before = self.folded.len();
+            self.folded.retain(|&f| !matches!(lines[f].fold, Some((close, _)) if f < row && row <= close));
+            if self.folded.len() != before {
+                self.refresh();
+            }
+            let position = self.visible.binary_search(&row).unwrap_or_else(|p| p);
+            let column = digits + 3 + line_text(&self.current().lines[row])[..range.start].chars().count();
+            area = area.scroll_offset(scroll_target(ui, &font, position, column));
+        }
+
+        let doc = self.current();
+        area.show_rows(ui, row_height, self.visible.len(), |ui, rows| {
+                for &index in &self.visible[rows] {
+                    let line = &doc.lines[index];
+                    let folded = self.folded.contains(&index);
+
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 0.0;
+                        ui.label(egui::RichText::new(format!("{:>width$} ", index + 1, width = digits)).font(font.clone()).color(egui::Color32::DARK_GRAY));
+
+                        let marker = match (line.fold, folded) {
+                            (Some(_), true) => "▸ ",
+                            (Some(_), false) => "▾ ",
+                            (None, _) => "  ",
+                        };
+                        let marker = ui.add(egui::Label::new(egui::RichText::new(marker).font(font.clone()).color(egui::Color32::GRAY)).sense(egui::Sense::click()));
+                        if line.fold.is_some() && marker.clicked() {
+                            toggle = Some(index);
+                        }
+
+                        let mut job = egui::text::LayoutJob::default();
+                        append(&mut job, &font, &"  ".repeat(line.depth), Token::Punct.color());
+                        for (token, text) in &line.tokens {
+                            append(&mut job, &font, text, token.color());
+                        }
+                        find::mark(&mut job, &self.find.in_row(index));
+                        if let (Some((close, count)), true) = (line.fold, folded) {
+                            append(&mut job, &font, " … ", egui::Color32::GRAY);
+                            for (token, text) in &doc.lines[close].tokens {
+                                append(&mut job, &font, text, token.color());
+                            }
+                            let noun = match line.tokens.last().map(|(_, t)| t.as_str()) {
+                                Some("{") => "keys",
+                                Some("[") => "items",
+                                _ => "children",
+                            };
+                            append(&mut job, &font, &format!("  {} {}", count, noun), egui::Color32::DARK_GRAY);
+                        }
+                        ui.add(egui::Label::new(job).wrap(false));
+                    });
+                }
+            });
+
+        if let Some(index) = toggle {
+            if !self.folded.remove(&index) {
+                self.folded.insert(index);
+            }
+            self.refresh();
+        }
+    }
+
+    /// `text` is the full body; a filtered result brings its own.
+    fn show_raw(&mut self, ui: &mut egui::Ui, text: &str) {
+        let text = self.filtered.as_ref().map(|(text, _)| text.as_str()).unwrap_or(text);
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = &self.filtered.as_ref().map(|(_, doc)| doc).unwrap_or(&self.doc).raw_rows;
+        let digits = rows.last().and_then(|row| row.number).unwrap_or(1).to_string().len();
+
+        let mut area = egui::ScrollArea::both().id_source("response_raw").auto_shrink([false; 2]);
+        if let Some((row, range)) = self.find.take_moved() {
+            let start = rows[row].range.start;
+            let column = digits + 3 + text[start..start + range.start].chars().count();
+            area = area.scroll_offset(scroll_target(ui, &font, row, column));
+        }
+
+        area.show_rows(ui, row_height, rows.len(), |ui, range| {
+            for (index, row) in rows.iter().enumerate().skip(range.start).take(range.len()) {
+                let number = row.number.map(|n| n.to_string()).unwrap_or_default();
+                let prefix = format!("{:>width$}   ", number, width = digits);
+                let mut job = egui::text::LayoutJob::default();
+                append(&mut job, &font, &prefix, egui::Color32::DARK_GRAY);
+                append(&mut job, &font, text[row.range.clone()].trim_end_matches('\r'), ui.visuals().text_color());
+                let marks: Vec<_> = self.find.in_row(index).into_iter().map(|(r, current)| (r.start + prefix.len()..r.end + prefix.len(), current)).collect();
+                find::mark(&mut job, &marks);
+                ui.add(egui::Label::new(job).wrap(false));
+            }
+        });
+    }
+
+    /// The image at its own size, or scaled down to fit the width.
+    fn show_image(&mut self, ui: &mut egui::Ui) {
+        let Some(image) = &self.doc.image else {
+            return;
+        };
+        let texture = self
+            .texture
+            .get_or_insert_with(|| ui.ctx().load_texture("response_image", image.pixels.clone(), egui::TextureOptions::LINEAR));
+        let size = texture.size_vec2();
+        let scale = (ui.available_width() / size.x).min(1.0);
+        egui::ScrollArea::both().id_source("response_image").auto_shrink([false; 2]).show(ui, |ui| {
+            ui.add(egui::Image::new(egui::load::SizedTexture::new(texture.id(), size * scale)));
+        });
+    }
+
+    /// Offset, 16 bytes in hex and the same as ASCII, per row.
+    fn show_hex(&self, ui: &mut egui::Ui) {
+        let Some(bytes) = &self.doc.bytes else {
+            return;
+        };
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let area = egui::ScrollArea::both().id_source("response_hex").auto_shrink([false; 2]);
+        area.show_rows(ui, row_height, bytes.len().div_ceil(16), |ui, rows| {
+            for row in rows {
+                let chunk = &bytes[row * 16..(row * 16 + 16).min(bytes.len())];
+                let mut hex = String::with_capacity(50);
+                for (i, byte) in chunk.iter().enumerate() {
+                    hex.push_str(&format!("{:02x} ", byte));
+                    if i == 7 {
+                        hex.push(' ');
+                    }
+                }
+                let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+
+                let mut job = egui::text::LayoutJob::default();
+                append(&mut job, &font, &format!("{:08x}  ", row * 16), egui::Color32::DARK_GRAY);
+                append(&mut job, &font, &format!("{:<50}", hex), ui.visuals().text_color());
+                append(&mut job, &font, &format!("|{}|", ascii), Token::String.color());
+                ui.add(egui::Label::new(job).wrap(false));
+            }
+        });
+    }
+
+    fn show_tree(&self, ui: &mut egui::Ui) {
+        let Some(json) = &self.current().json else {
+            return;
+        };
+        egui::ScrollArea::both().id_source("response_tree").auto_shrink([false; 2]).show(ui, |ui| {
+            tree_node(ui, "$", json, egui::Id::new(("response_tree", self.generation)), true);
+        });
+    }
+}
+
+/// What a pretty line reads as, without its gutter.
+fn line_text(line: &Line) -> String {
+    let mut text = "  ".repeat(line.depth);
+    for (_, token) in &line.tokens {
+        text.push_str(token);
+    }
+    text
+}
+
+fn search_pretty(re: &regex::Regex, lines: &[Line]) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let text = line_text(line);
+        for m in re.find_iter(&text).filter(|m| !m.is_empty()) {
+            matches.push((i, m.range(), matches.len()));
+        }
+        if matches.len() >= find::MAX_MATCHES {
+            break;
+        }
+    }
+    matches
+}
+
+/// Searches the whole text, so matches across split rows are found, then
+/// cuts each match at row boundaries.
+fn search_raw(re: &regex::Regex, text: &str, rows: &[RawRow]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for (index, m) in re.find_iter(text).filter(|m| !m.is_empty()).take(find::MAX_MATCHES).enumerate() {
+        let mut i = rows.partition_point(|row| row.range.end <= m.start());
+        while i < rows.len() && rows[i].range.start < m.end() {
+            let row = &rows[i].range;
+            let (start, end) = (m.start().max(row.start), m.end().min(row.end));
+            if start < end {
+                matches.push((i, start - row.start..end - row.start, index));
+            }
+            i += 1;
+        }
+    }
+    matches
+}
+
+/// A scroll offset that puts `row` and `column` of a monospace view near
+/// the middle of the visible area.
+fn scroll_target(ui: &egui::Ui, font: &egui::FontId, row: usize, column: usize) -> egui::Vec2 {
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace) + ui.spacing().item_spacing.y;
+    let char_width = ui.fonts(|f| f.glyph_width(font, '0'));
+    let size = ui.available_size();
+    let x = column as f32 * char_width;
+    egui::vec2(
+        if x < size.x * 0.8 { 0.0 } else { x - size.x / 2.0 },
+        (row as f32 * row_height - size.y / 2.0).max(0.0),
+    )
+}
+
+fn append(job: &mut egui::text::LayoutJob, font: &egui::FontId, text: &str, color: egui::Color32) {
+    job.append(text, 0.0, egui::TextFormat::simple(font.clone(), color));
+}
+
+fn tree_node(ui: &mut egui::Ui, key: &str, value: &Value, id: egui::Id, open: bool) {
+    let font = egui::TextStyle::Monospace.resolve(ui.style());
+    let mut job = egui::text::LayoutJob::default();
+    append(&mut job, &font, key, Token::Key.color());
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            append(&mut job, &font, &format!("  {{{}}}", map.len()), egui::Color32::GRAY);
+            egui::CollapsingHeader::new(job).id_source(id).default_open(open).show(ui, |ui| {
+                tree_children(ui, id, map.len(), map.iter().map(|(k, v)| (k.clone(), v)));
+            });
+        }
+        Value::Array(items) if !items.is_empty() => {
+            append(&mut job, &font, &format!("  [{}]", items.len()), egui::Color32::GRAY);
+            egui::CollapsingHeader::new(job).id_source(id).default_open(open).show(ui, |ui| {
+                tree_children(ui, id, items.len(), items.iter().enumerate().map(|(i, v)| (i.to_string(), v)));
+            });
+        }
+        scalar => {
+            let (token, text) = scalar_token(scalar);
+            append(&mut job, &font, ": ", Token::Punct.color());
+            append(&mut job, &font, &text, token.color());
+            ui.add(egui::Label::new(job).wrap(false));
+        }
+    }
+}
+
+/// Lists children a page at a time, so opening a huge array stays cheap.
+fn tree_children<'a>(ui: &mut egui::Ui, id: egui::Id, total: usize, children: impl Iterator<Item = (String, &'a Value)>) {
+    let shown_id = id.with("shown");
+    let shown = ui.data(|d| d.get_temp::<usize>(shown_id)).unwrap_or(TREE_PAGE);
+
+    for (i, (key, child)) in children.take(shown).enumerate() {
+        tree_node(ui, &key, child, id.with(i), false);
+    }
+    if total > shown && ui.small_button(format!("Show {} more of {}", TREE_PAGE.min(total - shown), total - shown)).clicked() {
+        ui.data_mut(|d| d.insert_temp(shown_id, shown + TREE_PAGE));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(text: &str, content_type: &str) -> Vec<String> {
+        let doc = Document::new(text, content_type);
+        doc.lines.iter().map(|line| line.tokens.iter().map(|(_, token)| token.as_str()).collect()).collect()
+    }
+
+    #[test]
+    fn html_with_non_ascii_text() {
+        assert_eq!(rendered("<p>élan</p>", "text/html"), ["<p>élan</p>"]);
+        assert_eq!(
+            rendered("<ul><li>日本語</li>ü<br></ul>", "text/html; charset=utf-8"),
+            ["<ul>", "<li>日本語</li>", "ü", "<br>", "</ul>"]
+        );
+    }
+
+    #[test]
+    fn xml_with_non_ascii_text() {
+        assert_eq!(
+            rendered("\u{feff}<?xml version=\"1.0\"?><a>ä<b>Ωmega</b></a>", "application/xml"),
+            ["\u{feff}", "<?xml version=\"1.0\"?>", "<a>", "ä", "<b>Ωmega</b>", "</a>"]
+        );
+        assert_eq!(rendered("<a>\n  naïve\n  café\n</a>", "text/xml"), ["<a>", "naïve", "café", "</a>"]);
+    }
+}
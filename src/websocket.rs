@@ -0,0 +1,670 @@
+use eframe::egui;
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::HandshakeError;
+use tungstenite::http::header::{HeaderName, HeaderValue};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Error, Message};
+
+/// Older log entries are dropped past this.
+const MAX_ENTRIES: usize = 50_000;
+/// Binary payloads are shown as hex up to this many bytes.
+const HEX_PREVIEW_BYTES: usize = 256;
+/// How long each address gets to accept the TCP connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the TLS and WebSocket handshakes may take together.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long to wait for the server to answer our close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Sent,
+    Received,
+    /// Connection events and errors.
+    Note,
+}
+
+/// One line of the message log.
+struct Entry {
+    time: String,
+    direction: Direction,
+    /// "text", "binary", "ping", "pong", "close" or empty for notes.
+    kind: &'static str,
+    text: String,
+}
+
+/// From the connection thread.
+enum Update {
+    /// The handshake response's status and headers.
+    Open(String, Vec<(String, String)>),
+    Entry(Entry),
+    /// Why the connection ended.
+    Closed(String),
+}
+
+/// To the connection thread.
+enum Outgoing {
+    Message(Message),
+    Close,
+}
+
+enum State {
+    Connecting,
+    Open,
+    Closed(String),
+}
+
+/// A WebSocket connection and its log. Dropping it closes the connection.
+pub struct Session {
+    tx: mpsc::Sender<Outgoing>,
+    rx: mpsc::Receiver<Update>,
+    stop: Arc<AtomicBool>,
+    state: State,
+    started: Instant,
+    /// Whether the handshake ever succeeded.
+    opened: bool,
+    log: Vec<Entry>,
+    dropped: usize,
+    handshake: Option<(String, Vec<(String, String)>)>,
+    /// Set when it closed and `poll` hasn't said so yet.
+    ended: bool,
+    follow: bool,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%H:%M:%S%.3f").to_string()
+}
+
+fn note(text: impl Into<String>) -> Update {
+    Update::Entry(Entry {
+        time: now(),
+        direction: Direction::Note,
+        kind: "",
+        text: text.into(),
+    })
+}
+
+/// Opens a connection to a ws:// or wss:// URL on a background thread.
+pub fn connect(url: &str, headers: &[(String, String)], insecure: bool, ctx: &egui::Context) -> Session {
+    let (tx, out_rx) = mpsc::channel();
+    let (update_tx, rx) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let worker_stop = stop.clone();
+    let url = url.trim().to_string();
+    let headers = headers.to_vec();
+    let ctx = ctx.clone();
+
+    thread::spawn(move || {
+        let send = |update: Update| {
+            update_tx.send(update).ok();
+            ctx.request_repaint();
+        };
+        send(note(format!("Connecting to {}", url)));
+        let end = match run(&url, &headers, insecure, &out_rx, &worker_stop, &send) {
+            Ok(end) => end,
+            Err(e) => {
+                send(note(format!("Error: {}", e)));
+                format!("Error: {}", e)
+            }
+        };
+        send(Update::Closed(end));
+    });
+
+    Session {
+        tx,
+        rx,
+        stop,
+        state: State::Connecting,
+        started: Instant::now(),
+        opened: false,
+        log: Vec::new(),
+        dropped: 0,
+        handshake: None,
+        ended: false,
+        follow: true,
+    }
+}
+
+/// The connection thread: handshake, then alternate between sending what
+/// the UI queued and reading with a short timeout. Returns how it ended.
+fn run(url: &str, headers: &[(String, String)], insecure: bool, out_rx: &mpsc::Receiver<Outgoing>, stop: &AtomicBool, send: &dyn Fn(Update)) -> Result<String, String> {
+    let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+    for (key, value) in headers {
+        let name = HeaderName::from_bytes(key.as_bytes()).map_err(|_| format!("Invalid header name: {}", key))?;
+        let value = HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header {}", key))?;
+        request.headers_mut().append(name, value);
+    }
+
+    let uri = request.uri();
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_string();
+    let tls = match uri.scheme_str() {
+        Some("wss") => true,
+        Some("ws") => false,
+        _ => return Err("Use a ws:// or wss:// URL".to_string()),
+    };
+    let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+    let tcp = connect_tcp(&host, port, stop)?;
+
+    // The handshakes run non-blocking so that a cancel or a server that
+    // never answers doesn't leave this thread stuck
+    tcp.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    let wait = || {
+        if stop.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        if Instant::now() > deadline {
+            return Err("Timed out during the handshake".to_string());
+        }
+        thread::sleep(Duration::from_millis(10));
+        Ok(())
+    };
+
+    let stream = if tls {
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(insecure)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut result = connector.connect(&host, tcp);
+        loop {
+            match result {
+                Ok(stream) => break MaybeTlsStream::NativeTls(stream),
+                Err(native_tls::HandshakeError::WouldBlock(mid)) => {
+                    wait()?;
+                    result = mid.handshake();
+                }
+                Err(native_tls::HandshakeError::Failure(e)) => return Err(e.to_string()),
+            }
+        }
+    } else {
+        MaybeTlsStream::Plain(tcp)
+    };
+
+    let mut result = tungstenite::client(request, stream);
+    let (mut socket, response) = loop {
+        match result {
+            Ok(connected) => break connected,
+            Err(HandshakeError::Interrupted(mid)) => {
+                wait()?;
+                result = mid.handshake();
+            }
+            Err(HandshakeError::Failure(e)) => return Err(e.to_string()),
+        }
+    };
+
+    let status = response.status();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    send(Update::Open(format!("{} {}", status.as_u16(), status.canonical_reason().unwrap_or_default()), headers));
+    send(note("Connected"));
+
+    // Reads time out so queued messages and a stop get a look in
+    let tcp = match socket.get_mut() {
+        MaybeTlsStream::Plain(stream) => stream,
+        MaybeTlsStream::NativeTls(stream) => stream.get_mut(),
+        _ => return Err("Unsupported stream".to_string()),
+    };
+    tcp.set_nonblocking(false)
+        .and_then(|()| tcp.set_read_timeout(Some(Duration::from_millis(50))))
+        .map_err(|e| e.to_string())?;
+
+    // When our close frame went out; the server gets `CLOSE_TIMEOUT` to answer
+    let mut close_sent: Option<Instant> = None;
+    let mut close_received = None;
+    loop {
+        match close_sent {
+            None if stop.load(Ordering::Relaxed) => {
+                socket.close(None).ok();
+                return Ok("Closed".to_string());
+            }
+            Some(sent) if sent.elapsed() > CLOSE_TIMEOUT => {
+                return Ok("Closed, the server didn't answer the close frame".to_string());
+            }
+            _ => {}
+        }
+        while let Ok(outgoing) = out_rx.try_recv() {
+            let entry = match outgoing {
+                Outgoing::Message(message) => {
+                    let entry = describe(&message, Direction::Sent);
+                    socket.send(message).map_err(|e| e.to_string())?;
+                    entry
+                }
+                Outgoing::Close => {
+                    close_sent = Some(Instant::now());
+                    socket
+                        .close(Some(CloseFrame {
+                            code: CloseCode::Normal,
+                            reason: "".into(),
+                        }))
+                        .map_err(|e| e.to_string())?;
+                    Some(Entry {
+                        time: now(),
+                        direction: Direction::Sent,
+                        kind: "close",
+                        text: close_text(1000, ""),
+                    })
+                }
+            };
+            if let Some(entry) = entry {
+                send(Update::Entry(entry));
+            }
+        }
+
+        match socket.read() {
+            Ok(message) => {
+                if let Message::Close(frame) = &message {
+                    close_received = Some(frame.as_ref().map_or("No status".to_string(), |f| close_text(f.code.into(), &f.reason)));
+                }
+                if let Some(entry) = describe(&message, Direction::Received) {
+                    send(Update::Entry(entry));
+                }
+                // A ping is answered automatically
+                if let Message::Ping(payload) = message {
+                    send(Update::Entry(Entry {
+                        time: now(),
+                        direction: Direction::Sent,
+                        kind: "pong",
+                        text: payload_text(&payload),
+                    }));
+                }
+            }
+            Err(Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                socket.flush().ok();
+            }
+            Err(Error::ConnectionClosed) | Err(Error::AlreadyClosed) => {
+                return Ok(match close_received {
+                    Some(close) => format!("Closed {}", close),
+                    None => "Closed".to_string(),
+                });
+            }
+            Err(e) => {
+                return Ok(match close_received {
+                    Some(close) => format!("Closed {}", close),
+                    None => format!("Connection lost: {}", e),
+                })
+            }
+        }
+    }
+}
+
+/// Connects to the first of the host's addresses that answers.
+fn connect_tcp(host: &str, port: u16, stop: &AtomicBool) -> Result<TcpStream, String> {
+    let mut error = format!("No address found for {}", host);
+    for addr in (host, port).to_socket_addrs().map_err(|e| e.to_string())? {
+        if stop.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => error = e.to_string(),
+        }
+    }
+    Err(error)
+}
+
+/// A log entry for a message, or None for raw frames.
+fn describe(message: &Message, direction: Direction) -> Option<Entry> {
+    let (kind, text) = match message {
+        Message::Text(text) => ("text", text.clone()),
+        Message::Binary(bytes) => ("binary", hex_preview(bytes)),
+        Message::Ping(payload) => ("ping", payload_text(payload)),
+        Message::Pong(payload) => ("pong", payload_text(payload)),
+        Message::Close(frame) => ("close", frame.as_ref().map_or("No status".to_string(), |f| close_text(f.code.into(), &f.reason))),
+        Message::Frame(_) => return None,
+    };
+    Some(Entry {
+        time: now(),
+        direction,
+        kind,
+        text,
+    })
+}
+
+fn payload_text(payload: &[u8]) -> String {
+    match std::str::from_utf8(payload) {
+        Ok(text) => text.to_string(),
+        Err(_) => hex_preview(payload),
+    }
+}
+
+fn hex_preview(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().take(HEX_PREVIEW_BYTES).map(|b| format!("{:02x}", b)).collect();
+    let more = if bytes.len() > HEX_PREVIEW_BYTES { " …" } else { "" };
+    format!("{}{} ({} bytes)", hex.join(" "), more, bytes.len())
+}
+
+/// "1000 (Normal closure): reason"
+fn close_text(code: u16, reason: &str) -> String {
+    let name = match code {
+        1000 => "Normal closure",
+        1001 => "Going away",
+        1002 => "Protocol error",
+        1003 => "Unsupported data",
+        1005 => "No status",
+        1006 => "Abnormal closure",
+        1007 => "Invalid payload",
+        1008 => "Policy violation",
+        1009 => "Message too big",
+        1010 => "Missing extension",
+        1011 => "Internal error",
+        1012 => "Service restart",
+        1013 => "Try again later",
+        _ => "",
+    };
+    let mut text = if name.is_empty() { code.to_string() } else { format!("{} ({})", code, name) };
+    if !reason.is_empty() {
+        text.push_str(&format!(": {}", reason));
+    }
+    text
+}
+
+/// Parses hex like "01 ff a0" or "01ffa0" for binary messages.
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text.split_whitespace().collect();
+    let digits = digits.strip_prefix("0x").unwrap_or(&digits);
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex digit: {}", c));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("Hex needs an even number of digits".to_string());
+    }
+    Ok((0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap_or_default()).collect())
+}
+
+impl Session {
+    /// Connecting or open.
+    pub fn is_live(&self) -> bool {
+        !matches!(self.state, State::Closed(_))
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(self.state, State::Open)
+    }
+
+    pub fn opened(&self) -> bool {
+        self.opened
+    }
+
+    /// Time since connecting started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The handshake response's status and headers, once after connecting.
+    pub fn take_handshake(&mut self) -> Option<(String, Vec<(String, String)>)> {
+        self.handshake.take()
+    }
+
+    /// Sends a close frame, or gives up on a connection still being made.
+    pub fn close(&mut self) {
+        match self.state {
+            State::Open => {
+                self.tx.send(Outgoing::Close).ok();
+            }
+            State::Connecting => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.state = State::Closed("Cancelled".to_string());
+                self.ended = true;
+            }
+            State::Closed(_) => {}
+        }
+    }
+
+    /// Takes what the connection thread sent. Returns true once after the
+    /// connection closed.
+    pub fn poll(&mut self) -> bool {
+        while let Ok(update) = self.rx.try_recv() {
+            match update {
+                Update::Open(status, headers) => {
+                    self.state = State::Open;
+                    self.opened = true;
+                    self.handshake = Some((status, headers));
+                }
+                Update::Entry(entry) => self.log.push(entry),
+                Update::Closed(end) => {
+                    if self.is_live() {
+                        self.state = State::Closed(end);
+                        self.ended = true;
+                    }
+                }
+            }
+        }
+        if self.log.len() > MAX_ENTRIES {
+            let excess = self.log.len() - MAX_ENTRIES;
+            self.log.drain(..excess);
+            self.dropped += excess;
+        }
+        std::mem::take(&mut self.ended)
+    }
+
+    /// The log as plain text, for history.
+    pub fn transcript(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.log {
+            let arrow = match entry.direction {
+                Direction::Sent => "→",
+                Direction::Received => "←",
+                Direction::Note => "•",
+            };
+            let kind = if entry.kind.is_empty() { String::new() } else { format!("[{}] ", entry.kind) };
+            text.push_str(&format!("{} {} {}{}\n", entry.time, arrow, kind, entry.text));
+        }
+        text
+    }
+
+    /// The message log, newest at the bottom.
+    pub fn show_log(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let (state, color) = match &self.state {
+                State::Connecting => ("Connecting...".to_string(), egui::Color32::GRAY),
+                State::Open => ("Connected".to_string(), egui::Color32::from_rgb(0, 200, 120)),
+                State::Closed(end) if end.starts_with("Error") || end.starts_with("Connection lost") => (end.clone(), egui::Color32::from_rgb(255, 80, 80)),
+                State::Closed(end) => (end.clone(), egui::Color32::GRAY),
+            };
+            if self.is_live() {
+                ui.spinner();
+            }
+            ui.label(egui::RichText::new(state).size(12.0).color(color));
+            ui.add_space(10.0);
+            let dropped = if self.dropped > 0 { format!(", oldest {} dropped", self.dropped) } else { String::new() };
+            ui.label(egui::RichText::new(format!("{} entries{}", self.log.len() + self.dropped, dropped)).size(12.0).color(egui::Color32::GRAY));
+            ui.add_space(10.0);
+            ui.toggle_value(&mut self.follow, "Follow").on_hover_text("Keep the newest message in view");
+        });
+        ui.add_space(4.0);
+
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .id_source("websocket_log")
+            .auto_shrink([false; 2])
+            .stick_to_bottom(self.follow)
+            .show_rows(ui, row_height, self.log.len(), |ui, rows| {
+                for entry in &self.log[rows] {
+                    let (arrow, color) = match entry.direction {
+                        Direction::Sent => ("→ ", egui::Color32::from_rgb(100, 180, 255)),
+                        Direction::Received => ("← ", egui::Color32::from_rgb(0, 200, 120)),
+                        Direction::Note => ("• ", egui::Color32::GRAY),
+                    };
+                    let mut job = egui::text::LayoutJob::default();
+                    let mut append = |text: &str, color: egui::Color32| {
+                        job.append(text, 0.0, egui::TextFormat::simple(font.clone(), color));
+                    };
+                    append(&format!("{}  ", entry.time), egui::Color32::DARK_GRAY);
+                    append(arrow, color);
+                    if !entry.kind.is_empty() {
+                        append(&format!("[{}] ", entry.kind), egui::Color32::from_rgb(200, 150, 255));
+                    }
+                    let text_color = if entry.direction == Direction::Note { egui::Color32::GRAY } else { ui.visuals().text_color() };
+                    append(&entry.text.replace('\n', " ⏎ "), text_color);
+
+                    let hover = if entry.text.contains('\n') { entry.text.as_str() } else { "Click to copy" };
+                    if ui.add(egui::Label::new(job).wrap(false).sense(egui::Sense::click())).on_hover_text(hover).clicked() {
+                        ui.output_mut(|o| o.copied_text = entry.text.clone());
+                    }
+                }
+            });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MessageKind {
+    Text,
+    Json,
+    Binary,
+}
+
+/// The message being written, kept per tab across connections.
+pub struct Composer {
+    text: String,
+    kind: MessageKind,
+    error: String,
+}
+
+impl Default for Composer {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            kind: MessageKind::Text,
+            error: String::new(),
+        }
+    }
+}
+
+impl Composer {
+    /// Queues the message on `session`. JSON must parse and binary is
+    /// written as hex.
+    pub fn send(&mut self, session: &mut Session) {
+        let message = match self.kind {
+            MessageKind::Text => Ok(Message::Text(self.text.clone())),
+            MessageKind::Json => serde_json::from_str::<serde_json::Value>(&self.text)
+                .map(|_| Message::Text(self.text.clone()))
+                .map_err(|e| format!("Invalid JSON: {}", e)),
+            MessageKind::Binary => parse_hex(&self.text).map(Message::Binary),
+        };
+        match message {
+            Ok(message) => {
+                self.error.clear();
+                session.tx.send(Outgoing::Message(message)).ok();
+            }
+            Err(e) => self.error = e,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, session: Option<&mut Session>) {
+        let open = session.as_ref().is_some_and(|s| s.is_open());
+        ui.horizontal(|ui| {
+            ui.label("Message:");
+            ui.selectable_value(&mut self.kind, MessageKind::Text, "Text");
+            ui.selectable_value(&mut self.kind, MessageKind::Json, "JSON");
+            ui.selectable_value(&mut self.kind, MessageKind::Binary, "Binary (hex)");
+        });
+
+        let hint = match self.kind {
+            MessageKind::Text => "Hello",
+            MessageKind::Json => "{\"type\": \"subscribe\"}",
+            MessageKind::Binary => "01 ff a0",
+        };
+        ui.add(
+            egui::TextEdit::multiline(&mut self.text)
+                .hint_text(hint)
+                .font(egui::TextStyle::Monospace)
+                .desired_width(f32::INFINITY)
+                .desired_rows(4),
+        );
+        if !self.error.is_empty() {
+            ui.label(egui::RichText::new(&self.error).size(12.0).color(egui::Color32::from_rgb(255, 80, 80)));
+        }
+
+        ui.horizontal(|ui| {
+            let send = ui.add_enabled(open, egui::Button::new("Send message")).on_hover_text("Cmd+Enter");
+            let ping = ui.add_enabled(open, egui::Button::new("Ping"));
+            if !open {
+                ui.label(egui::RichText::new("Connect to send messages").size(12.0).color(egui::Color32::GRAY));
+            }
+            if let Some(session) = session {
+                if send.clicked() {
+                    self.send(session);
+                }
+                if ping.clicked() {
+                    session.tx.send(Outgoing::Message(Message::Ping(Vec::new()))).ok();
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn hex() {
+        let cases: &[(&str, Result<Vec<u8>, String>)] = &[
+            ("0a ff", Ok(vec![0x0a, 0xff])),
+            ("0x0AFF", Ok(vec![0x0a, 0xff])),
+            ("  ", Ok(vec![])),
+            ("abc", Err("Hex needs an even number of digits".to_string())),
+            ("zz", Err("Invalid hex digit: z".to_string())),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(&parse_hex(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn close_texts() {
+        let cases = [
+            (1000, "", "1000 (Normal closure)"),
+            (1001, "bye", "1001 (Going away): bye"),
+            (4000, "x", "4000: x"),
+            (4000, "", "4000"),
+        ];
+        for (code, reason, expected) in cases {
+            assert_eq!(close_text(code, reason), expected);
+        }
+    }
+
+    #[test]
+    fn cancel_during_handshake() {
+        // Accepts the connection but never answers the upgrade request
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (_out_tx, out_rx) = mpsc::channel::<Outgoing>();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = stop.clone();
+        let worker = thread::spawn(move || run(&url, &[], false, &out_rx, &worker_stop, &|_| {}));
+
+        let _conn = listener.accept().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let cancelled = Instant::now();
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(worker.join().unwrap(), Err("Cancelled".to_string()));
+        assert!(cancelled.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rejects_other_schemes() {
+        let (_out_tx, out_rx) = mpsc::channel::<Outgoing>();
+        let result = run("http://localhost", &[], false, &out_rx, &AtomicBool::new(false), &|_| {});
+        assert!(result.is_err());
+    }
+}